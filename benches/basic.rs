@@ -11,11 +11,11 @@ use std::collections::HashMap;
 struct X(u64, u64);
 
 impl Record<u64, u64> for X {
-    fn chunk_key(&self) -> Cow<u64> {
+    fn chunk_key(&self) -> Cow<'_, u64> {
         Cow::Owned((self.0 & 0x00F0) >> 4)
     }
 
-    fn item_key(&self) -> Cow<u64> {
+    fn item_key(&self) -> Cow<'_, u64> {
         Cow::Borrowed(&self.0)
     }
 }
@@ -116,7 +116,7 @@ fn bench_query_even_integers_in_chunks(storage: &Storage<u64, u64, X>) {
 }
 
 fn bench_modify_even_integers(storage: &mut Storage<u64, u64, X>) {
-    storage.modify(&Everything.filter(|x: &X| x.1 % 2 == 0), |mut editor| {
+    storage.modify(Everything.filter(|x: &X| x.1 % 2 == 0), |mut editor| {
         editor.get_mut().1 += 1;
     });
 
@@ -129,7 +129,7 @@ fn bench_modify_even_integers(storage: &mut Storage<u64, u64, X>) {
 }
 
 fn bench_remove_even_integers(storage: &mut Storage<u64, u64, X>) {
-    storage.remove(&Everything.filter(|x: &X| x.1 % 2 == 0), std::mem::drop);
+    storage.remove(Everything.filter(|x: &X| x.1 % 2 == 0), std::mem::drop);
 
     let result: Vec<X> = storage
         .query(&Everything.filter(|x: &X| x.1 % 2 == 0))
@@ -139,7 +139,7 @@ fn bench_remove_even_integers(storage: &mut Storage<u64, u64, X>) {
 }
 
 fn bench_discard_even_integers(storage: &mut Storage<u64, u64, X>) {
-    storage.remove(&Everything.filter(|x: &X| x.1 % 2 == 0), std::mem::drop);
+    storage.remove(Everything.filter(|x: &X| x.1 % 2 == 0), std::mem::drop);
 
     let result: Vec<X> = storage
         .query(&Everything.filter(|x: &X| x.1 % 2 == 0))
@@ -163,10 +163,10 @@ fn bench_build_secondary_index(
 fn bench_build_secondary_index_first_time(
     storage: &Storage<u64, u64, X>,
 ) -> SecondaryIndex<u64, X, Option<()>, ()> {
-    let mut secondary = bench_build_secondary_index(storage);
+    let secondary = bench_build_secondary_index(storage);
 
     let count = storage
-        .query(&Everything.matching(&mut secondary, Cow::Owned(())))
+        .query(&Everything.matching(&secondary, Cow::Owned(())))
         .count();
     assert_eq!(10, count);
 
@@ -175,10 +175,10 @@ fn bench_build_secondary_index_first_time(
 
 fn bench_query_secondary_index_next_time(
     storage: &Storage<u64, u64, X>,
-    mut secondary: SecondaryIndex<u64, X, Option<()>, ()>,
+    secondary: SecondaryIndex<u64, X, Option<()>, ()>,
 ) {
     let count = storage
-        .query(&Everything.matching(&mut secondary, Cow::Owned(())))
+        .query(&Everything.matching(&secondary, Cow::Owned(())))
         .count();
     assert_eq!(10, count);
 }
@@ -195,10 +195,10 @@ fn bench_make_changes(storage: &mut Storage<u64, u64, X>) {
 
 fn bench_rebuild_secondary_index_after_change(
     storage: Storage<u64, u64, X>,
-    mut secondary: SecondaryIndex<u64, X, Option<()>, ()>,
+    secondary: SecondaryIndex<u64, X, Option<()>, ()>,
 ) {
     let count = storage
-        .query(&Everything.matching(&mut secondary, Cow::Owned(())))
+        .query(&Everything.matching(&secondary, Cow::Owned(())))
         .count();
     assert_eq!(10, count);
 }
@@ -230,10 +230,10 @@ fn bench_build_reduction_first_time(storage: &Storage<u64, u64, X>) -> Reduction
 }
 
 fn bench_rebuild_reduction_after_change(
-    mut storage: Storage<u64, u64, X>,
+    storage: Storage<u64, u64, X>,
     mut reduction: Reduction<u64, X, u64>,
 ) {
-    assert_eq!(Some(&773050860), reduction.reduce(&mut storage));
+    assert_eq!(Some(&773050860), reduction.reduce(&storage));
 }
 
 fn criterion_benchmark(c: &mut Criterion) {
@@ -243,19 +243,19 @@ fn criterion_benchmark(c: &mut Criterion) {
 
     everything_group.bench_function(
         "bench_add_integers_baseline (baseline of adding integers to a Vec)",
-        |b| b.iter(|| bench_add_integers_baseline()),
+        |b| b.iter(bench_add_integers_baseline),
     );
 
     everything_group.bench_function(
         "bench_hash_integers_baseline (baseline of adding integers to a HashMap)",
-        |b| b.iter(|| bench_hash_integers_baseline()),
+        |b| b.iter(bench_hash_integers_baseline),
     );
 
     everything_group.bench_function("bench_add_integers (39321 add() operations)", |b| {
-        b.iter(|| bench_add_integers())
+        b.iter(bench_add_integers)
     });
 
-    everything_group.bench_function("bench_add_integers_single_chunk (39321 add() operations, but all values happen to be in the same chunk)", |b| b.iter(|| bench_add_integers_single_chunk()));
+    everything_group.bench_function("bench_add_integers_single_chunk (39321 add() operations, but all values happen to be in the same chunk)", |b| b.iter(bench_add_integers_single_chunk));
 
     everything_group.bench_function(
         "bench_iter_integers (1 iter() operation over 39321 elements)",
@@ -285,7 +285,7 @@ fn criterion_benchmark(c: &mut Criterion) {
 
     everything_group.bench_function("bench_modify_even_integers (1 modify(Everything.filter(..)) operation over every other of 39321 elements)", |b| {
         b.iter_batched(
-            || bench_add_integers(),
+            bench_add_integers,
             |mut storage| bench_modify_even_integers(&mut storage),
             BatchSize::LargeInput
         )
@@ -293,7 +293,7 @@ fn criterion_benchmark(c: &mut Criterion) {
 
     everything_group.bench_function("bench_remove_even_integers (1 remove(Everything.filter(..)) operation over every other of 39321 elements)", |b| {
     b.iter_batched(
-     || bench_add_integers(),
+     bench_add_integers,
      |mut storage| bench_remove_even_integers(&mut storage),
      BatchSize::LargeInput
     )
@@ -301,7 +301,7 @@ fn criterion_benchmark(c: &mut Criterion) {
 
     everything_group.bench_function("bench_discard_even_integers (1 discard(Everything.filter(..)) operation over every other of 39321 elements)", |b| {
   b.iter_batched(
-   || bench_add_integers(),
+   bench_add_integers,
    |mut storage| bench_discard_even_integers(&mut storage),
    BatchSize::LargeInput
   )
@@ -17,11 +17,11 @@ fn main() {
     }
 
     impl Record<str, u128> for Spacecraft {
-        fn chunk_key(&self) -> Cow<str> {
+        fn chunk_key(&self) -> Cow<'_, str> {
             Cow::Borrowed(&self.location)
         }
 
-        fn item_key(&self) -> Cow<u128> {
+        fn item_key(&self) -> Cow<'_, u128> {
             Cow::Owned(self.id)
         }
     }
@@ -10,12 +10,12 @@ fn main() {
     struct X(u64);
 
     impl Record<u64, u64> for X {
-        fn chunk_key(&self) -> Cow<u64> {
+        fn chunk_key(&self) -> Cow<'_, u64> {
             let chunk = self.0 >> 16;
             Cow::Owned(chunk)
         }
 
-        fn item_key(&self) -> Cow<u64> {
+        fn item_key(&self) -> Cow<'_, u64> {
             Cow::Owned(self.0)
         }
     }
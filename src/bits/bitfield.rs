@@ -77,6 +77,33 @@ impl Bitfield {
         }
     }
 
+    /// Union of two bitfields, which must cover the same range.
+    pub(crate) fn unite(&self, other: &Bitfield) -> Bitfield {
+        assert!(self.valid());
+        assert!(other.valid());
+        assert_eq!(self.start, other.start);
+        Bitfield {
+            start: self.start,
+            bits: self.bits | other.bits,
+        }
+    }
+
+    /// Remove the contents of another bitfield from this one. If they do not have overlapping ranges, then the result is unchanged.
+    pub(crate) fn subtract(&self, other: &Bitfield) -> Bitfield {
+        assert!(self.valid());
+        assert!(other.valid());
+        Bitfield {
+            start: self.start,
+            bits: {
+                if self.start == other.start {
+                    self.bits & !other.bits
+                } else {
+                    self.bits
+                }
+            },
+        }
+    }
+
     /// Construct a Bitfield from the given Range of indices. This consumes the given indices from the range and adds them to returned Bitfield.
     /// The Bitfield can consume at most `size_of<usize>()` bits, so some portion of the Range is likely to remain afterwards.
     pub(crate) fn from_range(i: &mut Range<usize>) -> Option<Self> {
@@ -111,16 +138,18 @@ impl Bitfield {
     pub(crate) fn clip(mut self, range: &Range<usize>) -> Self {
         assert!(self.valid());
 
-        if range.end < self.start || range.start >= self.start + BITS {
+        let start = self.start();
+
+        if range.end <= start || range.start >= start + BITS {
             self.bits = 0b0;
             return self;
         }
 
-        for i in self.start..range.start {
+        for i in start..range.start.max(start) {
             self.unset(i);
         }
 
-        for i in range.end..self.start + BITS {
+        for i in range.end.min(start + BITS)..start + BITS {
             self.unset(i);
         }
 
@@ -65,7 +65,11 @@ impl Bitset {
             .bits
             .binary_search_by_key(&start_of(i), Bitfield::sort_order)
         {
-            Arc::make_mut(&mut self.bits)[bidx].unset(i);
+            let bits = Arc::make_mut(&mut self.bits);
+            bits[bidx].unset(i);
+            if bits[bidx].bits == 0b0 {
+                bits.remove(bidx);
+            }
         }
     }
 
@@ -121,7 +125,7 @@ impl Bitset {
     /// Set the specific bit position in this Bitset
     pub fn intersect_in_slice(slice: &[Bitfield], bits: &Bitfield) -> Bitfield {
         match slice.binary_search_by_key(&start_of(bits.start()), Bitfield::sort_order) {
-            Ok(bidx) => slice[bidx].intersect(&bits),
+            Ok(bidx) => slice[bidx].intersect(bits),
             Err(_) => Bitfield::new_empty(bits.start()),
         }
     }
@@ -366,6 +370,7 @@ mod test {
         b.unset(27);
 
         assert_eq!(0, b.iter().flatten().count());
+        assert!(b.is_empty());
     }
 
     #[test]
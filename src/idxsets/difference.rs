@@ -0,0 +1,75 @@
+use crate::bits::bitfield::Bitfield;
+use crate::traits::idxset::IdxSet;
+
+/// The difference of two `IdxSets`: every index in `A` that is not also in `B`.
+#[derive(Clone)]
+pub struct Difference<A, B> {
+    a: A,
+    b: B,
+}
+
+/// An iterator over a `Difference`.
+pub struct DifferenceIter<A: IdxSet, B: IdxSet> {
+    a: A::IdxIter,
+    b: B,
+}
+
+impl<A, B> Difference<A, B>
+where
+    A: IdxSet,
+    B: IdxSet,
+{
+    /// Construct the difference of two `IdxSets`.
+    pub fn new(a: A, b: B) -> Self {
+        Difference { a, b }
+    }
+}
+
+impl<A, B> IdxSet for Difference<A, B>
+where
+    A: IdxSet,
+    B: IdxSet,
+{
+    type IdxIter = DifferenceIter<A, B>;
+
+    fn into_idx_iter(self) -> DifferenceIter<A, B> {
+        DifferenceIter {
+            a: self.a.into_idx_iter(),
+            b: self.b,
+        }
+    }
+
+    fn size(&self) -> usize {
+        self.a.size()
+    }
+
+    fn intersect(&self, other: &Bitfield) -> Bitfield {
+        self.a.intersect(other).subtract(&self.b.intersect(other))
+    }
+}
+
+impl<A, B> Iterator for DifferenceIter<A, B>
+where
+    A: IdxSet,
+    B: IdxSet,
+{
+    type Item = Bitfield;
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<Bitfield> {
+        let a = self.a.next()?;
+        Some(a.subtract(&self.b.intersect(&a)))
+    }
+}
+
+impl<A, B> DoubleEndedIterator for DifferenceIter<A, B>
+where
+    A: IdxSet,
+    B: IdxSet,
+{
+    #[inline(always)]
+    fn next_back(&mut self) -> Option<Bitfield> {
+        let a = self.a.next_back()?;
+        Some(a.subtract(&self.b.intersect(&a)))
+    }
+}
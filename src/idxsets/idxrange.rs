@@ -10,13 +10,13 @@ impl Iterator for IdxRange {
     type Item = Bitfield;
 
     fn next(&mut self) -> Option<Bitfield> {
-        Some(Bitfield::from_range(&mut self.0)?)
+        Bitfield::from_range(&mut self.0)
     }
 }
 
 impl DoubleEndedIterator for IdxRange {
     fn next_back(&mut self) -> Option<Bitfield> {
-        Some(Bitfield::from_range_rev(&mut self.0)?)
+        Bitfield::from_range_rev(&mut self.0)
     }
 }
 
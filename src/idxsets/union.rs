@@ -0,0 +1,180 @@
+use crate::bits::bitfield::Bitfield;
+use crate::traits::idxset::IdxSet;
+
+/// The union of two `IdxSets`.
+#[derive(Clone)]
+pub struct Union<A, B> {
+    a: A,
+    b: B,
+}
+
+/// An iterator over a `Union`. Merges the sorted iterators of both `IdxSets`, combining
+/// any `Bitfields` that cover the same range.
+pub struct UnionIter<A: IdxSet, B: IdxSet> {
+    a: A::IdxIter,
+    b: B::IdxIter,
+    a_front: Option<Bitfield>,
+    b_front: Option<Bitfield>,
+    a_back: Option<Bitfield>,
+    b_back: Option<Bitfield>,
+}
+
+impl<A, B> Union<A, B>
+where
+    A: IdxSet,
+    B: IdxSet,
+{
+    /// Construct the union of two `IdxSets`.
+    pub fn new(a: A, b: B) -> Self {
+        Union { a, b }
+    }
+}
+
+impl<A, B> IdxSet for Union<A, B>
+where
+    A: IdxSet,
+    B: IdxSet,
+{
+    type IdxIter = UnionIter<A, B>;
+
+    fn into_idx_iter(self) -> UnionIter<A, B> {
+        UnionIter {
+            a: self.a.into_idx_iter(),
+            b: self.b.into_idx_iter(),
+            a_front: None,
+            b_front: None,
+            a_back: None,
+            b_back: None,
+        }
+    }
+
+    fn size(&self) -> usize {
+        self.a.size() + self.b.size()
+    }
+
+    fn intersect(&self, other: &Bitfield) -> Bitfield {
+        self.a.intersect(other).unite(&self.b.intersect(other))
+    }
+}
+
+impl<A, B> Iterator for UnionIter<A, B>
+where
+    A: IdxSet,
+    B: IdxSet,
+{
+    type Item = Bitfield;
+
+    fn next(&mut self) -> Option<Bitfield> {
+        // Once an iterator runs dry, the element held back by next_back() is the last one left.
+        let a = self
+            .a_front
+            .take()
+            .or_else(|| self.a.next())
+            .or_else(|| self.a_back.take());
+        let b = self
+            .b_front
+            .take()
+            .or_else(|| self.b.next())
+            .or_else(|| self.b_back.take());
+
+        match (a, b) {
+            (Some(a), Some(b)) if a.start() == b.start() => Some(a.unite(&b)),
+            (Some(a), Some(b)) if a.start() < b.start() => {
+                self.b_front = Some(b);
+                Some(a)
+            }
+            (Some(a), Some(b)) => {
+                self.a_front = Some(a);
+                Some(b)
+            }
+            (a, b) => a.or(b),
+        }
+    }
+}
+
+impl<A, B> DoubleEndedIterator for UnionIter<A, B>
+where
+    A: IdxSet,
+    B: IdxSet,
+{
+    fn next_back(&mut self) -> Option<Bitfield> {
+        let a = self
+            .a_back
+            .take()
+            .or_else(|| self.a.next_back())
+            .or_else(|| self.a_front.take());
+        let b = self
+            .b_back
+            .take()
+            .or_else(|| self.b.next_back())
+            .or_else(|| self.b_front.take());
+
+        match (a, b) {
+            (Some(a), Some(b)) if a.start() == b.start() => Some(a.unite(&b)),
+            (Some(a), Some(b)) if a.start() > b.start() => {
+                self.b_back = Some(b);
+                Some(a)
+            }
+            (Some(a), Some(b)) => {
+                self.a_back = Some(a);
+                Some(b)
+            }
+            (a, b) => a.or(b),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bits::Bitset;
+    use crate::idxsets::idxrange::IdxRange;
+
+    #[test]
+    fn test_union() {
+        let a: Bitset = vec![1, 5, 200, 700].into_iter().collect();
+        let b = IdxRange(150..260);
+        let expected: Vec<usize> = vec![1, 5]
+            .into_iter()
+            .chain(150..260)
+            .chain(vec![700])
+            .collect();
+
+        let forward: Vec<usize> = Union::new(a.clone(), b.clone())
+            .into_idx_iter()
+            .flatten()
+            .collect();
+        assert_eq!(expected, forward);
+
+        let backward: Vec<Bitfield> = Union::new(a.clone(), b.clone())
+            .into_idx_iter()
+            .rev()
+            .collect();
+        let backward: Vec<usize> = backward.into_iter().rev().flatten().collect();
+        assert_eq!(expected, backward);
+
+        let mut iter = Union::new(a.clone(), b.clone()).into_idx_iter();
+        let mut front = Vec::new();
+        let mut back = Vec::new();
+        while let Some(field) = iter.next() {
+            front.push(field);
+            if let Some(field) = iter.next_back() {
+                back.push(field);
+            }
+        }
+        let both: Vec<usize> = front
+            .into_iter()
+            .chain(back.into_iter().rev())
+            .flatten()
+            .collect();
+        assert_eq!(expected, both);
+
+        let union = Union::new(a, b);
+        for i in 0..800 {
+            assert_eq!(
+                expected.contains(&i),
+                union.intersect(&Bitfield::new(i)).ones() == 1
+            );
+        }
+    }
+}
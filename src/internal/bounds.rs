@@ -0,0 +1,78 @@
+use std::borrow::{Borrow, Cow};
+use std::ops::Bound;
+
+/// Check a pair of range bounds, returning `None` if no key could possibly fall between them.
+/// `BTreeMap::range()` panics on some of these ranges, so check before calling it.
+pub(crate) fn nonempty<'a, T>(
+    start: Bound<&'a T>,
+    end: Bound<&'a T>,
+) -> Option<(Bound<&'a T>, Bound<&'a T>)>
+where
+    T: Ord + ?Sized,
+{
+    match (start, end) {
+        (Bound::Included(s), Bound::Included(e)) if s > e => None,
+        (Bound::Included(s), Bound::Excluded(e))
+        | (Bound::Excluded(s), Bound::Included(e))
+        | (Bound::Excluded(s), Bound::Excluded(e))
+            if s >= e =>
+        {
+            None
+        }
+        bounds => Some(bounds),
+    }
+}
+
+/// True IFF the key falls between the given range bounds.
+pub(crate) fn contains<T>(start: Bound<&T>, end: Bound<&T>, key: &T) -> bool
+where
+    T: Ord + ?Sized,
+{
+    let after_start = match start {
+        Bound::Included(s) => s <= key,
+        Bound::Excluded(s) => s < key,
+        Bound::Unbounded => true,
+    };
+    let before_end = match end {
+        Bound::Included(e) => key <= e,
+        Bound::Excluded(e) => key < e,
+        Bound::Unbounded => true,
+    };
+
+    after_start && before_end
+}
+
+/// Borrow the key of a range bound as a different type.
+pub(crate) fn borrowed<Q, T>(bound: Bound<&Q>) -> Bound<&T>
+where
+    Q: Borrow<T>,
+    T: ?Sized,
+{
+    match bound {
+        Bound::Included(key) => Bound::Included(key.borrow()),
+        Bound::Excluded(key) => Bound::Excluded(key.borrow()),
+        Bound::Unbounded => Bound::Unbounded,
+    }
+}
+
+/// Borrow the key of a range bound. The same as `Bound::as_ref()`, which needs Rust 1.65.
+pub(crate) fn as_ref<T>(bound: &Bound<T>) -> Bound<&T> {
+    match bound {
+        Bound::Included(key) => Bound::Included(key),
+        Bound::Excluded(key) => Bound::Excluded(key),
+        Bound::Unbounded => Bound::Unbounded,
+    }
+}
+
+/// Clone the key of a range bound into a `Cow`.
+pub(crate) fn owned<'a, T>(bound: Bound<&T::Owned>) -> Bound<Cow<'a, T>>
+where
+    T: ToOwned + ?Sized,
+    T::Owned: Clone,
+{
+    match bound {
+        Bound::Included(key) => Bound::Included(Cow::Owned(key.clone())),
+        Bound::Excluded(key) => Bound::Excluded(Cow::Owned(key.clone())),
+        Bound::Unbounded => Bound::Unbounded,
+    }
+}
@@ -1,3 +1,5 @@
+/// Helpers for working with range bounds
+pub(crate) mod bounds;
 /// The hasher configuration
 pub(crate) mod hasher;
 /// Functions and data structures related to map reductions
@@ -3,6 +3,7 @@ use crate::traits::memory_usage::MemoryUsage;
 use crate::traits::memory_usage::MemoryUser;
 use std::sync::Arc;
 
+#[allow(clippy::type_complexity)]
 pub(crate) struct ReduceRules<Element, Summary> {
     map: Arc<dyn Fn(&Element, &Summary, usize) -> Option<Summary> + Send + Sync + 'static>,
     reduce: Arc<dyn Fn(&[Summary], &Summary) -> Option<Summary> + Send + Sync + 'static>,
@@ -54,7 +55,7 @@ where
         let map = &self.rules.map;
         let reduce = &self.rules.reduce;
 
-        if parent.len() == 0 {
+        if parent.is_empty() {
             self.reductions[layer] = RVec::default();
         } else {
             self.reductions[layer].reduce(parent, 1, |xs, y, i| {
@@ -91,7 +92,7 @@ where
     pub(crate) fn peek(&self) -> Option<&Summary> {
         let result_slice = &self.reductions[self.reductions.len() - 1];
 
-        if result_slice.len() == 0 {
+        if result_slice.is_empty() {
             None
         } else if result_slice.len() == 1 {
             Some(&result_slice[0])
@@ -110,6 +111,26 @@ impl<Element, Summary> Clone for ReduceRules<Element, Summary> {
     }
 }
 
+impl<Element, Summary> MemoryUser for Reduce<Element, Summary> {
+    fn memory_usage(&self) -> MemoryUsage {
+        let mut result = self.reductions.memory_usage();
+
+        for r in self.reductions.iter() {
+            result = MemoryUsage::merge(result, r.memory_usage());
+        }
+
+        result
+    }
+
+    fn shrink_with<F: Fn(&MemoryUsage) -> Option<usize>>(&mut self, f: F) {
+        self.reductions.shrink_with(&f);
+
+        for r in self.reductions.iter_mut() {
+            r.shrink_with(&f);
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -200,6 +221,29 @@ mod test {
         assert_eq!(sum.peek(), Some(&24));
     }
 
+    #[test]
+    fn test_sum_with_removal_from_partial_group() {
+        use super::*;
+
+        let mut numbers = RVec::default();
+
+        for i in 1..=15 {
+            numbers.push(i);
+        }
+
+        let mut sum = Reduce::new(&numbers, 2, summation_rules());
+
+        sum.update(&numbers);
+        assert_eq!(sum.peek(), Some(&120));
+
+        // Removing the last element shrinks the first layer of the reduction without changing
+        // any of its remaining elements.
+        numbers.swap_remove(14);
+
+        sum.update(&numbers);
+        assert_eq!(sum.peek(), Some(&105));
+    }
+
     #[test]
     fn test_sum_with_addition() {
         use super::*;
@@ -225,23 +269,3 @@ mod test {
         assert_eq!(sum.peek(), Some(&36));
     }
 }
-
-impl<Element, Summary> MemoryUser for Reduce<Element, Summary> {
-    fn memory_usage(&self) -> MemoryUsage {
-        let mut result = self.reductions.memory_usage();
-
-        for r in self.reductions.iter() {
-            result = MemoryUsage::merge(result, r.memory_usage());
-        }
-
-        result
-    }
-
-    fn shrink_with<F: Fn(&MemoryUsage) -> Option<usize>>(&mut self, f: F) {
-        self.reductions.shrink_with(&f);
-
-        for r in self.reductions.iter_mut() {
-            r.shrink_with(&f);
-        }
-    }
-}
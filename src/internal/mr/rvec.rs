@@ -36,6 +36,17 @@ impl<T> RVec<T> {
         self.data.len()
     }
 
+    /// A unique id for this RVec. Clones have different ids.
+    pub(crate) fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// True if this RVec was last reduced from some RVec other than the given source.
+    pub(crate) fn is_stale<S>(&self, source: &RVec<S>) -> bool {
+        self.parent_id
+            .map_or(false, |parent_id| parent_id != source.id)
+    }
+
     /// Number of changes made to this RVec.
     #[cfg(test)]
     pub(crate) fn change_count(&self) -> u128 {
@@ -65,16 +76,30 @@ impl<T> RVec<T> {
     where
         T: Default,
     {
-        resize_to_fit(&mut self.changed_vec.counts, new_size);
+        let old_size = self.data.len();
+        resize_to_fit(&mut self.changed_vec.counts, new_size.max(old_size));
 
         self.data.resize_with(new_size, Default::default);
-        for i in self.data.len()..new_size {
+
+        // Touch every position that was added or removed, so that anything reducing this RVec
+        // will notice.
+        for i in new_size.min(old_size)..new_size.max(old_size) {
             self.touch(i);
         }
 
         self
     }
 
+    /// Touch several elements of this RVec, and then borrow all of them mutably at once.
+    /// The caller must only mutate the elements that were touched.
+    pub(crate) fn touch_many(&mut self, idxs: &[usize]) -> &mut [T] {
+        for idx in idxs {
+            self.touch(*idx);
+        }
+
+        &mut self.data
+    }
+
     /// Push a single element to this RVec. As Vec::push(..).
     pub(crate) fn push(&mut self, t: T) {
         self.data.push(t);
@@ -228,9 +253,9 @@ fn resize_to_fit(counts: &mut [Vec<u128>; 5], len: usize) {
     counts[4].resize(len / STRIDE[4] + 1, 0);
 }
 
-impl<T> Into<Vec<T>> for RVec<T> {
-    fn into(self) -> Vec<T> {
-        self.data
+impl<T> From<RVec<T>> for Vec<T> {
+    fn from(val: RVec<T>) -> Self {
+        val.data
     }
 }
 
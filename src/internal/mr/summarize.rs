@@ -3,6 +3,7 @@ use crate::traits::memory_usage::{MemoryUsage, MemoryUser};
 use std::sync::Arc;
 
 #[derive(Clone)]
+#[allow(clippy::type_complexity)]
 pub(crate) struct SummaryRules<Element, Token, Summary> {
     pub(crate) map: Arc<dyn Fn(&Element, &Token, usize) -> Option<Token> + Send + Sync + 'static>,
     pub(crate) contribute: Arc<dyn Fn(&Token, usize, &mut Summary) + Send + Sync + 'static>,
@@ -18,7 +19,7 @@ pub(crate) struct Summarize<Element, Token, Summary> {
 
 impl<Element, Token, Summary> Summarize<Element, Token, Summary>
 where
-    Token: Default + Eq,
+    Token: Default + PartialEq,
 {
     pub(crate) fn new(
         _source: &RVec<Element>,
@@ -41,6 +42,19 @@ where
         let uncontribute = &self.rules.uncontribute;
         let summary = &mut self.summary;
 
+        // The parent was replaced (for example, a chunk was removed and then re-created), so
+        // none of the old tokens mean anything anymore. The reduction would forget them without
+        // uncontributing them, so do that first.
+        if tokens.is_stale(parent) {
+            for (i, old_token) in tokens.iter().enumerate() {
+                if old_token != &Token::default() {
+                    (uncontribute)(old_token, i, summary);
+                }
+            }
+
+            *tokens = RVec::default();
+        }
+
         tokens.reduce(parent, 1, move |elements, old_token, i| {
             if elements.is_empty() {
                 if old_token != &Token::default() {
@@ -69,11 +83,15 @@ where
     pub(crate) fn peek(&self) -> &Summary {
         &self.summary
     }
+
+    pub(crate) fn peek_mut(&mut self) -> &mut Summary {
+        &mut self.summary
+    }
 }
 
 impl<Element, Token, Summary> MemoryUser for Summarize<Element, Token, Summary>
 where
-    Token: Default + Eq,
+    Token: Default + PartialEq,
 {
     fn memory_usage(&self) -> MemoryUsage {
         self.tokens.memory_usage()
@@ -179,6 +197,29 @@ mod test {
         assert_eq!(*sum.peek(), 24);
     }
 
+    #[test]
+    fn test_sum_with_replaced_parent() {
+        use super::*;
+
+        let mut numbers = RVec::default();
+
+        numbers.push(1);
+        numbers.push(2);
+        numbers.push(3);
+
+        let mut sum = Summarize::new(&numbers, Arc::new(summation_rules()));
+
+        sum.update(&numbers);
+        assert_eq!(*sum.peek(), 6);
+
+        let mut numbers = RVec::default();
+
+        numbers.push(10);
+
+        sum.update(&numbers);
+        assert_eq!(*sum.peek(), 10);
+    }
+
     #[test]
     fn test_sum_with_addition() {
         use super::*;
@@ -21,20 +21,26 @@
 //!
 //! * Document-oriented storage and retrieval.
 //! * Index by unlimited secondary keys.
+//! * Sorted indexes and range queries.
+//! * Unique indexes that reject conflicting keys.
+//! * Boolean queries: chain query operators for intersection, or use `or`, `and_not` and `Not`.
 //! * Create indexes at will and drop them when you no longer need them.
 //! * Lazy indexing. Pay re-indexing costs when you query the index, not before.
 //! * Choice of borrowed or computed (dynamic) keys (using [Cow](https://doc.rust-lang.org/std/borrow/enum.Cow.html)).
 //! * Map-reduce-style summaries, if you want them.
 //! * Chunking: (optional) all records belonging to the same chunk are stored together in the same Vec.
+//! * Transactions: stage several changes and apply them all at once, or roll them all back.
+//! * Parallel queries, modifications and reductions, if you enable the `rayon` feature.
 //! * 100% safe Rust with no default dependencies.
 //! * Over 60 tests, doc-tests and benchmarks (need more)
 //! * Lots of full-featured examples to get started!
 //!
 //! ## Retriever does not have:
 //!
-//! * Parallelism. This is a "to-do".
-//! * Persistence. You can access the raw data for any chunk
-//!   and pass it to serde for serialization. See `Storage::raw()` for an example.
+//! * Persistence. Enable the `serde` feature to serialize and deserialize an entire `Storage`,
+//!   or page individual chunks in and out of a `ChunkStore` with `Storage::load_chunk()` and
+//!   `Storage::unload_chunk()`. See `stores::filesystem` for a ready-made backend. For crash
+//!   recovery, attach a `Journal` and use `Storage::recover()`.
 //! * Networking. Retriever is embedded in your application like any other crate. It doesn't
 //!   access anything over the network, nor can it be accessed over a network.
 //! * Novelty. I've tried to make Retriever as simple and obvious as possible, and I hope people
@@ -378,15 +384,15 @@
 //!  * A good chunk key is predictable; ideally you know what chunk a record is in before you
 //!    go looking for it.
 //!  * A good chunk key might correspond to persistent storage, such as a single file in the file
-//!    system. It's easy to load and unload chunks as a block.
+//!    system. It's easy to load and unload chunks as a block; see `Storage::load_chunk()` and
+//!    `Storage::unload_chunk()`.
 //!  * For stores that represent geographical or spatial information, a good chunk key
 //!    might represent a grid square or some other subdivision strategy.
 //!  * For a time-series database, a good chunk key might represent a time interval.
 //!  * In a GUI framework, each window might have its own chunk, and each widget might be a record
 //!    in that chunk.
 //!  * If you want to perform a `Reduction` on only part of your storage, then that part must be defined
-//!    as a single chunk. In the future, I want to implement convolutional reductions that map onto
-//!    zero or more chunks.
+//!    as a single chunk, or as a set of chunks using a `ConvolutionalReduction`.
 //!
 //! ### About Cow
 //!
@@ -434,16 +440,10 @@
 //! I'm also interested in any suggestions that would help further simplify the code base.
 //!
 //! ## To Do: (I want these features, but they aren't yet implemented)
-//! * Parallelism (will probably be implemented behind a rayon feature flag)
-//! * Sorted indexes / range queries
-//! * Boolean queries (union, intersection, difference, etc -- note: you can perform intersection
-//!   queries now just by chaining query operators)
-//! * External mutable iterators (currently only internal iteration is supported for modify)
 //! * More small vector optimization in some places where I expect it to matter
 //! * Need rigorous testing for space usage (currently no effort is made to shrink storage
 //!   or index vectors, this is probably priority #1 right now)
 //! * Lazy item key indexing or opt-out for item keys is a potential performance win.
-//! * Convolutional reductions summarizing zero or more source chunks.
 //! * Idea: data elements could be stored in a [persistent data structure](https://en.wikipedia.org/wiki/Persistent_data_structure)
 //!   which might make it possible to iterate over elements while separately mutating them. This idea needs research.
 //! * Theoretically, I expect retriever's performance to break down beyond about
@@ -463,11 +463,16 @@ pub mod prelude;
 pub mod queries;
 /// Module containing various strategies to reduce a storage to a single value.
 pub mod reductions;
+/// Module containing various backends to persist chunks.
+pub mod stores;
 /// Module containing various traits.
 pub mod traits;
 /// Module containing various types.
 pub mod types;
 
+pub use crate::types::error::Error;
+pub use crate::types::error::LoadChunkError;
+
 //
 // Puppy is from: https://commons.wikimedia.org/wiki/File:Callie_the_golden_retriever_puppy.jpg
 //
@@ -478,21 +483,23 @@ pub mod types;
 mod test {
     use crate::prelude::*;
     use crate::types::reduction::Reduction;
+    use crate::Error;
     use std::borrow::Cow;
 
     static_assertions::assert_impl_all!(Storage<u64,u64,(u64,u64,u64)>: Send, Sync);
     static_assertions::assert_impl_all!(Reduction<u64, (u64,u64,u64), u64>: Send, Sync);
     static_assertions::assert_impl_all!(SecondaryIndex<u64, (u64,u64,u64), std::collections::HashSet<u64>, u64>: Send, Sync);
+    static_assertions::assert_impl_all!(SortedIndex<u64, (u64,u64,u64), std::collections::HashSet<u64>, u64>: Send, Sync);
 
     #[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
     struct X(u64, u64);
 
     impl Record<u64, u64> for X {
-        fn chunk_key(&self) -> Cow<u64> {
+        fn chunk_key(&self) -> Cow<'_, u64> {
             Cow::Owned((self.0 & 0x00F0) >> 4)
         }
 
-        fn item_key(&self) -> Cow<u64> {
+        fn item_key(&self) -> Cow<'_, u64> {
             Cow::Borrowed(&self.0)
         }
     }
@@ -501,11 +508,11 @@ mod test {
     struct S(String, String, String);
 
     impl Record<str, str> for S {
-        fn chunk_key(&self) -> Cow<str> {
+        fn chunk_key(&self) -> Cow<'_, str> {
             Cow::Borrowed(&self.0)
         }
 
-        fn item_key(&self) -> Cow<str> {
+        fn item_key(&self) -> Cow<'_, str> {
             Cow::Borrowed(&self.1)
         }
     }
@@ -558,7 +565,7 @@ mod test {
         storage.add(X(0x202, 0x101));
         storage.add(X(0x111, 0x101));
 
-        storage.modify(&Id(0x0, 0x202), |mut editor| {
+        storage.modify(Id(0x0, 0x202), |mut editor| {
             assert_eq!(&Id(&0x0, &0x202), editor.id());
             assert_eq!(&X(0x202, 0x101), editor.get());
             editor.get_mut().1 = 0x102;
@@ -575,7 +582,7 @@ mod test {
         storage.add(X(0x202, 0x999));
         storage.add(X(0x111, 0x111));
 
-        storage.remove(&Chunks([0x0]).filter(|x: &X| x.1 == 0x999), std::mem::drop);
+        storage.remove(Chunks([0x0]).filter(|x: &X| x.1 == 0x999), std::mem::drop);
         assert_eq!(2, storage.iter().count());
         assert!(storage.get(&Id(0x0, 0x101)).is_some());
         assert!(storage.get(&Id(0x1, 0x111)).is_some());
@@ -835,109 +842,1725 @@ mod test {
     }
 
     #[test]
-    fn test_duplicate_clean() {
+    fn test_try_methods() {
         let mut storage: Storage<u64, u64, X> = Storage::new();
+        let mut reduction: Reduction<u64, X, u64> = Reduction::new(
+            &storage,
+            2,
+            |x: &X, _| Some(x.1),
+            |xs: &[u64], _| Some(xs.iter().sum::<u64>()),
+        );
 
-        storage.add(X(0, 0));
+        storage.try_add(X(0x00, 1)).unwrap();
+        storage.try_add_chunk(vec![X(0x10, 2), X(0x11, 3)]).unwrap();
 
-        storage.entry(&ID.chunk(0).item(0));
-        storage.remove(&ID.chunk(0).item(0), std::mem::drop);
+        assert_eq!(
+            Err(Error::DuplicateId(ID.chunk(1).item(0x11))),
+            storage.try_add(X(0x11, 100)).map(|_| ())
+        );
+        assert_eq!(
+            Err(Error::DuplicateId(ID.chunk(2).item(0x20))),
+            storage
+                .try_add_chunk(vec![X(0x20, 100), X(0x21, 100), X(0x20, 100)])
+                .map(|_| ())
+        );
+        assert_eq!(
+            Err(Error::WrongId {
+                expected: ID.chunk(2).item(0x31),
+                found: ID.chunk(3).item(0x31)
+            }),
+            storage
+                .try_add_chunk(vec![X(0x20, 100), X(0x31, 100)])
+                .map(|_| ())
+        );
+        assert_eq!(
+            Err(Error::WrongId {
+                expected: ID.chunk(2).item(0x20),
+                found: ID.chunk(2).item(0x21)
+            }),
+            storage
+                .entry(ID.chunk(2).item(0x20))
+                .try_or_insert_with(|| X(0x21, 100))
+                .map(|_| ())
+        );
+        assert_eq!(
+            Err(Error::IdCollision {
+                before: ID.chunk(1).item(0x10),
+                after: ID.chunk(1).item(0x11)
+            }),
+            storage.try_modify(Everything, |mut editor| {
+                editor.get_mut().1 *= 10;
+                if editor.get().0 == 0x10 {
+                    editor.get_mut().0 = 0x11;
+                }
+            })
+        );
+
+        // The colliding modification was rolled back, but the others were not.
+        assert_eq!(Some(&X(0x00, 10)), storage.get(&ID.chunk(0).item(0x00)));
+        assert_eq!(Some(&X(0x10, 2)), storage.get(&ID.chunk(1).item(0x10)));
+        assert_eq!(Some(&X(0x11, 30)), storage.get(&ID.chunk(1).item(0x11)));
+        assert_eq!(3, storage.iter().count());
+        assert_eq!(Some(&42), reduction.reduce(&storage));
 
         storage.validate();
     }
 
     #[test]
-    fn test_str() {
-        let mut storage: Storage<str, str, S> = Storage::new();
+    fn test_modify_moves_elements() {
+        let mut storage: Storage<u64, u64, X> = Storage::new();
+        let mut dependents = Dependents::new(&storage);
 
-        storage.add(S(
-            String::from("broberts"),
-            String::from("name"),
-            String::from("Bob Roberts"),
-        ));
-        storage.add(S(
-            String::from("broberts"),
-            String::from("password"),
-            String::from("password1"),
-        ));
-        storage.add(S(
-            String::from("ssmith"),
-            String::from("name"),
-            String::from("Sue Smith"),
-        ));
-        storage.add(S(
-            String::from("ssmith"),
-            String::from("password"),
-            String::from("1234"),
-        ));
+        storage.add_chunk(vec![X(0x00, 1), X(0x01, 2), X(0x02, 3)]);
+        storage.add_chunk(vec![X(0x10, 4), X(0x11, 5)]);
 
         assert_eq!(
-            Some("Bob Roberts"),
-            storage
-                .get(&ID.chunk("broberts").item("name"))
-                .map(|s| s.2.as_str())
+            vec![X(0x00, 1), X(0x02, 3), X(0x11, 5)],
+            dependents.odds(&storage)
         );
+        assert_eq!(Some(&15), dependents.sum(&storage));
+
+        // Re-key within a chunk, move to another chunk, and move to a new chunk.
+        storage.modify(Everything, |mut editor| match editor.get().0 {
+            0x00 => editor.get_mut().0 = 0x03,
+            0x01 => editor.get_mut().0 = 0x12,
+            0x11 => editor.get_mut().0 = 0x21,
+            _ => {}
+        });
+
         assert_eq!(
-            Some("Bob Roberts"),
-            storage
-                .get(&ID.chunk(String::from("broberts")).item("name"))
-                .map(|s| s.2.as_str())
+            vec![X(0x02, 3), X(0x03, 1), X(0x21, 5)],
+            dependents.odds(&storage)
         );
+        assert_eq!(Some(&15), dependents.sum(&storage));
+        assert_eq!(None, storage.get(&ID.chunk(0).item(0x00)));
+        assert_eq!(Some(&X(0x12, 2)), storage.get(&ID.chunk(1).item(0x12)));
+        storage.validate();
+
+        // Trade ids, and empty out a chunk entirely.
+        storage.modify(Everything, |mut editor| match editor.get().0 {
+            0x02 => editor.get_mut().0 = 0x03,
+            0x03 => editor.get_mut().0 = 0x02,
+            0x21 => editor.get_mut().0 = 0x13,
+            _ => {}
+        });
+
         assert_eq!(
-            Some("Bob Roberts"),
-            storage
-                .get(&ID.chunk("broberts").item(String::from("name")))
-                .map(|s| s.2.as_str())
+            vec![X(0x02, 1), X(0x03, 3), X(0x13, 5)],
+            dependents.odds(&storage)
         );
+        assert_eq!(Some(&15), dependents.sum(&storage));
+        assert_eq!(2, storage.chunk_keys().into_iter().count());
+        storage.validate();
+
+        // Collisions are still forbidden. Only the element that collided is dropped, and the
+        // others are still moved.
+        let collided = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            storage.modify(Everything, |mut editor| match editor.get().0 {
+                0x10 => editor.get_mut().0 = 0x12,
+                0x13 => editor.get_mut().0 = 0x14,
+                _ => {}
+            });
+        }));
+        assert!(collided.is_err());
+        dependents.validate(&mut storage);
+        assert_eq!(None, storage.get(&ID.chunk(1).item(0x10)));
+        assert_eq!(Some(&X(0x12, 2)), storage.get(&ID.chunk(1).item(0x12)));
         assert_eq!(
-            Some("Bob Roberts"),
+            vec![X(0x02, 1), X(0x03, 3), X(0x14, 5)],
+            dependents.odds(&storage)
+        );
+        assert_eq!(Some(&11), dependents.sum(&storage));
+    }
+
+    #[test]
+    fn test_sorted_index() {
+        let mut storage: Storage<u64, u64, X> = Storage::new();
+        let index: SortedIndex<u64, X, Option<u64>, u64> =
+            SortedIndex::new(&storage, |x: &X| Cow::Owned(Some(x.1)));
+
+        for i in 0..0x40 {
+            storage.add(X(i, (i * 37) % 0x40));
+        }
+
+        let check = |storage: &Storage<u64, u64, X>, lo: u64, hi: u64| {
+            let mut expected: Vec<X> = storage
+                .iter()
+                .filter(|x| lo <= x.1 && x.1 < hi)
+                .cloned()
+                .collect();
+            expected.sort_by_key(|x| x.1);
+
+            let query = Everything.in_range(&index, lo..hi);
+            let mut actual: Vec<X> = storage.query(&query).cloned().collect();
+            actual.sort_by_key(|x| x.1);
+            assert_eq!(expected, actual);
+
+            let sorted: Vec<X> = query.sorted(storage).cloned().collect();
+            assert_eq!(expected, sorted);
+        };
+
+        check(&storage, 0x10, 0x20);
+        check(&storage, 0x00, 0x40);
+        check(&storage, 0x20, 0x10);
+
+        storage.modify(Everything.in_range(&index, 0x10..0x18), |mut editor| {
+            editor.get_mut().1 += 0x100;
+        });
+        storage.remove(ID.chunk(2).item(0x21), std::mem::drop);
+        storage.remove_chunk(&1);
+
+        check(&storage, 0x00, 0x20);
+        check(&storage, 0x08, 0x120);
+
+        assert_eq!(
+            vec![
+                X(0x3D, 0x111),
+                X(0x2A, 0x112),
+                X(0x04, 0x114),
+                X(0x31, 0x115),
+                X(0x0B, 0x117)
+            ],
+            Everything
+                .at_least(&index, Cow::Owned(0x110))
+                .sorted(&storage)
+                .cloned()
+                .collect::<Vec<X>>()
+        );
+        assert_eq!(
+            4,
             storage
-                .get(
-                    &ID.chunk(String::from("broberts"))
-                        .item(String::from("name"))
+                .query(Chunks([0]).at_most(&index, Cow::Owned(0x0A)))
+                .count()
+        );
+
+        storage.validate();
+        index.validate(&storage);
+    }
+
+    #[test]
+    fn test_boolean_queries() {
+        let mut storage: Storage<u64, u64, X> = Storage::new();
+        let index: SecondaryIndex<u64, X, Option<u64>, u64> =
+            SecondaryIndex::new(&storage, |x: &X| Cow::Owned(Some(x.1 % 5)));
+
+        for i in 0..0x40 {
+            storage.add(X(i, (i * 37) % 0x40));
+        }
+
+        let check = |storage: &Storage<u64, u64, X>, mut actual: Vec<X>, f: &dyn Fn(&X) -> bool| {
+            let mut expected: Vec<X> = storage.iter().filter(|x| f(x)).cloned().collect();
+            expected.sort();
+            actual.sort();
+            assert_eq!(expected, actual);
+        };
+
+        check(
+            &storage,
+            storage
+                .query(
+                    Everything
+                        .matching(&index, Cow::Owned(1))
+                        .or(Everything.matching(&index, Cow::Owned(3))),
                 )
-                .map(|s| s.2.as_str())
+                .cloned()
+                .collect(),
+            &|x| x.1 % 5 == 1 || x.1 % 5 == 3,
         );
-        assert_eq!(
-            Some("Bob Roberts"),
+        check(
+            &storage,
             storage
-                .get(
-                    &ID.chunk(Cow::Borrowed("broberts"))
-                        .item(String::from("name"))
+                .query(
+                    Chunks([1])
+                        .filter(|x: &X| x.1 < 0x20)
+                        .or(ID.chunk(2).item(0x21))
+                        .or(ID.chunk(3).item(0x21)),
                 )
-                .map(|s| s.2.as_str())
+                .cloned()
+                .collect(),
+            &|x| (x.0 >> 4 == 1 && x.1 < 0x20) || x.0 == 0x21,
         );
-        assert_eq!(
-            Some("Bob Roberts"),
+        check(
+            &storage,
             storage
-                .get(
-                    &ID.chunk(Cow::Owned(String::from("broberts")))
-                        .item(Cow::Borrowed("name"))
+                .query(
+                    Chunks([0, 2])
+                        .and_not(Everything.matching(&index, Cow::Owned(0)))
+                        .and_not(Everything.filter(|x: &X| x.1 > 0x30)),
                 )
-                .map(|s| s.2.as_str())
+                .cloned()
+                .collect(),
+            &|x| x.0 >> 4 != 1 && x.0 >> 4 != 3 && x.1 % 5 != 0 && x.1 <= 0x30,
+        );
+        check(
+            &storage,
+            storage
+                .query(Not(ID.chunk(3).item(0x30).or(Chunks([0, 1]))))
+                .cloned()
+                .collect(),
+            &|x| x.0 >= 0x20 && x.0 != 0x30,
+        );
+
+        storage.modify(
+            Everything.matching(&index, Cow::Owned(2)).or(Chunks([3])),
+            |mut editor| {
+                editor.get_mut().1 += 0x100;
+            },
+        );
+        storage.remove(Not(Everything.filter(|x: &X| x.1 >= 0x100)), std::mem::drop);
+
+        check(
+            &storage,
+            storage.query(Everything).cloned().collect(),
+            &|x| x.0 >> 4 == 3 || (x.1 - 0x100) % 5 == 2,
         );
+        check(
+            &storage,
+            storage
+                .query(Everything.matching(&index, Cow::Owned(2)).or(Chunks([5])))
+                .cloned()
+                .collect(),
+            &|x| x.1 % 5 == 2,
+        );
+
+        storage.validate();
+        index.validate(&storage);
+    }
+
+    #[test]
+    fn test_sorted_chunk_keys() {
+        use std::ops::Bound;
+
+        let mut storage: Storage<u64, u64, (u64, u64, u64)> = Storage::new();
+
+        for i in 0..0x300 {
+            storage.add((i / 0x10, i, 0));
+        }
+
+        let check = |storage: &Storage<u64, u64, (u64, u64, u64)>| {
+            assert_eq!(0x300, storage.query(Chunks(0..u64::MAX)).count());
+            assert_eq!(0x30, storage.query(Chunks(0x10..0x13)).count());
+            assert_eq!(0x40, storage.query(Chunks(0x10..=0x13)).count());
+            assert_eq!(0x50, storage.query(Chunks(0x2B..)).count());
+            assert_eq!(0x20, storage.query(Chunks(..0x02)).count());
+            assert_eq!(0x30, storage.query(Chunks(..=0x02)).count());
+            assert_eq!(
+                0,
+                storage
+                    .query(Chunks((Bound::Included(0x13), Bound::Excluded(0x10))))
+                    .count()
+            );
+            assert_eq!(
+                0x20,
+                storage
+                    .query(Chunks((Bound::Excluded(0x04), Bound::Included(0x06))))
+                    .count()
+            );
+        };
+
+        check(&storage);
+        storage.set_sorted_chunk_keys(true);
+        storage.validate();
+        check(&storage);
+
+        storage.remove(Chunks(0x20..0x28), std::mem::drop);
+        storage.remove_chunk(&0x2F);
+        storage.add((0x100, 0x1000, 0));
+        storage.validate();
+        assert_eq!(0x41, storage.query(Chunks(0x2B..)).count());
+        assert_eq!(0x60, storage.query(Chunks(0x1E..0x2C)).count());
+
+        storage.set_sorted_chunk_keys(false);
+        assert_eq!(0x41, storage.query(Chunks(0x2B..)).count());
+        storage.validate();
+
+        let mut storage: Storage<str, str, S> = Storage::new();
+        storage.set_sorted_chunk_keys(true);
+
+        for (chunk, item) in [("apple", "a"), ("kiwi", "k"), ("kiwi", "l"), ("mango", "m")] {
+            storage.add(S(chunk.to_string(), item.to_string(), String::new()));
+        }
+
+        assert_eq!(2, storage.query(Chunks("b".."m")).count());
+        assert_eq!(3, storage.query(Chunks("b"..="mango")).count());
         assert_eq!(
-            Some("Bob Roberts"),
+            1,
             storage
-                .get(
-                    &ID.chunk(Cow::Owned(String::from("broberts")))
-                        .item(Cow::Owned(String::from("name")))
-                )
-                .map(|s| s.2.as_str())
+                .query(Chunks(String::from("l")..).or(Chunks(..="a")))
+                .count()
         );
 
+        storage.validate();
+
+        let mut storage: Storage<String, u64, (String, u64, u64)> = Storage::new();
+
+        for (i, chunk) in ["apple", "kiwi", "mango", "zucchini"].iter().enumerate() {
+            storage.add((chunk.to_string(), i as u64, 0));
+        }
+
+        for sorted in [false, true] {
+            storage.set_sorted_chunk_keys(sorted);
+            assert_eq!(
+                2,
+                storage
+                    .query(Chunks(String::from("a")..String::from("m")))
+                    .count()
+            );
+            assert_eq!(
+                3,
+                storage
+                    .query(Chunks(String::from("a")..=String::from("mango")))
+                    .count()
+            );
+            storage.validate();
+        }
+    }
+
+    #[test]
+    fn test_chunks_iter() {
+        let mut storage: Storage<str, str, S> = Storage::new();
+
+        for (chunk, item) in [("apple", "a"), ("kiwi", "k"), ("kiwi", "l"), ("mango", "m")] {
+            storage.add(S(chunk.to_string(), item.to_string(), String::new()));
+        }
+
+        let keys = [String::from("kiwi"), String::from("pear")];
         assert_eq!(
             2,
             storage
-                .query(Chunks(vec![String::from("broberts")]))
+                .query(ChunksIter::new(keys.iter().map(String::as_str)))
+                .count()
+        );
+        assert_eq!(
+            3,
+            storage
+                .query(ChunksIter::new(["apple", "kiwi"].iter().copied()))
                 .count()
         );
         assert_eq!(
             2,
             storage
-                .query(Chunks(vec![Cow::Borrowed("broberts")]))
+                .query(Everything.and_not(ChunksIter::new(keys.iter().map(String::as_str))))
                 .count()
         );
-        assert_eq!(2, storage.query(Chunks(vec!["broberts"])).count());
+
+        storage.modify(ChunksIter::new(std::iter::once("mango")), |mut editor| {
+            editor.get_mut().2.push_str("ripe")
+        });
+        assert_eq!(
+            Some("ripe"),
+            storage
+                .get(&ID.chunk("mango").item("m"))
+                .map(|x| x.2.as_str())
+        );
+
+        storage.validate();
+    }
+
+    #[test]
+    fn test_items() {
+        let mut storage: Storage<u64, u64, X> = Storage::new();
+
+        for i in 0..0x40 {
+            storage.add(X(i, i));
+        }
+
+        let items: Items<Id<u64, u64>> = vec![0x31, 0x02, 0x17, 0x03, 0x17, 0x2F, 0x50]
+            .into_iter()
+            .map(|i| ID.chunk((i & 0xF0) >> 4).item(i))
+            .collect();
+
+        let mut found: Vec<u64> = storage.query(&items).map(|x| x.0).collect();
+        found.sort_unstable();
+        assert_eq!(vec![0x02, 0x03, 0x17, 0x2F, 0x31], found);
+
+        storage.modify(&items, |mut editor| {
+            editor.get_mut().1 += 0x100;
+        });
+        assert_eq!(
+            0x117,
+            storage.get(&ID.chunk(1).item(0x17)).map(|x| x.1).unwrap()
+        );
+        assert_eq!(
+            5,
+            storage
+                .query(Everything.filter(|x: &X| x.1 >= 0x100))
+                .count()
+        );
+
+        // An id with the right item key in the wrong chunk matches nothing.
+        assert_eq!(
+            0,
+            storage
+                .query(Items::new(vec![ID.chunk(0).item(0x17)]))
+                .count()
+        );
+
+        storage.remove(items.or(Chunks([3])), std::mem::drop);
+        assert_eq!(0x40 - 5 - 0x0F, storage.iter().count());
+
+        storage.validate();
+    }
+
+    #[test]
+    fn test_page() {
+        let mut storage: Storage<u64, u64, X> = Storage::new();
+
+        for i in 0..0x40 {
+            storage.add(X((i * 37) % 0x40, i));
+        }
+
+        let query = Everything.filter(|x: &X| x.0 & 0x3 != 0);
+        let mut cursor = None;
+        let mut seen: Vec<u64> = Vec::new();
+        let mut added: Vec<u64> = Vec::new();
+
+        loop {
+            let page = storage.page(&query, 5, cursor.as_ref());
+            assert!(page.elements().len() <= 5);
+            seen.extend(page.elements().iter().map(|x| x.0));
+            cursor = page.next_cursor().cloned();
+
+            if cursor.is_none() {
+                break;
+            }
+
+            // Remove an element that was already seen and add one that will be seen later.
+            let last = *seen.last().unwrap();
+            storage.remove(ID.chunk((last & 0xF0) >> 4).item(last), std::mem::drop);
+            storage.add(X(0x100 + last, 0));
+            added.push(0x100 + last);
+        }
+
+        // Pages never go backwards, so nothing is seen twice, and nothing that was there
+        // all along, or added ahead of the cursor, is skipped.
+        let order = |i: &u64| ((i & 0xF0) >> 4, *i);
+        assert!(seen.windows(2).all(|w| order(&w[0]) < order(&w[1])));
+        for i in (0..0x40).filter(|i| i & 0x3 != 0) {
+            assert!(seen.contains(&i));
+        }
+        for i in added {
+            assert!(seen.contains(&i));
+        }
+
+        let sorted: Vec<u64> = storage
+            .query_sorted_by(&query, |x| std::cmp::Reverse(x.1))
+            .map(|x| x.0)
+            .collect();
+        let mut expected: Vec<&X> = storage.query(&query).collect();
+        expected.sort_by_key(|x| (std::cmp::Reverse(x.1), (x.0 & 0xF0) >> 4, x.0));
+        assert_eq!(
+            expected.into_iter().map(|x| x.0).collect::<Vec<u64>>(),
+            sorted
+        );
+
+        storage.validate();
+    }
+
+    #[test]
+    fn test_explain() {
+        let mut storage: Storage<u64, u64, X> = Storage::new();
+        let index: SecondaryIndex<u64, X, Option<u64>, u64> =
+            SecondaryIndex::new(&storage, |x: &X| Cow::Owned(Some(x.1 % 4)));
+
+        for i in 0..0x40 {
+            storage.add(X(i, i));
+        }
+
+        let query = Everything
+            .matching(&index, Cow::Owned(1))
+            .or(ID.chunk(0).item(2))
+            .and_not(Chunks([3]));
+        let plan = query.explain(&storage);
+
+        assert_eq!(
+            "AndNot: 4 chunks, 13 items, 13 matches
+  Or: 4 chunks, 17 items, 17 matches
+    MatchingSecondaryIndex: 4 chunks, 16 items, 16 matches
+      Everything: 4 chunks, 64 items, 64 matches
+    Id: 1 chunks, 1 items, 1 matches
+  Chunks: 1 chunks, 16 items, 16 matches
+",
+            plan.to_string()
+        );
+        assert_eq!(storage.query(&query).count(), plan.matches);
+
+        let plan = Everything.filter(|x: &X| x.1 > 0x30).explain(&storage);
+        assert!(plan.per_element_test);
+        assert!(!plan.children[0].per_element_test);
+        assert_eq!(0x0F, plan.matches);
+    }
+
+    #[test]
+    fn test_dyn_query() {
+        struct Search<'a> {
+            query: DynQuery<'a, u64, u64, X>,
+        }
+
+        let mut storage: Storage<u64, u64, X> = Storage::new();
+        let index: SecondaryIndex<u64, X, Option<u64>, u64> =
+            SecondaryIndex::new(&storage, |x: &X| Cow::Owned(Some(x.1 % 3)));
+
+        for i in 0..0x40 {
+            storage.add(X(i, (i * 7) % 0x40));
+        }
+
+        let mut searches = Vec::new();
+        for chunks in [None, Some(vec![0, 2])] {
+            let query = match chunks {
+                Some(chunks) => Chunks(chunks).boxed(),
+                None => Everything.boxed(),
+            };
+
+            searches.push(Search {
+                query: query
+                    .matching(&index, Cow::Owned(1))
+                    .filter(|x: &X| x.1 > 0x10)
+                    .boxed(),
+            });
+        }
+
+        let mut everything: Vec<X> = storage.query(&searches[0].query).cloned().collect();
+        let mut expected: Vec<X> = storage
+            .iter()
+            .filter(|x| x.1 % 3 == 1 && x.1 > 0x10)
+            .cloned()
+            .collect();
+        everything.sort();
+        expected.sort();
+        assert_eq!(expected, everything);
+
+        let mut chunks: Vec<X> = storage
+            .query(searches[1].query.clone().boxed())
+            .cloned()
+            .collect();
+        expected.retain(|x| x.0 >> 4 == 0 || x.0 >> 4 == 2);
+        chunks.sort();
+        assert_eq!(expected, chunks);
+
+        storage.remove(searches[1].query.clone(), std::mem::drop);
+        assert_eq!(0, storage.query(&searches[1].query).count());
+        assert_eq!(
+            everything.len() - chunks.len(),
+            storage.query(&searches[0].query).count()
+        );
+
+        storage.validate();
+        index.validate(&storage);
+    }
+
+    #[test]
+    fn test_unique_index() {
+        let mut storage: Storage<u64, u64, X> = Storage::new();
+        let index: UniqueIndex<u64, X, Option<u64>, u64> =
+            UniqueIndex::new(&storage, |x: &X| Cow::Owned(Some(x.1)));
+
+        for i in 0..0x40 {
+            index.try_add(&mut storage, X(i, i + 0x100)).unwrap();
+        }
+
+        assert_eq!(Some(&X(0x21, 0x121)), index.get_by(&storage, &0x121));
+        assert_eq!(None, index.get_by(&storage, &0x21));
+        assert_eq!(
+            Err(Error::DuplicateId(ID.chunk(2).item(0x21))),
+            index.try_add(&mut storage, X(0x21, 0)).map(|_| ())
+        );
+        assert_eq!(
+            Err(Error::KeyConflict {
+                existing: ID.chunk(2).item(0x21),
+                found: ID.chunk(4).item(0x40),
+            }),
+            index.try_add(&mut storage, X(0x40, 0x121)).map(|_| ())
+        );
+
+        // Keys can move between the elements being modified.
+        index
+            .try_modify(&mut storage, Chunks([0, 1]), |x: &mut X| x.1 ^= 0x10)
+            .unwrap();
+        assert_eq!(Some(&X(0x01, 0x111)), index.get_by(&storage, &0x111));
+        assert_eq!(Some(&X(0x11, 0x101)), index.get_by(&storage, &0x101));
+
+        // But two modified elements can't end up with the same key.
+        assert!(matches!(
+            index.try_modify(&mut storage, Chunks([3]), |x: &mut X| x.1 = 0),
+            Err(Error::KeyConflict { .. })
+        ));
+        assert_eq!(
+            Err(Error::KeyConflict {
+                existing: ID.chunk(0).item(0x01),
+                found: ID.chunk(3).item(0x30),
+            }),
+            index.try_modify(&mut storage, ID.chunk(3).item(0x30), |x: &mut X| x.1 =
+                0x111)
+        );
+        assert_eq!((0x30..0x40).map(|i| i + 0x100).collect::<Vec<u64>>(), {
+            let mut keys: Vec<u64> = storage.query(Chunks([3])).map(|x| x.1).collect();
+            keys.sort();
+            keys
+        });
+
+        storage.remove(ID.chunk(2).item(0x21), std::mem::drop);
+        assert_eq!(None, index.get_by(&storage, &0x121));
+        index.try_add(&mut storage, X(0x40, 0x121)).unwrap();
+        assert_eq!(Some(&X(0x40, 0x121)), index.get_by(&storage, &0x121));
+
+        storage.validate();
+        index.validate(&storage);
+
+        // Lookups still work after the shared secondary index stops keeping a global index.
+        index.secondary_index().clone().set_global_index(false);
+        assert_eq!(Some(&X(0x40, 0x121)), index.get_by(&storage, &0x121));
+        assert_eq!(None, index.get_by(&storage, &0x21));
+        assert_eq!(
+            Err(Error::KeyConflict {
+                existing: ID.chunk(0).item(0x01),
+                found: ID.chunk(4).item(0x42),
+            }),
+            index.try_add(&mut storage, X(0x42, 0x111)).map(|_| ())
+        );
+        index.validate(&storage);
+
+        // A conflict introduced through Storage itself isn't hidden by either element.
+        storage.add(X(0x41, 0x121));
+        assert_eq!(
+            Err(Error::KeyConflict {
+                existing: ID.chunk(4).item(0x41),
+                found: ID.chunk(4).item(0x40),
+            }),
+            index.try_modify(&mut storage, ID.chunk(4).item(0x40), |x: &mut X| x.1 =
+                0x121)
+        );
+        assert!(std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            index.validate(&storage)
+        }))
+        .is_err());
+    }
+
+    #[test]
+    fn test_global_secondary_index() {
+        let mut storage: Storage<u64, u64, X> = Storage::new();
+        let mut index: SecondaryIndex<u64, X, Option<u64>, u64> =
+            SecondaryIndex::new(&storage, |x: &X| Cow::Owned(Some(x.1 % 7)));
+
+        let check = |storage: &Storage<u64, u64, X>,
+                     index: &SecondaryIndex<u64, X, Option<u64>, u64>| {
+            for key in 0..7 {
+                let query = Everything.matching(index, Cow::Owned(key));
+                let mut actual: Vec<X> = storage.query(&query).cloned().collect();
+                let mut expected: Vec<X> =
+                    storage.iter().filter(|x| x.1 % 7 == key).cloned().collect();
+                actual.sort();
+                expected.sort();
+                assert_eq!(expected, actual);
+
+                let mut chunks: Vec<u64> = expected.iter().map(|x| (x.0 & 0xF0) >> 4).collect();
+                chunks.dedup();
+                assert_eq!(chunks.len(), query.explain(storage).chunks);
+            }
+
+            index.validate(storage);
+        };
+
+        for i in 0..0x100 {
+            storage.add(X(i, i & 0xF0));
+        }
+
+        check(&storage, &index);
+        index.set_global_index(true);
+        check(&storage, &index);
+
+        storage.modify(Chunks([3, 5]), |mut x| x.get_mut().1 += 1);
+        check(&storage, &index);
+
+        storage.remove(Chunks([0, 7]), std::mem::drop);
+        storage.remove_chunk(&9);
+        check(&storage, &index);
+
+        storage.add(X(0x01, 0x30));
+        storage.add(X(0x91, 0x90));
+        check(&storage, &index);
+
+        index.set_global_index(false);
+        storage.remove(
+            Everything.matching(&index, Cow::Owned(0x30 % 7)),
+            std::mem::drop,
+        );
+        check(&storage, &index);
+
+        index.set_global_index(true);
+        storage.modify(Chunks([0xA]), |mut x| x.get_mut().1 = 0);
+        check(&storage, &index);
+
+        storage.validate();
+    }
+
+    #[test]
+    fn test_secondary_index_with_recreated_chunk() {
+        let mut storage: Storage<u64, u64, X> = Storage::new();
+        let index: SecondaryIndex<u64, X, Option<u64>, u64> =
+            SecondaryIndex::new(&storage, |x: &X| Cow::Owned(Some(x.1)));
+
+        storage.add(X(0x01, 0x1));
+        assert_eq!(
+            1,
+            storage
+                .query(Everything.matching(&index, Cow::Owned(0x1)))
+                .count()
+        );
+
+        // The new chunk has the same key and the same internal index as the old one.
+        storage.remove_chunk(&0);
+        storage.add(X(0x02, 0x2));
+
+        assert_eq!(
+            0,
+            storage
+                .query(Everything.matching(&index, Cow::Owned(0x1)))
+                .count()
+        );
+        assert_eq!(
+            1,
+            storage
+                .query(Everything.matching(&index, Cow::Owned(0x2)))
+                .count()
+        );
+
+        index.validate(&storage);
+        storage.validate();
+    }
+
+    /// A `SecondaryIndex` of which elements have an odd `X.1`, and a `Reduction` summing `X.1`,
+    /// for tests of operations that must keep both up to date.
+    struct Dependents {
+        index: SecondaryIndex<u64, X, Option<bool>, bool>,
+        reduction: Reduction<u64, X, u64>,
+    }
+
+    impl Dependents {
+        fn new(storage: &Storage<u64, u64, X>) -> Self {
+            Dependents {
+                index: SecondaryIndex::new(storage, |x: &X| Cow::Owned(Some(x.1 % 2 == 1))),
+                reduction: Reduction::new(
+                    storage,
+                    2,
+                    |x: &X, _| Some(x.1),
+                    |xs: &[u64], _| Some(xs.iter().sum::<u64>()),
+                ),
+            }
+        }
+
+        /// Every element with an odd `X.1`, sorted.
+        fn odds(&self, storage: &Storage<u64, u64, X>) -> Vec<X> {
+            let mut result: Vec<X> = storage
+                .query(&Everything.matching(&self.index, Cow::Owned(true)))
+                .cloned()
+                .collect();
+            result.sort();
+            result
+        }
+
+        fn sum(&mut self, storage: &Storage<u64, u64, X>) -> Option<&u64> {
+            self.reduction.reduce(storage)
+        }
+
+        fn validate(&self, storage: &mut Storage<u64, u64, X>) {
+            storage.validate();
+            self.index.validate(storage);
+        }
+    }
+
+    /// Fill a storage with elements, then make 20 rounds of random modifications, removals,
+    /// insertions and chunk removals, and finally remove everything. `check` is called on the
+    /// empty storage, after filling it, after each round, and once everything is removed.
+    fn change_randomly<F>(storage: &mut Storage<u64, u64, X>, mut check: F)
+    where
+        F: FnMut(&Storage<u64, u64, X>),
+    {
+        use rand::Rng;
+
+        check(storage);
+
+        for i in 0..0x100 {
+            storage.add(X(i, i * 3));
+        }
+
+        check(storage);
+
+        for _ in 0..20 {
+            for _ in 0..10 {
+                let id = rand::thread_rng().gen_range(0..0x100);
+                let value = rand::thread_rng().gen_range(0..100);
+                match rand::thread_rng().gen_range(0..4) {
+                    0 => {
+                        storage.modify(ID.chunk((id & 0xF0) >> 4).item(id), |mut x| {
+                            x.get_mut().1 = value
+                        });
+                    }
+                    1 => {
+                        storage.remove(ID.chunk((id & 0xF0) >> 4).item(id), std::mem::drop);
+                    }
+                    2 => {
+                        storage.entry(&X(id, 0)).or_insert_with(|| X(id, value));
+                    }
+                    _ => {
+                        storage.remove_chunk(&((id & 0xF0) >> 4));
+                    }
+                }
+            }
+
+            check(storage);
+        }
+
+        storage.remove(Everything, std::mem::drop);
+        check(storage);
+    }
+
+    #[test]
+    fn test_grouped_reduction() {
+        use std::collections::HashMap;
+
+        let mut storage: Storage<u64, u64, X> = Storage::new();
+        let mut reduction: GroupedReduction<u64, X, u64, (u64, u64)> = GroupedReduction::new(
+            &storage,
+            4,
+            |x: &X| Cow::Owned(x.1 % 5),
+            |x: &X, was| {
+                if (1, x.1) != *was {
+                    Some((1, x.1))
+                } else {
+                    None
+                }
+            },
+            |xs: &[(u64, u64)], was| {
+                let total = xs
+                    .iter()
+                    .fold((0, 0), |(count, sum), x| (count + x.0, sum + x.1));
+
+                if total != *was {
+                    Some(total)
+                } else {
+                    None
+                }
+            },
+        );
+
+        change_randomly(&mut storage, |storage| {
+            let mut expected: HashMap<u64, (u64, u64)> = HashMap::new();
+            for x in storage.iter() {
+                let group = expected.entry(x.1 % 5).or_default();
+                group.0 += 1;
+                group.1 += x.1;
+            }
+
+            let actual: HashMap<u64, (u64, u64)> =
+                reduction.iter(storage).map(|(k, v)| (*k, *v)).collect();
+            assert_eq!(expected, actual);
+
+            for key in 0..6 {
+                assert_eq!(expected.get(&key), reduction.get(storage, &key));
+            }
+        });
+        assert_eq!(0, reduction.iter(&storage).count());
+    }
+
+    #[test]
+    fn test_convolutional_reduction() {
+        let mut storage: Storage<u64, u64, X> = Storage::new();
+        let mut reduction: ConvolutionalReduction<u64, X, u64, u64> = ConvolutionalReduction::new(
+            &storage,
+            4,
+            |chunk_key: &u64| (chunk_key.saturating_sub(1)..=chunk_key + 1).collect(),
+            |x: &X, was| if x.1 != *was { Some(x.1) } else { None },
+            |xs: &[u64], was| {
+                let total = xs.iter().sum();
+
+                if total != *was {
+                    Some(total)
+                } else {
+                    None
+                }
+            },
+        );
+
+        change_randomly(&mut storage, |storage| {
+            for output_key in 0..0x12_u64 {
+                let sources = output_key.saturating_sub(1)..=output_key + 1;
+                let expected: Vec<u64> = storage
+                    .iter()
+                    .filter(|x| sources.contains(&x.chunk_key()))
+                    .map(|x| x.1)
+                    .collect();
+                let expected = if expected.is_empty() {
+                    None
+                } else {
+                    Some(expected.iter().sum())
+                };
+
+                assert_eq!(expected.as_ref(), reduction.reduce(storage, &output_key));
+            }
+        });
+    }
+
+    #[test]
+    fn test_filtered_reduction() {
+        let mut storage: Storage<u64, u64, X> = Storage::new();
+        let even_odd: SecondaryIndex<u64, X, Option<bool>, bool> =
+            SecondaryIndex::new(&storage, |x: &X| Cow::Owned(Some(x.1 % 2 == 1)));
+        let mut reduction = FilteredReduction::new(
+            &storage,
+            Chunks([0x1, 0x2, 0x4, 0x8])
+                .matching(&even_odd, Cow::Owned(true))
+                .filter(|x: &X| x.1 % 3 == 0),
+            4,
+            |x: &X, was| if x.1 != *was { Some(x.1) } else { None },
+            |xs: &[u64], was| {
+                let total = xs.iter().sum();
+
+                if total != *was {
+                    Some(total)
+                } else {
+                    None
+                }
+            },
+        );
+
+        change_randomly(&mut storage, |storage| {
+            let expected: Vec<u64> = storage
+                .iter()
+                .filter(|x| [0x1, 0x2, 0x4, 0x8].contains(x.chunk_key().as_ref()))
+                .filter(|x| x.1 % 2 == 1 && x.1 % 3 == 0)
+                .map(|x| x.1)
+                .collect();
+            let expected = if expected.is_empty() {
+                None
+            } else {
+                Some(expected.iter().sum())
+            };
+
+            assert_eq!(expected.as_ref(), reduction.reduce(storage));
+        });
+        even_odd.validate(&storage);
+    }
+
+    #[test]
+    fn test_invertible_reduction() {
+        let mut storage: Storage<u64, u64, X> = Storage::new();
+        let mut reduction: InvertibleReduction<u64, X, (i64, i64)> = InvertibleReduction::new(
+            &storage,
+            |x: &X| (1, x.1 as i64),
+            |total: &mut (i64, i64), x: &(i64, i64)| {
+                total.0 += x.0;
+                total.1 += x.1;
+            },
+            |total: &mut (i64, i64), x: &(i64, i64)| {
+                total.0 -= x.0;
+                total.1 -= x.1;
+            },
+        );
+
+        change_randomly(&mut storage, |storage| {
+            let expected = storage
+                .iter()
+                .fold((0, 0), |total, x| (total.0 + 1, total.1 + x.1 as i64));
+            assert_eq!(&expected, reduction.reduce(storage));
+
+            for chunk_key in 0..0x10 {
+                let expected = storage
+                    .query(Chunks([chunk_key]))
+                    .fold((0, 0), |total, x| (total.0 + 1, total.1 + x.1 as i64));
+
+                if storage.query(Chunks([chunk_key])).next().is_some() {
+                    assert_eq!(Some(&expected), reduction.reduce_chunk(storage, &chunk_key));
+                }
+            }
+        });
+        assert_eq!(&(0, 0), reduction.reduce(&storage));
+    }
+
+    #[test]
+    fn test_upsert() {
+        let mut storage: Storage<u64, u64, X> = Storage::new();
+        let mut dependents = Dependents::new(&storage);
+
+        storage.add_chunk(vec![X(0x00, 1), X(0x01, 2), X(0x02, 3)]);
+
+        assert_eq!(vec![X(0x00, 1), X(0x02, 3)], dependents.odds(&storage));
+        assert_eq!(Some(&6), dependents.sum(&storage));
+
+        assert_eq!(Some(X(0x01, 2)), storage.upsert(X(0x01, 5)));
+        assert_eq!(None, storage.upsert(X(0x10, 7)));
+
+        // The replaced element keeps its place.
+        assert_eq!(
+            vec![X(0x00, 1), X(0x01, 5), X(0x02, 3), X(0x10, 7)],
+            storage.iter().cloned().collect::<Vec<X>>()
+        );
+        assert_eq!(
+            vec![X(0x00, 1), X(0x01, 5), X(0x02, 3), X(0x10, 7)],
+            dependents.odds(&storage)
+        );
+        assert_eq!(Some(&16), dependents.sum(&storage));
+
+        assert_eq!(
+            vec![X(0x00, 1), X(0x10, 7), X(0x11, 9)],
+            storage.upsert_all(vec![X(0x00, 0), X(0x10, 0), X(0x11, 9), X(0x11, 0)])
+        );
+        assert_eq!(vec![X(0x01, 5), X(0x02, 3)], dependents.odds(&storage));
+        assert_eq!(Some(&8), dependents.sum(&storage));
+
+        dependents.validate(&mut storage);
+    }
+
+    #[test]
+    fn test_duplicate_clean() {
+        let mut storage: Storage<u64, u64, X> = Storage::new();
+
+        storage.add(X(0, 0));
+
+        storage.entry(&ID.chunk(0).item(0));
+        storage.remove(ID.chunk(0).item(0), std::mem::drop);
+
+        storage.validate();
+    }
+
+    #[test]
+    fn test_str() {
+        let mut storage: Storage<str, str, S> = Storage::new();
+
+        storage.add(S(
+            String::from("broberts"),
+            String::from("name"),
+            String::from("Bob Roberts"),
+        ));
+        storage.add(S(
+            String::from("broberts"),
+            String::from("password"),
+            String::from("password1"),
+        ));
+        storage.add(S(
+            String::from("ssmith"),
+            String::from("name"),
+            String::from("Sue Smith"),
+        ));
+        storage.add(S(
+            String::from("ssmith"),
+            String::from("password"),
+            String::from("1234"),
+        ));
+
+        assert_eq!(
+            Some("Bob Roberts"),
+            storage
+                .get(&ID.chunk("broberts").item("name"))
+                .map(|s| s.2.as_str())
+        );
+        assert_eq!(
+            Some("Bob Roberts"),
+            storage
+                .get(&ID.chunk(String::from("broberts")).item("name"))
+                .map(|s| s.2.as_str())
+        );
+        assert_eq!(
+            Some("Bob Roberts"),
+            storage
+                .get(&ID.chunk("broberts").item(String::from("name")))
+                .map(|s| s.2.as_str())
+        );
+        assert_eq!(
+            Some("Bob Roberts"),
+            storage
+                .get(
+                    &ID.chunk(String::from("broberts"))
+                        .item(String::from("name"))
+                )
+                .map(|s| s.2.as_str())
+        );
+        assert_eq!(
+            Some("Bob Roberts"),
+            storage
+                .get(
+                    &ID.chunk(Cow::Borrowed("broberts"))
+                        .item(String::from("name"))
+                )
+                .map(|s| s.2.as_str())
+        );
+        assert_eq!(
+            Some("Bob Roberts"),
+            storage
+                .get(
+                    &ID.chunk(Cow::Owned(String::from("broberts")))
+                        .item(Cow::Borrowed("name"))
+                )
+                .map(|s| s.2.as_str())
+        );
+        assert_eq!(
+            Some("Bob Roberts"),
+            storage
+                .get(
+                    &ID.chunk(Cow::Owned(String::from("broberts")))
+                        .item(Cow::Owned(String::from("name")))
+                )
+                .map(|s| s.2.as_str())
+        );
+
+        assert_eq!(
+            2,
+            storage
+                .query(Chunks(vec![String::from("broberts")]))
+                .count()
+        );
+        assert_eq!(
+            2,
+            storage
+                .query(Chunks(vec![Cow::Borrowed("broberts")]))
+                .count()
+        );
+        assert_eq!(2, storage.query(Chunks(vec!["broberts"])).count());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let mut storage: Storage<u64, u64, (u64, u64, String)> = Storage::new();
+
+        storage.add((1, 1, String::from("one")));
+        storage.add((1, 2, String::from("two")));
+        storage.add((2, 1, String::from("three")));
+        storage.remove(ID.chunk(1).item(2), std::mem::drop);
+
+        let serialized = serde_json::to_string(&storage).unwrap();
+        let mut deserialized: Storage<u64, u64, (u64, u64, String)> =
+            serde_json::from_str(&serialized).unwrap();
+
+        deserialized.validate();
+        assert_eq!(2, deserialized.iter().count());
+        assert_eq!(
+            Some(&(1, 1, String::from("one"))),
+            deserialized.get(&ID.chunk(1).item(1))
+        );
+        assert_eq!(
+            Some(&(2, 1, String::from("three"))),
+            deserialized.get(&ID.chunk(2).item(1))
+        );
+        assert_eq!(None, deserialized.get(&ID.chunk(1).item(2)));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_rejects_duplicate_item_keys() {
+        let serialized = "[[[1,1,\"one\"]],[[1,1,\"uno\"]]]";
+        let result: Result<Storage<u64, u64, (u64, u64, String)>, _> =
+            serde_json::from_str(serialized);
+
+        assert!(result.is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_id_and_chunks() {
+        let id: Id<u64, String> = ID.chunk(7).item(String::from("seven"));
+        let serialized = serde_json::to_string(&id).unwrap();
+        assert_eq!(id, serde_json::from_str(&serialized).unwrap());
+
+        let chunks = Chunks(vec![1_u64, 2, 3]);
+        let serialized = serde_json::to_string(&chunks).unwrap();
+        assert_eq!(chunks, serde_json::from_str(&serialized).unwrap());
+    }
+
+    #[test]
+    fn test_remove_chunk_keeps_other_chunks_indexed() {
+        let mut storage: Storage<u64, u64, (u64, u64, u64)> = Storage::new();
+
+        storage.add((1, 1, 1));
+        storage.add((2, 2, 2));
+        storage.add((3, 3, 3));
+
+        storage.remove_chunk(&1);
+
+        assert_eq!(Some(&(3, 3, 3)), storage.get(&ID.chunk(3).item(3)));
+        storage.validate();
+    }
+
+    #[test]
+    fn test_reduction_sees_modifications() {
+        let mut storage: Storage<u64, u64, X> = Storage::new();
+        let mut reduction: Reduction<u64, X, u64> = Reduction::new(
+            &storage,
+            2,
+            |x: &X, _| Some(x.1),
+            |xs: &[u64], _| Some(xs.iter().sum::<u64>()),
+        );
+
+        storage.add(X(0x000, 1));
+        storage.add(X(0x010, 2));
+        assert_eq!(Some(&3), reduction.reduce(&storage));
+
+        storage.modify(ID.chunk(0).item(0x000), |mut editor| {
+            editor.get_mut().1 = 10;
+        });
+        assert_eq!(Some(&12), reduction.reduce(&storage));
+    }
+
+    #[test]
+    fn test_unload_and_load_chunks() {
+        use std::collections::BTreeMap;
+
+        let mut storage: Storage<u64, u64, X> = Storage::new();
+        let mut store: BTreeMap<u64, Vec<X>> = BTreeMap::new();
+        let mut dependents = Dependents::new(&storage);
+
+        storage.add(X(0x000, 1));
+        storage.add(X(0x001, 2));
+        storage.add(X(0x010, 3));
+        storage.add(X(0x020, 4));
+        storage.add(X(0x021, 5));
+
+        assert_eq!(3, dependents.odds(&storage).len());
+        assert_eq!(Some(&15), dependents.sum(&storage));
+
+        storage.unload_chunk(&mut store, &0).unwrap();
+        assert_eq!(2, dependents.odds(&storage).len());
+        assert_eq!(Some(&12), dependents.sum(&storage));
+        assert_eq!(None, dependents.reduction.reduce_chunk(&storage, &0));
+        dependents.validate(&mut storage);
+
+        storage.unload_chunk(&mut store, &2).unwrap();
+        assert_eq!(1, dependents.odds(&storage).len());
+        assert_eq!(Some(&3), dependents.sum(&storage));
+
+        assert!(storage.load_chunk(&store, &0).unwrap());
+        assert_eq!(2, dependents.odds(&storage).len());
+        assert_eq!(Some(&6), dependents.sum(&storage));
+        assert_eq!(Some(&3), dependents.reduction.reduce_chunk(&storage, &0));
+
+        // Loading a chunk that is already in memory replaces it.
+        storage.modify(ID.chunk(0).item(0x000), |mut editor| {
+            editor.get_mut().1 = 100;
+        });
+        assert_eq!(Some(&105), dependents.sum(&storage));
+        assert!(storage.load_chunk(&store, &0).unwrap());
+        assert_eq!(Some(&6), dependents.sum(&storage));
+
+        assert!(storage.load_chunk(&store, &2).unwrap());
+        assert!(!storage.load_chunk(&store, &7).unwrap());
+        assert_eq!(3, dependents.odds(&storage).len());
+        assert_eq!(Some(&15), dependents.sum(&storage));
+
+        dependents.validate(&mut storage);
+    }
+
+    #[test]
+    fn test_load_chunk_rejects_bad_elements() {
+        use crate::LoadChunkError;
+        use std::collections::BTreeMap;
+
+        let mut storage: Storage<u64, u64, X> = Storage::new();
+        let mut store: BTreeMap<u64, Vec<X>> = BTreeMap::new();
+
+        storage.add(X(0x000, 1));
+        storage.add(X(0x001, 2));
+
+        store.insert(0, vec![X(0x002, 3), X(0x010, 4)]);
+        assert_eq!(
+            Err(LoadChunkError::Element(Error::WrongId {
+                expected: ID.chunk(0).item(0x010),
+                found: ID.chunk(1).item(0x010),
+            })),
+            storage.load_chunk(&store, &0)
+        );
+
+        store.insert(0, vec![X(0x002, 3), X(0x002, 4)]);
+        assert_eq!(
+            Err(LoadChunkError::Element(Error::DuplicateId(
+                ID.chunk(0).item(0x002)
+            ))),
+            storage.load_chunk(&store, &0)
+        );
+
+        // The chunk that was already in memory is untouched.
+        assert_eq!(
+            vec![X(0x000, 1), X(0x001, 2)],
+            storage.iter().cloned().collect::<Vec<_>>()
+        );
+        storage.validate();
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_journal_recovery() {
+        use std::io::Write;
+
+        type T = Storage<u64, u64, (u64, u64, String)>;
+
+        let dir = std::env::temp_dir().join(format!(
+            "retriever-test-journal-recovery-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let snapshot = dir.join("snapshot.json");
+        let journal = dir.join("journal.jsonl");
+        let _ = std::fs::remove_file(&snapshot);
+        let _ = std::fs::remove_file(&journal);
+
+        let mut storage: T = Storage::recover(&snapshot, &journal).unwrap();
+
+        storage.add((1, 1, String::from("one")));
+        storage.add_chunk(vec![
+            (2, 1, String::from("two")),
+            (2, 2, String::from("three")),
+        ]);
+        storage.add((3, 1, String::from("four")));
+        storage.modify(ID.chunk(2).item(2), |mut editor| {
+            editor.get_mut().2.push('!');
+        });
+        storage.remove(ID.chunk(1).item(1), std::mem::drop);
+        storage.remove_chunk(&3);
+        storage
+            .entry(ID.chunk(4).item(1))
+            .or_insert_with(|| (4, 1, String::from("five")))
+            .2
+            .push('?');
+        for mut element in storage.query_mut(Chunks([2])) {
+            element.get_mut().2.push('.');
+        }
+        storage.sync_journal().unwrap();
+
+        let mut expected = storage.clone();
+        drop(storage);
+
+        // Simulate a crash part way through writing a record.
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&journal)
+            .unwrap()
+            .write_all(b"{\"Put\":[9,9,")
+            .unwrap();
+
+        let mut recovered: T = Storage::recover(&snapshot, &journal).unwrap();
+        recovered.validate();
+        expected.validate();
+
+        let mut recovered_elements: Vec<_> = recovered.iter().cloned().collect();
+        let mut expected_elements: Vec<_> = expected.iter().cloned().collect();
+        recovered_elements.sort();
+        expected_elements.sort();
+        assert_eq!(expected_elements, recovered_elements);
+        assert_eq!(
+            Some(&(4, 1, String::from("five?"))),
+            recovered.get(&ID.chunk(4).item(1))
+        );
+
+        // The torn record is gone, so new records are readable again.
+        recovered.add((5, 1, String::from("six")));
+        recovered.sync_journal().unwrap();
+        drop(recovered);
+
+        let mut recovered: T = Storage::recover(&snapshot, &journal).unwrap();
+        assert_eq!(4, recovered.iter().count());
+
+        // Replaying a stale journal on top of a fresh snapshot changes nothing.
+        let stale_journal = std::fs::read(&journal).unwrap();
+        recovered.compact_journal(&snapshot).unwrap();
+        assert_eq!(0, std::fs::metadata(&journal).unwrap().len());
+        drop(recovered);
+        std::fs::write(&journal, stale_journal).unwrap();
+
+        let mut recovered: T = Storage::recover(&snapshot, &journal).unwrap();
+        recovered.validate();
+        assert_eq!(4, recovered.iter().count());
+
+        // Nothing is recorded once the journal is detached.
+        recovered.take_journal().unwrap();
+        recovered.remove_chunk(&5);
+        drop(recovered);
+
+        let recovered: T = Storage::recover(&snapshot, &journal).unwrap();
+        assert_eq!(4, recovered.iter().count());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_transaction() {
+        let mut storage: Storage<u64, u64, X> = Storage::new();
+        let mut dependents = Dependents::new(&storage);
+
+        storage.add(X(0x000, 1));
+        storage.add(X(0x001, 2));
+        storage.add(X(0x010, 3));
+
+        assert_eq!(2, dependents.odds(&storage).len());
+        assert_eq!(Some(&6), dependents.sum(&storage));
+
+        // Roll back on Err.
+        let result: Result<(), &str> = storage.transaction(|tx| {
+            tx.add(X(0x020, 5));
+            tx.modify(ID.chunk(0).item(0x001), |x| x.1 = 7);
+            tx.remove(ID.chunk(1).item(0x010));
+
+            assert_eq!(Some(&X(0x001, 7)), tx.get(&ID.chunk(0).item(0x001)));
+            assert_eq!(None, tx.get(&ID.chunk(1).item(0x010)));
+            assert_eq!(Some(&X(0x020, 5)), tx.get(&ID.chunk(2).item(0x020)));
+
+            // The index only knows about the committed state.
+            assert_eq!(2, dependents.odds(tx.storage()).len());
+
+            Err("rollback")
+        });
+
+        assert_eq!(Err("rollback"), result);
+        assert_eq!(2, dependents.odds(&storage).len());
+        assert_eq!(Some(&6), dependents.sum(&storage));
+        assert_eq!(3, storage.iter().count());
+
+        // Roll back on panic.
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _: Result<(), ()> = storage.transaction(|tx| {
+                tx.modify(ID.chunk(0).item(0x000), |x| x.1 = 100);
+                tx.add(X(0x000, 4));
+                Ok(())
+            });
+        }));
+
+        assert!(panicked.is_err());
+        assert_eq!(Some(&X(0x000, 1)), storage.get(&ID.chunk(0).item(0x000)));
+        assert_eq!(Some(&6), dependents.sum(&storage));
+
+        // Commit on Ok.
+        let result: Result<u64, ()> = storage.transaction(|tx| {
+            tx.add(X(0x020, 5));
+            tx.modify(Everything, |x| x.1 += 10);
+            tx.remove(ID.chunk(1).item(0x010));
+            tx.modify(ID.chunk(0).item(0x001), |x| x.1 += 1);
+            Ok(tx.get(&ID.chunk(0).item(0x001)).unwrap().1)
+        });
+
+        assert_eq!(Ok(13), result);
+        assert_eq!(vec![X(0x000, 11), X(0x001, 13), X(0x020, 5)], {
+            let mut xs: Vec<X> = storage.iter().cloned().collect();
+            xs.sort();
+            xs
+        });
+        assert_eq!(3, dependents.odds(&storage).len());
+        assert_eq!(Some(&29), dependents.sum(&storage));
+
+        dependents.validate(&mut storage);
+
+        // An element moved onto the id of another match is only modified once.
+        let mut storage: Storage<u64, u64, (u64, u64, u64)> = Storage::new();
+        storage.add((0, 1, 10));
+        storage.add((0, 2, 20));
+
+        let result: Result<(), ()> = storage.transaction(|tx| {
+            tx.remove(ID.chunk(0).item(2));
+            tx.modify(Everything, |x| x.1 += 1);
+            Ok(())
+        });
+
+        assert_eq!(Ok(()), result);
+        assert_eq!(
+            vec![(0, 2, 10)],
+            storage.iter().cloned().collect::<Vec<_>>()
+        );
+
+        // Elements modified together may trade ids.
+        storage.add((0, 3, 30));
+
+        let result: Result<(), ()> = storage.transaction(|tx| {
+            tx.modify(Everything, |x| x.1 += 1);
+            Ok(())
+        });
+
+        assert_eq!(Ok(()), result);
+        assert_eq!(vec![(0, 3, 10), (0, 4, 30)], {
+            let mut xs: Vec<(u64, u64, u64)> = storage.iter().cloned().collect();
+            xs.sort();
+            xs
+        });
+        storage.validate();
+    }
+
+    #[test]
+    fn test_query_mut() {
+        let mut storage: Storage<u64, u64, X> = Storage::new();
+        let mut dependents = Dependents::new(&storage);
+
+        for i in 0..0x40 {
+            storage.add(X(i, i));
+        }
+
+        assert_eq!(32, dependents.odds(&storage).len());
+        assert_eq!(Some(&2016), dependents.sum(&storage));
+
+        // Stop part way through the second chunk.
+        for mut element in storage
+            .query_mut(Everything)
+            .take_while(|element| element.get().0 < 0x18)
+        {
+            element.get_mut().1 += 1;
+        }
+
+        assert_eq!(Some(&(2016 + 0x18)), dependents.sum(&storage));
+        assert_eq!(Some(&X(0x17, 0x18)), storage.get(&ID.chunk(1).item(0x17)));
+        assert_eq!(Some(&X(0x18, 0x18)), storage.get(&ID.chunk(1).item(0x18)));
+        assert_eq!(32, dependents.odds(&storage).len());
+
+        // Hold several elements at once.
+        let mut elements: Vec<_> = storage.query_mut(Chunks([3])).collect();
+        let (first, rest) = elements.split_first_mut().unwrap();
+        for element in rest {
+            first.get_mut().1 += element.get().1;
+            element.get_mut().1 = 0;
+        }
+        drop(elements);
+
+        assert_eq!(Some(&(2016 + 0x18)), dependents.sum(&storage));
+        assert_eq!(24, dependents.odds(&storage).len());
+
+        dependents.validate(&mut storage);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_query_mut_with_bogus_item() {
+        let mut storage: Storage<u64, u64, X> = Storage::new();
+
+        storage.add(X(0, 0));
+
+        for mut element in storage.query_mut(Everything) {
+            element.get_mut().0 = 1;
+        }
+    }
+
+    #[test]
+    fn test_change_cursor() {
+        use crate::types::change_cursor::{Change, ChangeCursor};
+
+        let mut storage: Storage<u64, u64, X> = Storage::new();
+
+        for i in 0..0x40 {
+            storage.add(X(i, i));
+        }
+
+        let mut cursor = ChangeCursor::new(&storage);
+        assert!(cursor.changes_since(&storage).is_empty());
+
+        storage.modify(ID.chunk(2).item(0x25), |mut editor| {
+            editor.get_mut().1 = 0;
+        });
+        storage
+            .entry(&ID.chunk(3).item(0x33))
+            .and_modify(|x| x.1 = 0);
+        storage.add(X(0x44, 0));
+
+        let changes = cursor.changes_since(&storage);
+        assert!(changes.contains(&Change::Modified(ID.chunk(2).item(0x25))));
+        assert!(changes.contains(&Change::Modified(ID.chunk(3).item(0x33))));
+        assert!(changes.contains(&Change::Added(ID.chunk(4).item(0x44))));
+        assert!(changes
+            .iter()
+            .all(|change| matches!(change, Change::Modified(_)) || change.id().0 == 4));
+
+        // Removing a chunk moves another chunk into its place, but only the removed
+        // chunk's elements are reported.
+        storage.remove_chunk(&0);
+        storage.remove(ID.chunk(1).item(0x11), std::mem::drop);
+
+        let changes = cursor.changes_since(&storage);
+        let removed: Vec<_> = changes
+            .iter()
+            .filter(|change| matches!(change, Change::Removed(_)))
+            .map(|change| *change.id())
+            .collect();
+        let mut expected: Vec<_> = (0..0x10).map(|i| ID.chunk(0).item(i)).collect();
+        expected.push(ID.chunk(1).item(0x11));
+        assert_eq!(expected, removed);
+        assert!(!changes
+            .iter()
+            .any(|change| matches!(change, Change::Added(_))));
+
+        // Removing and re-creating a chunk between calls.
+        storage.remove_chunk(&2);
+        storage.add(X(0x20, 7));
+        storage.add(X(0x120, 7));
+
+        let changes = cursor.changes_since(&storage);
+        assert!(changes.contains(&Change::Modified(ID.chunk(2).item(0x20))));
+        assert!(changes.contains(&Change::Added(ID.chunk(2).item(0x120))));
+        assert!(changes.contains(&Change::Removed(ID.chunk(2).item(0x25))));
+        assert_eq!(
+            17,
+            changes.iter().filter(|change| change.id().0 == 2).count()
+        );
+
+        assert!(cursor.changes_since(&storage).is_empty());
+    }
+
+    #[cfg(feature = "rayon")]
+    mod parallel {
+        use super::X;
+        use crate::prelude::*;
+        use proptest::prelude::*;
+        use rayon::prelude::*;
+        use std::borrow::Cow;
+
+        fn sum_reduction(storage: &Storage<u64, u64, X>) -> Reduction<u64, X, u64> {
+            Reduction::new(
+                storage,
+                4,
+                |x: &X, was| Some(x.1).filter(|x| x != was),
+                |xs: &[u64], was| Some(xs.iter().sum::<u64>()).filter(|x| x != was),
+            )
+        }
+
+        proptest! {
+            #[test]
+            fn test_parallel_matches_sequential(
+                ops in prop::collection::vec((0..4u8, 0..0x400u64, 0..100u64), 0..200)
+            ) {
+                let mut sequential: Storage<u64, u64, X> = Storage::new();
+                let index: SecondaryIndex<u64, X, Option<bool>, bool> =
+                    SecondaryIndex::new(&sequential, |x: &X| Cow::Owned(Some(x.1 % 3 == 0)));
+                let mut sequential_sum = sum_reduction(&sequential);
+                let mut parallel_sum = sum_reduction(&sequential);
+
+                for (op, key, value) in ops {
+                    match op {
+                        0 => {
+                            sequential.entry(X(key, 0)).or_insert_with(|| X(key, value));
+                        }
+                        1 => sequential.remove(ID.chunk((key & 0xF0) >> 4).item(key), std::mem::drop),
+                        2 => {
+                            sequential.remove_chunk(&((key & 0xF0) >> 4));
+                        }
+                        _ => {
+                            let mut parallel = sequential.clone();
+                            let query = Everything.filter(move |x: &X| x.1 > value);
+
+                            sequential.modify(query, |mut editor| editor.get_mut().1 += 1);
+                            parallel.par_modify(query, |mut editor| editor.get_mut().1 += 1);
+
+                            let sequential_elements: Vec<&X> = sequential.iter().collect();
+                            let parallel_elements: Vec<&X> = parallel.iter().collect();
+                            prop_assert_eq!(sequential_elements, parallel_elements);
+                        }
+                    }
+
+                    let query = Everything.matching(&index, Cow::Owned(true));
+                    let sequential_elements: Vec<&X> = sequential.query(&query).collect();
+                    let parallel_elements: Vec<&X> = sequential.par_query(&query).collect();
+                    prop_assert_eq!(sequential_elements, parallel_elements);
+
+                    prop_assert_eq!(
+                        sequential_sum.reduce(&sequential).cloned(),
+                        parallel_sum.par_reduce(&sequential).cloned()
+                    );
+                }
+
+                let sequential_elements: Vec<&X> = sequential.iter().collect();
+                let parallel_elements: Vec<&X> = sequential.par_iter().collect();
+                prop_assert_eq!(sequential_elements, parallel_elements);
+            }
+        }
     }
 }
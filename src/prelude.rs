@@ -1,10 +1,21 @@
-pub use crate::queries::chunks::Chunks;
+pub use crate::queries::chunks::{Chunks, ChunksIter};
+pub use crate::queries::dyn_query::DynQuery;
 pub use crate::queries::everything::Everything;
+pub use crate::queries::items::Items;
+pub use crate::queries::not::Not;
 pub use crate::queries::secondary_index::SecondaryIndex;
+pub use crate::queries::sorted_index::SortedIndex;
+pub use crate::queries::unique_index::UniqueIndex;
 pub use crate::traits::query::Query;
 pub use crate::traits::record::Record;
-pub use crate::types::editor::Editor;
+pub use crate::types::convolutional_reduction::ConvolutionalReduction;
+pub use crate::types::editor::{Editor, ElementMut};
 pub use crate::types::entry::Entry;
+pub use crate::types::filtered_reduction::FilteredReduction;
+pub use crate::types::grouped_reduction::GroupedReduction;
 pub use crate::types::id::{Id, ID};
+pub use crate::types::invertible_reduction::InvertibleReduction;
+pub use crate::types::page::{Page, PageCursor};
 pub use crate::types::reduction::Reduction;
 pub use crate::types::storage::Storage;
+pub use crate::types::transaction::Transaction;
@@ -0,0 +1,101 @@
+use crate::bits::Bitset;
+use crate::idxsets::difference::Difference;
+use crate::traits::query::Query;
+use crate::traits::record::Record;
+use crate::traits::valid_key::{BorrowedKey, ValidKey};
+use crate::types::chunk_storage::ChunkStorage;
+use crate::types::plan::Plan;
+use crate::types::storage::Storage;
+use std::fmt;
+use std::marker::PhantomData;
+
+/// A `Query` that visits every element that matches one query but not another.
+///
+/// # Type Parameters
+///
+/// * `Element`: The element type of the `Storage`, which lets the compiler infer the other
+///   type parameters of the queries being combined.
+pub struct AndNot<A, B, Element> {
+    a: A,
+    b: B,
+    _element: PhantomData<fn(&Element)>,
+}
+
+impl<A, B, Element> AndNot<A, B, Element> {
+    /// Construct a new difference query. You probably don't want to call this constructor
+    /// directly. Prefer the `Query::and_not` method instead.
+    pub fn new(a: A, b: B) -> Self {
+        AndNot {
+            a,
+            b,
+            _element: PhantomData,
+        }
+    }
+}
+
+impl<A: Clone, B: Clone, Element> Clone for AndNot<A, B, Element> {
+    fn clone(&self) -> Self {
+        AndNot::new(self.a.clone(), self.b.clone())
+    }
+}
+
+impl<A: Copy, B: Copy, Element> Copy for AndNot<A, B, Element> {}
+
+impl<A: fmt::Debug, B: fmt::Debug, Element> fmt::Debug for AndNot<A, B, Element> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AndNot")
+            .field("a", &self.a)
+            .field("b", &self.b)
+            .finish()
+    }
+}
+
+impl<ChunkKey, ItemKey, Element, A, B> Query<ChunkKey, ItemKey, Element> for AndNot<A, B, Element>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+    ItemKey: BorrowedKey + ?Sized,
+    ItemKey::Owned: ValidKey,
+    Element: Record<ChunkKey, ItemKey>,
+    A: Query<ChunkKey, ItemKey, Element>,
+    B: Query<ChunkKey, ItemKey, Element>,
+{
+    type ChunkIdxSet = A::ChunkIdxSet;
+    type ItemIdxSet = Difference<A::ItemIdxSet, Bitset>;
+
+    fn chunk_idxs(&self, storage: &Storage<ChunkKey, ItemKey, Element>) -> Self::ChunkIdxSet {
+        // We never visit chunks that only `b` would visit, but `b` may still need to
+        // bring its indexes up to date.
+        self.b.chunk_idxs(storage);
+        self.a.chunk_idxs(storage)
+    }
+
+    fn item_idxs(
+        &self,
+        chunk_key: &ChunkKey,
+        chunk_storage: &ChunkStorage<ChunkKey, ItemKey, Element>,
+    ) -> Self::ItemIdxSet {
+        Difference::new(
+            self.a.item_idxs(chunk_key, chunk_storage),
+            chunk_storage.matching_idxs(&self.b),
+        )
+    }
+
+    fn test(&self, element: &Element) -> bool {
+        self.a.test(element)
+    }
+
+    fn test_chunk(&self, chunk_key: &ChunkKey) -> bool {
+        self.a.test_chunk(chunk_key)
+    }
+
+    fn explain(&self, storage: &Storage<ChunkKey, ItemKey, Element>) -> Plan {
+        Plan::measure(
+            self,
+            storage,
+            "AndNot",
+            false,
+            vec![self.a.explain(storage), self.b.explain(storage)],
+        )
+    }
+}
@@ -1,16 +1,18 @@
 use crate::bits::bitfield::Bitfield;
 use crate::bits::Bitset;
 use crate::idxsets::idxrange::IdxRange;
+use crate::internal::bounds;
 use crate::traits::query::Query;
 use crate::traits::record::Record;
 use crate::traits::valid_key::{BorrowedKey, ValidKey};
 use crate::types::chunk_storage::ChunkStorage;
+use crate::types::plan::Plan;
 use crate::types::storage::Storage;
 use std::borrow::Borrow;
 use std::collections::BTreeSet;
 use std::collections::HashSet;
-use std::ops::Range;
-use std::ops::RangeInclusive;
+use std::hash::BuildHasher;
+use std::ops::{Bound, Range, RangeBounds, RangeFrom, RangeInclusive, RangeTo, RangeToInclusive};
 
 /// A `Query` that visits a collection of explicitly enumerated chunks
 /// (as opposed to the `Everything` query, which visits every chunk).
@@ -18,12 +20,60 @@ use std::ops::RangeInclusive;
 /// number of chunks.
 ///
 /// `Chunks` supports several collection types: `Vec`, `HashSet`, `BTreeSet`,
-/// `Range`, `RangeInclusive`, slices, and arrays up to length 16. If the
+/// slices, and arrays up to length 16. It also supports ranges of chunk keys,
+/// even of keys like `str` that can't be enumerated. Ranges are fastest when the
+/// `Storage` has sorted chunk keys; see `Storage::set_sorted_chunk_keys()`. Without
+/// them, a range scans every chunk key, so to look up a few integer keys in a `Storage`
+/// with many chunks, `ChunksIter::new(2017..=2019)` is faster. If the
 /// `smallvec` feature is enabled, this adds support for `SmallVec` backed
-/// by arrays up to length 16.
+/// by arrays up to length 16. If the `serde` feature is enabled, `Chunks`
+/// can be serialized and deserialized whenever its collection can.
 #[derive(Copy, Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Chunks<A>(pub A);
 
+/// A `Query` that visits the chunks listed by any `IntoIterator` of chunk keys,
+/// including lazily generated ones. Unlike `Chunks`, there's no need to collect the
+/// chunk keys into a fixed-size array or collection first.
+///
+/// `ChunksIter::new()` walks the iterator exactly once, remembering each chunk key in a set,
+/// so evaluating the query never walks it again.
+///
+/// ```
+/// use retriever::prelude::*;
+///
+/// let mut storage : Storage<u64, u64, (u64, u64, u64)> = Storage::new();
+///
+/// for i in 0..100 {
+///   storage.add((i % 10, i, i));
+/// }
+///
+/// let evens = ChunksIter::new((0..5).map(|i| i * 2));
+/// assert_eq!(50, storage.query(&evens).count());
+/// assert!(storage.query(&evens).all(|x| x.0 % 2 == 0));
+///
+/// # storage.validate();
+/// ```
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct ChunksIter<Q> {
+    chunk_keys: BTreeSet<Q>,
+}
+
+impl<Q> ChunksIter<Q>
+where
+    Q: Ord,
+{
+    /// Create a new `ChunksIter` visiting every chunk listed by the given chunk keys.
+    pub fn new<I>(chunk_keys: I) -> Self
+    where
+        I: IntoIterator<Item = Q>,
+    {
+        ChunksIter {
+            chunk_keys: chunk_keys.into_iter().collect(),
+        }
+    }
+}
+
 macro_rules! common_chunk_idxs_impl {
     () => {
         fn chunk_idxs(&self, storage: &Storage<ChunkKey, ItemKey, Element>) -> Self::ChunkIdxSet {
@@ -47,6 +97,22 @@ macro_rules! common_item_idxs_impl {
     };
 }
 
+macro_rules! common_test_chunk_impl {
+    () => {
+        fn test_chunk(&self, chunk_key: &ChunkKey) -> bool {
+            self.0.iter().any(|x| x.borrow() == chunk_key)
+        }
+    };
+}
+
+macro_rules! common_explain_impl {
+    ( $name:literal ) => {
+        fn explain(&self, storage: &Storage<ChunkKey, ItemKey, Element>) -> Plan {
+            Plan::measure(self, storage, $name, false, Vec::new())
+        }
+    };
+}
+
 macro_rules! common_test_impl {
     () => {
         #[inline(always)]
@@ -71,10 +137,13 @@ where
     common_chunk_idxs_impl!();
     common_item_idxs_impl!();
     common_test_impl!();
+    common_explain_impl!("Chunks");
+    common_test_chunk_impl!();
 }
 
 impl<Q, S, ChunkKey, ItemKey, Element> Query<ChunkKey, ItemKey, Element> for Chunks<HashSet<Q, S>>
 where
+    S: BuildHasher,
     Q: ValidKey + Borrow<ChunkKey>,
     ChunkKey: BorrowedKey + ?Sized,
     ChunkKey::Owned: ValidKey + Borrow<ChunkKey>,
@@ -88,6 +157,11 @@ where
     common_chunk_idxs_impl!();
     common_item_idxs_impl!();
     common_test_impl!();
+    common_explain_impl!("Chunks");
+
+    fn test_chunk(&self, chunk_key: &ChunkKey) -> bool {
+        self.0.contains(chunk_key)
+    }
 }
 
 impl<Q, ChunkKey, ItemKey, Element> Query<ChunkKey, ItemKey, Element> for Chunks<BTreeSet<Q>>
@@ -105,9 +179,14 @@ where
     common_chunk_idxs_impl!();
     common_item_idxs_impl!();
     common_test_impl!();
+    common_explain_impl!("Chunks");
+
+    fn test_chunk(&self, chunk_key: &ChunkKey) -> bool {
+        self.0.contains(chunk_key)
+    }
 }
 
-impl<'a, Q, ChunkKey, ItemKey, Element> Query<ChunkKey, ItemKey, Element> for Chunks<&'a [Q]>
+impl<Q, ChunkKey, ItemKey, Element> Query<ChunkKey, ItemKey, Element> for Chunks<&[Q]>
 where
     Q: ValidKey + Borrow<ChunkKey>,
     ChunkKey: BorrowedKey + ?Sized,
@@ -122,12 +201,13 @@ where
     common_chunk_idxs_impl!();
     common_item_idxs_impl!();
     common_test_impl!();
+    common_explain_impl!("Chunks");
+    common_test_chunk_impl!();
 }
 
-impl<Q, ChunkKey, ItemKey, Element> Query<ChunkKey, ItemKey, Element> for Chunks<Range<Q>>
+impl<Q, ChunkKey, ItemKey, Element> Query<ChunkKey, ItemKey, Element> for ChunksIter<Q>
 where
-    Range<Q>: IntoIterator<Item = Q>,
-    Q: ValidKey + Borrow<ChunkKey>,
+    Q: Ord + Borrow<ChunkKey>,
     ChunkKey: BorrowedKey + ?Sized,
     ChunkKey::Owned: ValidKey + Borrow<ChunkKey>,
     ItemKey: BorrowedKey + ?Sized,
@@ -138,42 +218,72 @@ where
     type ItemIdxSet = IdxRange;
 
     fn chunk_idxs(&self, storage: &Storage<ChunkKey, ItemKey, Element>) -> Self::ChunkIdxSet {
-        self.0
-            .clone()
-            .into_iter()
+        self.chunk_keys
+            .iter()
             .filter_map(|x| storage.internal_idx_of(x.borrow()))
             .collect()
     }
 
     common_item_idxs_impl!();
     common_test_impl!();
+    common_explain_impl!("ChunksIter");
+
+    fn test_chunk(&self, chunk_key: &ChunkKey) -> bool {
+        self.chunk_keys.contains(chunk_key)
+    }
 }
 
-impl<Q, ChunkKey, ItemKey, Element> Query<ChunkKey, ItemKey, Element> for Chunks<RangeInclusive<Q>>
-where
-    RangeInclusive<Q>: IntoIterator<Item = Q>,
-    Q: ValidKey + Borrow<ChunkKey>,
-    ChunkKey: BorrowedKey + ?Sized,
-    ChunkKey::Owned: ValidKey + Borrow<ChunkKey>,
-    ItemKey: BorrowedKey + ?Sized,
-    ItemKey::Owned: ValidKey,
-    Element: Record<ChunkKey, ItemKey>,
-{
-    type ChunkIdxSet = Bitset;
-    type ItemIdxSet = IdxRange;
+macro_rules! range_test_chunk_impl {
+    () => {
+        fn test_chunk(&self, chunk_key: &ChunkKey) -> bool {
+            bounds::contains(
+                bounds::borrowed(self.0.start_bound()),
+                bounds::borrowed(self.0.end_bound()),
+                chunk_key,
+            )
+        }
+    };
+}
 
-    fn chunk_idxs(&self, storage: &Storage<ChunkKey, ItemKey, Element>) -> Self::ChunkIdxSet {
-        self.0
-            .clone()
-            .into_iter()
-            .filter_map(|x| storage.internal_idx_of(x.borrow()))
-            .collect()
-    }
+macro_rules! range_query_impl {
+    ( $range:ty ) => {
+        impl<Q, ChunkKey, ItemKey, Element> Query<ChunkKey, ItemKey, Element> for Chunks<$range>
+        where
+            Q: Borrow<ChunkKey>,
+            ChunkKey: BorrowedKey + ?Sized,
+            ChunkKey::Owned: ValidKey + Borrow<ChunkKey>,
+            ItemKey: BorrowedKey + ?Sized,
+            ItemKey::Owned: ValidKey,
+            Element: Record<ChunkKey, ItemKey>,
+        {
+            type ChunkIdxSet = Bitset;
+            type ItemIdxSet = IdxRange;
+
+            fn chunk_idxs(
+                &self,
+                storage: &Storage<ChunkKey, ItemKey, Element>,
+            ) -> Self::ChunkIdxSet {
+                storage.internal_idxs_in_range(
+                    bounds::borrowed(self.0.start_bound()),
+                    bounds::borrowed(self.0.end_bound()),
+                )
+            }
 
-    common_item_idxs_impl!();
-    common_test_impl!();
+            common_item_idxs_impl!();
+            common_test_impl!();
+            common_explain_impl!("Chunks");
+            range_test_chunk_impl!();
+        }
+    };
 }
 
+range_query_impl!(Range<Q>);
+range_query_impl!(RangeInclusive<Q>);
+range_query_impl!(RangeFrom<Q>);
+range_query_impl!(RangeTo<Q>);
+range_query_impl!(RangeToInclusive<Q>);
+range_query_impl!((Bound<Q>, Bound<Q>));
+
 macro_rules! sized_array_query_impl {
   ( $n:literal ) => {
     #[cfg(feature="smallvec")]
@@ -210,6 +320,8 @@ macro_rules! sized_array_query_impl {
 
         common_item_idxs_impl!();
         common_test_impl!();
+        common_explain_impl!("Chunks");
+        common_test_chunk_impl!();
     }
 
     impl<Q, ChunkKey, ItemKey, Element> Query<ChunkKey, ItemKey, Element> for Chunks<[Q;$n]>
@@ -246,6 +358,8 @@ macro_rules! sized_array_query_impl {
 
         common_item_idxs_impl!();
         common_test_impl!();
+        common_explain_impl!("Chunks");
+        common_test_chunk_impl!();
     }
   }
 }
@@ -0,0 +1,194 @@
+use crate::bits::Bitset;
+use crate::traits::idxset::IdxSet;
+use crate::traits::query::Query;
+use crate::traits::record::Record;
+use crate::traits::valid_key::{BorrowedKey, ValidKey};
+use crate::types::chunk_storage::ChunkStorage;
+use crate::types::plan::Plan;
+use crate::types::storage::Storage;
+use std::sync::Arc;
+
+type ErasedQuery<'a, ChunkKey, ItemKey, Element> = dyn Query<ChunkKey, ItemKey, Element, ChunkIdxSet = Bitset, ItemIdxSet = Bitset>
+    + Send
+    + Sync
+    + 'a;
+
+/// A `Query` of any type, erased behind a pointer. Construct one with `Query::boxed()`.
+///
+/// Every combination of queries is a distinct type, which makes it impossible to choose a
+/// query at runtime or to store one in a struct field without naming that type. A `DynQuery`
+/// always has the same type, whatever query it was built from, and it still composes with
+/// `filter()`, `matching()`, and every other method of `Query`.
+///
+/// A `DynQuery` collects the chunk and item indices of the underlying query into `Bitsets`,
+/// so it is a little slower than the query it was built from. It is cheap to clone.
+///
+/// # Type Parameters
+///
+/// * `'a`: The lifetime of anything borrowed by the underlying query, such as a `SecondaryIndex`
+/// * `ChunkKey`: The chunk key type of the `Storage`
+/// * `ItemKey`: The item key type of the `Storage`
+/// * `Element`: The element type of the `Storage`
+///
+/// # Example
+///
+/// ```
+/// use retriever::prelude::*;
+///
+/// type Fruit = (u64, u64, &'static str);
+///
+/// let mut storage : Storage<u64, u64, Fruit> = Storage::new();
+///
+/// storage.add((0, 0, "apple"));
+/// storage.add((0, 1, "banana"));
+/// storage.add((1, 0, "cherry"));
+///
+/// fn search(chunk: Option<u64>, prefix: &'static str) -> DynQuery<'static, u64, u64, Fruit> {
+///   let query = match chunk {
+///     Some(chunk) => Chunks([chunk]).boxed(),
+///     None => Everything.boxed(),
+///   };
+///
+///   query.filter(move |x : &Fruit| x.2.starts_with(prefix)).boxed()
+/// }
+///
+/// assert_eq!(1, storage.query(search(None, "c")).count());
+/// assert_eq!(0, storage.query(search(Some(0), "c")).count());
+/// assert_eq!(2, storage.query(search(Some(0), "")).count());
+///
+/// # storage.validate();
+/// ```
+pub struct DynQuery<'a, ChunkKey, ItemKey, Element>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+    ItemKey: BorrowedKey + ?Sized,
+    ItemKey::Owned: ValidKey,
+{
+    query: Arc<ErasedQuery<'a, ChunkKey, ItemKey, Element>>,
+}
+
+// Adapts any `Query` to the `IdxSet` types of a `DynQuery`.
+struct Erased<Q>(Q);
+
+impl<'a, ChunkKey, ItemKey, Element> DynQuery<'a, ChunkKey, ItemKey, Element>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+    ItemKey: BorrowedKey + ?Sized,
+    ItemKey::Owned: ValidKey,
+    Element: Record<ChunkKey, ItemKey>,
+{
+    /// Erase the type of a query. You probably don't want to call this constructor directly.
+    /// Prefer the `Query::boxed` method instead.
+    pub fn new<Q>(query: Q) -> Self
+    where
+        Q: Query<ChunkKey, ItemKey, Element> + Send + Sync + 'a,
+    {
+        DynQuery {
+            query: Arc::new(Erased(query)),
+        }
+    }
+}
+
+impl<'a, ChunkKey, ItemKey, Element> Clone for DynQuery<'a, ChunkKey, ItemKey, Element>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+    ItemKey: BorrowedKey + ?Sized,
+    ItemKey::Owned: ValidKey,
+{
+    fn clone(&self) -> Self {
+        DynQuery {
+            query: Arc::clone(&self.query),
+        }
+    }
+}
+
+impl<ChunkKey, ItemKey, Element, Q> Query<ChunkKey, ItemKey, Element> for Erased<Q>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+    ItemKey: BorrowedKey + ?Sized,
+    ItemKey::Owned: ValidKey,
+    Element: Record<ChunkKey, ItemKey>,
+    Q: Query<ChunkKey, ItemKey, Element>,
+{
+    type ChunkIdxSet = Bitset;
+    type ItemIdxSet = Bitset;
+
+    fn chunk_idxs(&self, storage: &Storage<ChunkKey, ItemKey, Element>) -> Self::ChunkIdxSet {
+        self.0
+            .chunk_idxs(storage)
+            .into_idx_iter()
+            .flatten()
+            .collect()
+    }
+
+    fn item_idxs(
+        &self,
+        chunk_key: &ChunkKey,
+        chunk_storage: &ChunkStorage<ChunkKey, ItemKey, Element>,
+    ) -> Self::ItemIdxSet {
+        self.0
+            .item_idxs(chunk_key, chunk_storage)
+            .into_idx_iter()
+            .flatten()
+            .collect()
+    }
+
+    fn test(&self, element: &Element) -> bool {
+        self.0.test(element)
+    }
+
+    fn test_chunk(&self, chunk_key: &ChunkKey) -> bool {
+        self.0.test_chunk(chunk_key)
+    }
+
+    fn explain(&self, storage: &Storage<ChunkKey, ItemKey, Element>) -> Plan {
+        self.0.explain(storage)
+    }
+}
+
+impl<'a, ChunkKey, ItemKey, Element> Query<ChunkKey, ItemKey, Element>
+    for DynQuery<'a, ChunkKey, ItemKey, Element>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+    ItemKey: BorrowedKey + ?Sized,
+    ItemKey::Owned: ValidKey,
+    Element: Record<ChunkKey, ItemKey>,
+{
+    type ChunkIdxSet = Bitset;
+    type ItemIdxSet = Bitset;
+
+    fn chunk_idxs(&self, storage: &Storage<ChunkKey, ItemKey, Element>) -> Self::ChunkIdxSet {
+        self.query.chunk_idxs(storage)
+    }
+
+    fn item_idxs(
+        &self,
+        chunk_key: &ChunkKey,
+        chunk_storage: &ChunkStorage<ChunkKey, ItemKey, Element>,
+    ) -> Self::ItemIdxSet {
+        self.query.item_idxs(chunk_key, chunk_storage)
+    }
+
+    fn test(&self, element: &Element) -> bool {
+        self.query.test(element)
+    }
+
+    fn test_chunk(&self, chunk_key: &ChunkKey) -> bool {
+        self.query.test_chunk(chunk_key)
+    }
+
+    fn explain(&self, storage: &Storage<ChunkKey, ItemKey, Element>) -> Plan {
+        Plan::measure(
+            self,
+            storage,
+            "DynQuery",
+            false,
+            vec![self.query.explain(storage)],
+        )
+    }
+}
@@ -3,6 +3,7 @@ use crate::traits::query::Query;
 use crate::traits::record::Record;
 use crate::traits::valid_key::{BorrowedKey, ValidKey};
 use crate::types::chunk_storage::ChunkStorage;
+use crate::types::plan::Plan;
 use crate::types::storage::Storage;
 
 /// A query that visits every record in storage.
@@ -35,4 +36,12 @@ where
     fn test(&self, _element: &Element) -> bool {
         true
     }
+
+    fn test_chunk(&self, _chunk_key: &ChunkKey) -> bool {
+        true
+    }
+
+    fn explain(&self, storage: &Storage<ChunkKey, ItemKey, Element>) -> Plan {
+        Plan::measure(self, storage, "Everything", false, Vec::new())
+    }
 }
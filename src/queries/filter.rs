@@ -2,6 +2,7 @@ use crate::traits::query::Query;
 use crate::traits::record::Record;
 use crate::traits::valid_key::{BorrowedKey, ValidKey};
 use crate::types::chunk_storage::ChunkStorage;
+use crate::types::plan::Plan;
 use crate::types::storage::Storage;
 
 /// Filter a `Query` by a predicate.
@@ -47,4 +48,18 @@ where
     fn test(&self, element: &Element) -> bool {
         self.parent.test(element) && (self.filter)(element)
     }
+
+    fn test_chunk(&self, chunk_key: &ChunkKey) -> bool {
+        self.parent.test_chunk(chunk_key)
+    }
+
+    fn explain(&self, storage: &Storage<ChunkKey, ItemKey, Element>) -> Plan {
+        Plan::measure(
+            self,
+            storage,
+            "Filter",
+            true,
+            vec![self.parent.explain(storage)],
+        )
+    }
 }
@@ -0,0 +1,162 @@
+use crate::bits::Bitset;
+use crate::traits::query::Query;
+use crate::traits::record::Record;
+use crate::traits::valid_key::{BorrowedKey, ValidKey};
+use crate::types::chunk_storage::ChunkStorage;
+use crate::types::plan::Plan;
+use crate::types::storage::Storage;
+use std::borrow::Borrow;
+use std::iter::FromIterator;
+use std::sync::{Arc, RwLock};
+
+/// A `Query` that visits an explicitly enumerated set of elements, by id.
+/// Ids that don't exist in the `Storage` are ignored.
+///
+/// ```
+/// use retriever::prelude::*;
+///
+/// let mut storage : Storage<u64, u64, (u64, u64, &'static str)> = Storage::new();
+///
+/// storage.add((0, 0, "apple"));
+/// storage.add((0, 1, "banana"));
+/// storage.add((1, 0, "cherry"));
+/// storage.add((2, 0, "date"));
+///
+/// let items = Items::new(vec![ID.chunk(0).item(1), ID.chunk(2).item(0), ID.chunk(3).item(0)]);
+/// let mut fruits : Vec<_> = storage.query(&items).map(|x| x.2).collect();
+/// fruits.sort();
+/// assert_eq!(vec!["banana", "date"], fruits);
+///
+/// storage.remove(&items, std::mem::drop);
+/// assert_eq!(2, storage.iter().count());
+///
+/// # storage.validate();
+/// ```
+#[derive(Debug, Default)]
+pub struct Items<R> {
+    ids: Vec<R>,
+    // Positions within `ids`, sorted by chunk key, so that each chunk can quickly find its own ids.
+    // This is built the first time the query is evaluated, because that's the first time we know
+    // the `ChunkKey` type.
+    by_chunk: RwLock<Option<Arc<Vec<usize>>>>,
+}
+
+impl<R: Clone> Clone for Items<R> {
+    fn clone(&self) -> Self {
+        Items {
+            ids: self.ids.clone(),
+            by_chunk: RwLock::new(self.by_chunk.read().unwrap().clone()),
+        }
+    }
+}
+
+impl<R> Items<R> {
+    /// Construct a new `Items` query from any collection of ids.
+    pub fn new<I>(ids: I) -> Self
+    where
+        I: IntoIterator<Item = R>,
+    {
+        Items {
+            ids: ids.into_iter().collect(),
+            by_chunk: RwLock::new(None),
+        }
+    }
+
+    /// The ids that this query visits.
+    pub fn ids(&self) -> &[R] {
+        &self.ids
+    }
+
+    fn ids_in<'a, ChunkKey, ItemKey>(
+        &'a self,
+        chunk_key: &'a ChunkKey,
+    ) -> impl Iterator<Item = &'a R>
+    where
+        R: Record<ChunkKey, ItemKey>,
+        ChunkKey: BorrowedKey + ?Sized,
+        ChunkKey::Owned: ValidKey,
+        ItemKey: BorrowedKey + ?Sized,
+        ItemKey::Owned: ValidKey,
+    {
+        let by_chunk = self.by_chunk();
+        let start = by_chunk.partition_point(|i| self.ids[*i].chunk_key().as_ref() < chunk_key);
+
+        (start..by_chunk.len())
+            .map(move |j| &self.ids[by_chunk[j]])
+            .take_while(move |id| id.chunk_key().as_ref() == chunk_key)
+    }
+
+    fn by_chunk<ChunkKey, ItemKey>(&self) -> Arc<Vec<usize>>
+    where
+        R: Record<ChunkKey, ItemKey>,
+        ChunkKey: BorrowedKey + ?Sized,
+        ChunkKey::Owned: ValidKey,
+        ItemKey: BorrowedKey + ?Sized,
+        ItemKey::Owned: ValidKey,
+    {
+        if let Some(by_chunk) = self.by_chunk.read().unwrap().as_ref() {
+            return Arc::clone(by_chunk);
+        }
+
+        let mut by_chunk: Vec<usize> = (0..self.ids.len()).collect();
+        by_chunk.sort_by(|a, b| self.ids[*a].chunk_key().cmp(&self.ids[*b].chunk_key()));
+        let by_chunk = Arc::new(by_chunk);
+        *self.by_chunk.write().unwrap() = Some(Arc::clone(&by_chunk));
+
+        by_chunk
+    }
+}
+
+impl<R> From<Vec<R>> for Items<R> {
+    fn from(ids: Vec<R>) -> Self {
+        Items::new(ids)
+    }
+}
+
+impl<R> FromIterator<R> for Items<R> {
+    fn from_iter<I: IntoIterator<Item = R>>(ids: I) -> Self {
+        Items::new(ids)
+    }
+}
+
+impl<R, ChunkKey, ItemKey, Element> Query<ChunkKey, ItemKey, Element> for Items<R>
+where
+    R: Record<ChunkKey, ItemKey>,
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey + Borrow<ChunkKey>,
+    ItemKey: BorrowedKey + ?Sized,
+    ItemKey::Owned: ValidKey + Borrow<ItemKey>,
+    Element: Record<ChunkKey, ItemKey>,
+{
+    type ChunkIdxSet = Bitset;
+    type ItemIdxSet = Bitset;
+
+    fn chunk_idxs(&self, storage: &Storage<ChunkKey, ItemKey, Element>) -> Self::ChunkIdxSet {
+        self.ids
+            .iter()
+            .filter_map(|id| storage.internal_idx_of(id.chunk_key().as_ref()))
+            .collect()
+    }
+
+    fn item_idxs(
+        &self,
+        chunk_key: &ChunkKey,
+        chunk_storage: &ChunkStorage<ChunkKey, ItemKey, Element>,
+    ) -> Self::ItemIdxSet {
+        self.ids_in(chunk_key)
+            .filter_map(|id| chunk_storage.internal_idx_of(id.item_key().as_ref()))
+            .collect()
+    }
+
+    fn test(&self, _element: &Element) -> bool {
+        true
+    }
+
+    fn test_chunk(&self, chunk_key: &ChunkKey) -> bool {
+        self.ids_in(chunk_key).next().is_some()
+    }
+
+    fn explain(&self, storage: &Storage<ChunkKey, ItemKey, Element>) -> Plan {
+        Plan::measure(self, storage, "Items", false, Vec::new())
+    }
+}
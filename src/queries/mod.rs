@@ -1,8 +1,22 @@
+/// Query to visit elements of one query but not another.
+pub mod and_not;
 /// Query all elements of some explicitly enumerated chunks.
 pub mod chunks;
+/// Query with its concrete type erased.
+pub mod dyn_query;
 /// Query every element.
 pub mod everything;
 /// Query to filter elements by predicate.
 pub mod filter;
+/// Query an explicitly enumerated set of elements by id.
+pub mod items;
+/// Query to visit every element that does not match another query.
+pub mod not;
+/// Query to visit elements of either of two queries.
+pub mod or;
 /// Query to filter elements by a pre-computed index.
 pub mod secondary_index;
+/// Query to filter elements by a range of keys in a pre-computed, sorted index.
+pub mod sorted_index;
+/// Query to look up elements by a pre-computed index of keys that are unique across the whole storage.
+pub mod unique_index;
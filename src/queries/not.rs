@@ -0,0 +1,57 @@
+use crate::bits::Bitset;
+use crate::idxsets::difference::Difference;
+use crate::idxsets::idxrange::IdxRange;
+use crate::traits::query::Query;
+use crate::traits::record::Record;
+use crate::traits::valid_key::{BorrowedKey, ValidKey};
+use crate::types::chunk_storage::ChunkStorage;
+use crate::types::plan::Plan;
+use crate::types::storage::Storage;
+
+/// A `Query` that visits every element that does not match another query.
+///
+/// `Not` has to visit every chunk, so prefer `Query::and_not` where possible.
+#[derive(Copy, Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Not<Q>(pub Q);
+
+impl<ChunkKey, ItemKey, Element, Q> Query<ChunkKey, ItemKey, Element> for Not<Q>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+    ItemKey: BorrowedKey + ?Sized,
+    ItemKey::Owned: ValidKey,
+    Element: Record<ChunkKey, ItemKey>,
+    Q: Query<ChunkKey, ItemKey, Element>,
+{
+    type ChunkIdxSet = IdxRange;
+    type ItemIdxSet = Difference<IdxRange, Bitset>;
+
+    fn chunk_idxs(&self, storage: &Storage<ChunkKey, ItemKey, Element>) -> Self::ChunkIdxSet {
+        // Give the negated query a chance to bring its indexes up to date.
+        self.0.chunk_idxs(storage);
+        IdxRange(0..storage.internal_rvec().len())
+    }
+
+    fn item_idxs(
+        &self,
+        _chunk_key: &ChunkKey,
+        chunk_storage: &ChunkStorage<ChunkKey, ItemKey, Element>,
+    ) -> Self::ItemIdxSet {
+        Difference::new(
+            IdxRange(0..chunk_storage.len()),
+            chunk_storage.matching_idxs(&self.0),
+        )
+    }
+
+    fn test(&self, _element: &Element) -> bool {
+        true
+    }
+
+    fn test_chunk(&self, _chunk_key: &ChunkKey) -> bool {
+        true
+    }
+
+    fn explain(&self, storage: &Storage<ChunkKey, ItemKey, Element>) -> Plan {
+        Plan::measure(self, storage, "Not", false, vec![self.0.explain(storage)])
+    }
+}
@@ -0,0 +1,100 @@
+use crate::bits::Bitset;
+use crate::idxsets::union::Union;
+use crate::traits::query::Query;
+use crate::traits::record::Record;
+use crate::traits::valid_key::{BorrowedKey, ValidKey};
+use crate::types::chunk_storage::ChunkStorage;
+use crate::types::plan::Plan;
+use crate::types::storage::Storage;
+use std::fmt;
+use std::marker::PhantomData;
+
+/// A `Query` that visits every element that matches either of two other queries.
+///
+/// # Type Parameters
+///
+/// * `Element`: The element type of the `Storage`, which lets the compiler infer the other
+///   type parameters of the queries being combined.
+pub struct Or<A, B, Element> {
+    a: A,
+    b: B,
+    _element: PhantomData<fn(&Element)>,
+}
+
+impl<A, B, Element> Or<A, B, Element> {
+    /// Construct a new union query. You probably don't want to call this constructor directly.
+    /// Prefer the `Query::or` method instead.
+    pub fn new(a: A, b: B) -> Self {
+        Or {
+            a,
+            b,
+            _element: PhantomData,
+        }
+    }
+}
+
+impl<A: Clone, B: Clone, Element> Clone for Or<A, B, Element> {
+    fn clone(&self) -> Self {
+        Or::new(self.a.clone(), self.b.clone())
+    }
+}
+
+impl<A: Copy, B: Copy, Element> Copy for Or<A, B, Element> {}
+
+impl<A: fmt::Debug, B: fmt::Debug, Element> fmt::Debug for Or<A, B, Element> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Or")
+            .field("a", &self.a)
+            .field("b", &self.b)
+            .finish()
+    }
+}
+
+impl<ChunkKey, ItemKey, Element, A, B> Query<ChunkKey, ItemKey, Element> for Or<A, B, Element>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+    ItemKey: BorrowedKey + ?Sized,
+    ItemKey::Owned: ValidKey,
+    Element: Record<ChunkKey, ItemKey>,
+    A: Query<ChunkKey, ItemKey, Element>,
+    B: Query<ChunkKey, ItemKey, Element>,
+{
+    type ChunkIdxSet = Union<A::ChunkIdxSet, B::ChunkIdxSet>;
+    type ItemIdxSet = Union<Bitset, Bitset>;
+
+    fn chunk_idxs(&self, storage: &Storage<ChunkKey, ItemKey, Element>) -> Self::ChunkIdxSet {
+        Union::new(self.a.chunk_idxs(storage), self.b.chunk_idxs(storage))
+    }
+
+    fn item_idxs(
+        &self,
+        _chunk_key: &ChunkKey,
+        chunk_storage: &ChunkStorage<ChunkKey, ItemKey, Element>,
+    ) -> Self::ItemIdxSet {
+        // Each branch's test only applies to that branch's own items, so both are
+        // evaluated here, and `test()` has nothing left to do.
+        Union::new(
+            chunk_storage.matching_idxs(&self.a),
+            chunk_storage.matching_idxs(&self.b),
+        )
+    }
+
+    fn test(&self, _element: &Element) -> bool {
+        true
+    }
+
+    fn test_chunk(&self, chunk_key: &ChunkKey) -> bool {
+        self.a.test_chunk(chunk_key) || self.b.test_chunk(chunk_key)
+    }
+
+    fn explain(&self, storage: &Storage<ChunkKey, ItemKey, Element>) -> Plan {
+        Plan::measure(
+            self,
+            storage,
+            "Or",
+            false,
+            vec![self.a.explain(storage), self.b.explain(storage)],
+        )
+    }
+}
@@ -9,10 +9,11 @@ use crate::traits::query::Query;
 use crate::traits::record::Record;
 use crate::traits::valid_key::{BorrowedKey, ValidKey};
 use crate::types::chunk_storage::ChunkStorage;
+use crate::types::plan::Plan;
 use crate::types::storage::Storage;
 use std::borrow::Borrow;
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::collections::{BTreeSet, HashSet};
 use std::fmt::Debug;
 use std::iter::Map;
@@ -63,14 +64,103 @@ where
     }
 }
 
-struct ChunkSecondaryIndex<IndexKey>
+struct ChunkSecondaryIndex<IndexKey, Map>
 where
     IndexKey: BorrowedKey + ?Sized,
     IndexKey::Owned: ValidKey,
 {
-    reverse_index: HashMap<IndexKey::Owned, Bitset>,
+    reverse_index: Map,
+    // keys that were added to (true) or removed from (false) the reverse index since the last update
+    key_changes: Vec<(IndexKey::Owned, bool)>,
 }
 
+/// The reverse index of a single chunk, mapping each index key to the internal indices of the
+/// elements that have that key. `SecondaryIndex` uses a `HashMap`, while `SortedIndex` uses a
+/// `BTreeMap` so that it can look up ranges of keys.
+pub(crate) trait ReverseIndex<IndexKey>: Default + MemoryUser
+where
+    IndexKey: BorrowedKey + ?Sized,
+    IndexKey::Owned: ValidKey,
+{
+    /// The internal indices of the elements that have the given key.
+    fn idxs(&self, index_key: &IndexKey) -> Option<&Bitset>;
+
+    /// Add an element to the given key. Returns true if no other element had that key.
+    fn insert_idx(&mut self, index_key: &IndexKey, idx: usize) -> bool;
+
+    /// Remove an element from the given key. Returns true if no other element has that key.
+    fn remove_idx(&mut self, index_key: &IndexKey, idx: usize) -> bool;
+
+    /// Every key that belongs to at least one element.
+    fn index_keys<'a>(&'a self) -> Box<dyn Iterator<Item = &'a IndexKey::Owned> + 'a>;
+
+    /// The internal indices of the elements of every key.
+    fn idx_sets<'a>(&'a self) -> Box<dyn Iterator<Item = &'a Bitset> + 'a>;
+
+    /// The internal indices of the elements of every key, mutably.
+    fn idx_sets_mut<'a>(&'a mut self) -> Box<dyn Iterator<Item = &'a mut Bitset> + 'a>;
+}
+
+type HashReverseIndex<IndexKey> = HashMap<<IndexKey as ToOwned>::Owned, Bitset>;
+
+macro_rules! reverse_index_impl {
+    ( $map:ident ) => {
+        impl<IndexKey> ReverseIndex<IndexKey> for $map<IndexKey::Owned, Bitset>
+        where
+            IndexKey: BorrowedKey + ?Sized,
+            IndexKey::Owned: ValidKey,
+        {
+            fn idxs(&self, index_key: &IndexKey) -> Option<&Bitset> {
+                self.get(index_key)
+            }
+
+            fn insert_idx(&mut self, index_key: &IndexKey, idx: usize) -> bool {
+                if let Some(idx_set) = self.get_mut(index_key) {
+                    idx_set.set(idx);
+                    return false;
+                }
+
+                let mut idx_set = Bitset::default();
+                idx_set.set(idx);
+                self.insert(index_key.to_owned(), idx_set);
+                true
+            }
+
+            fn remove_idx(&mut self, index_key: &IndexKey, idx: usize) -> bool {
+                let mut remove = false;
+
+                if let Some(idx_set) = self.get_mut(index_key) {
+                    idx_set.unset(idx);
+                    if idx_set.is_empty() {
+                        remove = true;
+                    }
+                }
+
+                if remove {
+                    self.remove(index_key);
+                }
+
+                remove
+            }
+
+            fn index_keys<'a>(&'a self) -> Box<dyn Iterator<Item = &'a IndexKey::Owned> + 'a> {
+                Box::new(self.keys())
+            }
+
+            fn idx_sets<'a>(&'a self) -> Box<dyn Iterator<Item = &'a Bitset> + 'a> {
+                Box::new(self.values())
+            }
+
+            fn idx_sets_mut<'a>(&'a mut self) -> Box<dyn Iterator<Item = &'a mut Bitset> + 'a> {
+                Box::new(self.values_mut())
+            }
+        }
+    };
+}
+
+reverse_index_impl!(HashMap);
+reverse_index_impl!(BTreeMap);
+
 /// A secondary index of the records in a `Storage`. You can attach as many `SecondaryIndices`
 /// to a given `Storage` as you want. Each `SecondaryIndex` will index each stored element under
 /// zero or more key values (but only one key type).
@@ -90,13 +180,22 @@ where
 /// | Index automobiles by model year            | `Option<i32>`        | `i32`              |
 /// | Index artwork by dominant color            | `HashSet<Color>`     | `Color`            |
 ///
+/// # Global Index
+///
+/// By default, a `SecondaryIndex` only knows which elements have a key within each chunk, so
+/// `Query::matching` has to bring every chunk up to date and look inside it, even if the key
+/// belongs to only one of them. If you have many chunks, use
+/// `SecondaryIndex::set_global_index` to also keep track of which chunks contain each key.
+/// Then `Query::matching` only has to look at the chunks that have changed since the last
+/// query, and skips chunks that don't contain the key at all.
+///
 /// # Panic
 ///
 /// A `SecondaryIndex` is associated with exactly one storage.
 /// If you attempt to use a `SecondaryIndex` with a `Storage` other than the one it was
 /// initialized with, it will panic.
 pub struct SecondaryIndex<ChunkKey, Element, IndexKeys, IndexKey>(
-    Arc<RwLock<SecondaryIndexImpl<ChunkKey, Element, IndexKeys, IndexKey>>>,
+    SharedImpl<ChunkKey, Element, IndexKeys, IndexKey, HashReverseIndex<IndexKey>>,
 )
 where
     ChunkKey: BorrowedKey + ?Sized,
@@ -119,28 +218,46 @@ where
     }
 }
 
-struct SecondaryIndexImpl<ChunkKey, Element, IndexKeys, IndexKey>
+/// The implementation shared by `SecondaryIndex` and `SortedIndex`, which differ only in the
+/// `ReverseIndex` of each chunk.
+pub(crate) struct SecondaryIndexImpl<ChunkKey, Element, IndexKeys, IndexKey, Map>
 where
     ChunkKey: BorrowedKey + ?Sized,
     ChunkKey::Owned: ValidKey,
     IndexKey: BorrowedKey + ?Sized,
     IndexKey::Owned: ValidKey,
     for<'k> IndexKeys: Clone + Debug + Default + Eq + KeySet<'k, IndexKey>,
+    Map: ReverseIndex<IndexKey>,
 {
     // parent_id, used to see that this SecondaryIndex isn't suddenly used with a different parent storage
     parent_id: u64,
     // gc_chunk_list, remember the chunks from our last update, so we can remove indices for newly-absent chunks
     gc_chunk_list: RVec<Option<ChunkKey::Owned>>,
     // rule for constructing index keys
-    rules: Arc<SummaryRules<Element, IndexKeys, ChunkSecondaryIndex<IndexKey>>>,
+    rules: Arc<SummaryRules<Element, IndexKeys, ChunkSecondaryIndex<IndexKey, Map>>>,
     // the index itself
-    index: HashMap<
-        ChunkKey::Owned,
-        Summarize<Element, IndexKeys, ChunkSecondaryIndex<IndexKey>>,
-        crate::internal::hasher::HasherImpl,
-    >,
+    index: ChunkIndices<ChunkKey, Element, IndexKeys, IndexKey, Map>,
+    // the chunks that contain each key, if the global index is enabled
+    global_index: Option<GlobalIndex<ChunkKey, IndexKey>>,
+    // tracks which chunks have changed since the global index was last brought up to date
+    changed_chunks: RVec<()>,
 }
 
+pub(crate) type SharedImpl<ChunkKey, Element, IndexKeys, IndexKey, Map> =
+    Arc<RwLock<SecondaryIndexImpl<ChunkKey, Element, IndexKeys, IndexKey, Map>>>;
+
+type ChunkIndices<ChunkKey, Element, IndexKeys, IndexKey, Map> = HashMap<
+    <ChunkKey as ToOwned>::Owned,
+    Summarize<Element, IndexKeys, ChunkSecondaryIndex<IndexKey, Map>>,
+    crate::internal::hasher::HasherImpl,
+>;
+
+type GlobalIndex<ChunkKey, IndexKey> = HashMap<
+    <IndexKey as ToOwned>::Owned,
+    HashSet<<ChunkKey as ToOwned>::Owned, crate::internal::hasher::HasherImpl>,
+    crate::internal::hasher::HasherImpl,
+>;
+
 impl<ChunkKey, Element, IndexKeys, IndexKey> SecondaryIndex<ChunkKey, Element, IndexKeys, IndexKey>
 where
     ChunkKey: BorrowedKey + ?Sized,
@@ -163,14 +280,40 @@ where
         Element: Record<ChunkKey, ItemKey>,
         F: Fn(&Element) -> Cow<IndexKeys> + Clone + Send + Sync + 'static,
     {
-        SecondaryIndex(Arc::new(RwLock::new(SecondaryIndexImpl {
-            parent_id: storage.id(),
-            gc_chunk_list: RVec::default(),
-            index: HashMap::with_hasher(crate::internal::hasher::HasherImpl::default()),
-            rules: Arc::new(
-                SecondaryIndexImpl::<ChunkKey, Element, IndexKeys, IndexKey>::indexing_rules(f),
-            ),
-        })))
+        SecondaryIndex(Arc::new(RwLock::new(SecondaryIndexImpl::new(storage, f))))
+    }
+
+    /// Enable or disable the global index, which keeps track of which chunks contain each key.
+    /// See the section on the global index above. Enabling the global index is a slow
+    /// operation, proportional to the number of chunks and keys that are already indexed.
+    ///
+    /// ```
+    /// use retriever::prelude::*;
+    /// use std::borrow::Cow;
+    ///
+    /// // Note that (A,B,C) implements Record<A,B>.
+    /// let mut storage : Storage<u64, u64, (u64, u64, &'static str)> = Storage::new();
+    /// let mut by_name : SecondaryIndex<u64, (u64, u64, &'static str), Option<&'static str>, &'static str> =
+    ///   SecondaryIndex::new(&storage, |x: &(u64, u64, &'static str)| Cow::Owned(Some(x.2)));
+    ///
+    /// by_name.set_global_index(true);
+    ///
+    /// for i in 0..100 {
+    ///   storage.add((i, 0, "puppy"));
+    /// }
+    ///
+    /// storage.add((50, 1, "kitten"));
+    ///
+    /// let query = Everything.matching(&by_name, Cow::Borrowed(&"kitten"));
+    /// assert_eq!(1, query.explain(&storage).chunks);
+    /// assert_eq!(vec![&(50, 1, "kitten")], storage.query(&query).collect::<Vec<_>>());
+    ///
+    /// # storage.validate();
+    /// # by_name.validate(&storage);
+    /// ```
+    pub fn set_global_index(&mut self, enabled: bool) -> &mut Self {
+        self.0.write().unwrap().set_global_index(enabled);
+        self
     }
 
     /// Panic if this storage is malformed or broken in any detectable way.
@@ -183,18 +326,109 @@ where
     {
         self.0.write().unwrap().validate(parent);
     }
+
+    /// Find every element with the given key, as the internal index of its chunk and its
+    /// internal index within that chunk. With the global index, only the chunks that contain
+    /// the key are visited; without it, every chunk is.
+    pub(crate) fn locate<ItemKey>(
+        &self,
+        parent: &Storage<ChunkKey, ItemKey, Element>,
+        index_key: &IndexKey,
+    ) -> Vec<(usize, usize)>
+    where
+        ItemKey: BorrowedKey + ?Sized,
+        ItemKey::Owned: ValidKey,
+        Element: Record<ChunkKey, ItemKey>,
+    {
+        let mut secondary_index_impl = self.0.write().unwrap();
+        assert_eq!(secondary_index_impl.parent_id, parent.id(), "Id mismatch: a secondary index may only be used with it's parent Storage, never any other Storage");
+
+        secondary_index_impl.gc(parent);
+
+        let mut result = Vec::new();
+
+        if secondary_index_impl.global_index.is_none() {
+            for idx in 0..parent.internal_rvec().len() {
+                let item_idxs = secondary_index_impl
+                    .update_chunk_idx(parent, idx)
+                    .idxs(index_key);
+
+                for item_idx in item_idxs.into_iter().flat_map(Bitset::iter).flatten() {
+                    result.push((idx, item_idx));
+                }
+            }
+
+            return result;
+        }
+
+        secondary_index_impl.update_changed_chunks(parent);
+
+        let chunk_keys = secondary_index_impl
+            .global_index
+            .as_ref()
+            .and_then(|global_index| global_index.get(index_key))
+            .into_iter()
+            .flatten();
+
+        for chunk_key in chunk_keys {
+            let idx = match parent.internal_idx_of(chunk_key.borrow()) {
+                Some(idx) => idx,
+                None => continue,
+            };
+            let item_idxs = secondary_index_impl
+                .reverse_index(chunk_key.borrow())
+                .and_then(|reverse_index| reverse_index.idxs(index_key));
+
+            for item_idx in item_idxs.into_iter().flat_map(Bitset::iter).flatten() {
+                result.push((idx, item_idx));
+            }
+        }
+
+        result
+    }
 }
 
-impl<ChunkKey, Element, IndexKeys, IndexKey>
-    SecondaryIndexImpl<ChunkKey, Element, IndexKeys, IndexKey>
+impl<ChunkKey, Element, IndexKeys, IndexKey, Map>
+    SecondaryIndexImpl<ChunkKey, Element, IndexKeys, IndexKey, Map>
 where
     ChunkKey: BorrowedKey + ?Sized,
     ChunkKey::Owned: ValidKey,
     IndexKey: BorrowedKey + ?Sized,
     IndexKey::Owned: ValidKey,
     for<'k> IndexKeys: Clone + Debug + Default + Eq + KeySet<'k, IndexKey>,
+    Map: ReverseIndex<IndexKey>,
 {
-    fn indexing_rules<F>(f: F) -> SummaryRules<Element, IndexKeys, ChunkSecondaryIndex<IndexKey>>
+    pub(crate) fn new<ItemKey, F>(storage: &Storage<ChunkKey, ItemKey, Element>, f: F) -> Self
+    where
+        ItemKey: BorrowedKey + ?Sized,
+        ItemKey::Owned: ValidKey,
+        Element: Record<ChunkKey, ItemKey>,
+        F: Fn(&Element) -> Cow<IndexKeys> + Clone + Send + Sync + 'static,
+    {
+        SecondaryIndexImpl {
+            parent_id: storage.id(),
+            gc_chunk_list: RVec::default(),
+            index: HashMap::with_hasher(crate::internal::hasher::HasherImpl::default()),
+            global_index: None,
+            changed_chunks: RVec::default(),
+            rules: Arc::new(Self::indexing_rules(f)),
+        }
+    }
+
+    pub(crate) fn parent_id(&self) -> u64 {
+        self.parent_id
+    }
+
+    /// The reverse index of a chunk, if that chunk has been indexed.
+    pub(crate) fn reverse_index(&self, chunk_key: &ChunkKey) -> Option<&Map> {
+        self.index
+            .get(chunk_key)
+            .map(|summarize| &summarize.peek().reverse_index)
+    }
+
+    fn indexing_rules<F>(
+        f: F,
+    ) -> SummaryRules<Element, IndexKeys, ChunkSecondaryIndex<IndexKey, Map>>
     where
         F: Fn(&Element) -> Cow<IndexKeys> + Clone + Send + Sync + 'static,
     {
@@ -210,27 +444,23 @@ where
             }),
             contribute: Arc::new(|new_index_keys, internal_idx, summary| {
                 for new_index_key in new_index_keys.iter_keys() {
-                    let idx_set = summary
+                    if summary
                         .reverse_index
-                        .entry(new_index_key.into_owned())
-                        .or_insert_with(Bitset::default);
-
-                    idx_set.set(internal_idx);
+                        .insert_idx(new_index_key.borrow(), internal_idx)
+                    {
+                        summary.key_changes.push((new_index_key.into_owned(), true));
+                    }
                 }
             }),
             uncontribute: Arc::new(|old_index_keys, internal_idx, summary| {
                 for old_index_key in old_index_keys.iter_keys() {
-                    let mut remove = false;
-
-                    if let Some(idx_set) = summary.reverse_index.get_mut(old_index_key.borrow()) {
-                        idx_set.unset(internal_idx);
-                        if idx_set.is_empty() {
-                            remove = true;
-                        }
-                    }
-
-                    if remove {
-                        summary.reverse_index.remove(old_index_key.borrow());
+                    if summary
+                        .reverse_index
+                        .remove_idx(old_index_key.borrow(), internal_idx)
+                    {
+                        summary
+                            .key_changes
+                            .push((old_index_key.into_owned(), false));
                     }
                 }
             }),
@@ -250,10 +480,111 @@ where
         let rules = &self.rules;
         let internal_storage = chunk_storage.internal_rvec();
 
-        index
+        let summarize = index
             .entry(chunk_key.to_owned())
-            .or_insert_with(|| Summarize::new(&internal_storage, Arc::clone(rules)))
-            .update(&internal_storage);
+            .or_insert_with(|| Summarize::new(internal_storage, Arc::clone(rules)));
+        summarize.update(internal_storage);
+
+        let key_changes = std::mem::take(&mut summarize.peek_mut().key_changes);
+
+        if let Some(global_index) = self.global_index.as_mut() {
+            for (index_key, added) in key_changes {
+                if added {
+                    global_index
+                        .entry(index_key)
+                        .or_default()
+                        .insert(chunk_key.to_owned());
+                } else {
+                    Self::unmark(global_index, index_key.borrow(), chunk_key);
+                }
+            }
+        }
+    }
+
+    /// Bring the chunk at the given internal index up to date, and return its reverse index.
+    /// Only valid immediately after `gc()`.
+    pub(crate) fn update_chunk_idx<ItemKey>(
+        &mut self,
+        parent: &Storage<ChunkKey, ItemKey, Element>,
+        idx: usize,
+    ) -> &Map
+    where
+        ItemKey: BorrowedKey + ?Sized,
+        ItemKey::Owned: ValidKey,
+        Element: Record<ChunkKey, ItemKey>,
+    {
+        let chunk_key = self.gc_chunk_list[idx]
+            .as_ref()
+            .cloned()
+            .expect("gc_chunk_list should not contain None immediately after gc");
+        self.update_chunk(chunk_key.borrow(), &parent.internal_rvec()[idx]);
+
+        self.reverse_index(chunk_key.borrow())
+            .expect("every chunk has a reverse index immediately after update_chunk")
+    }
+
+    /// Bring every chunk that has changed since the last call up to date, so that the global
+    /// index is accurate.
+    fn update_changed_chunks<ItemKey>(&mut self, parent: &Storage<ChunkKey, ItemKey, Element>)
+    where
+        ItemKey: BorrowedKey + ?Sized,
+        ItemKey::Owned: ValidKey,
+        Element: Record<ChunkKey, ItemKey>,
+    {
+        let mut changed = Vec::new();
+
+        self.changed_chunks
+            .reduce(parent.internal_rvec(), 1, |chunk_storages, _, idx| {
+                if !chunk_storages.is_empty() {
+                    changed.push(idx);
+                }
+
+                None
+            });
+
+        for idx in changed {
+            let chunk_storage = &parent.internal_rvec()[idx];
+            self.update_chunk(chunk_storage.chunk_key(), chunk_storage);
+        }
+    }
+
+    fn set_global_index(&mut self, enabled: bool) {
+        self.changed_chunks = RVec::default();
+        self.global_index = if enabled {
+            Some(self.build_global_index())
+        } else {
+            None
+        };
+    }
+
+    /// Build the global index from scratch, using the reverse index of every chunk.
+    fn build_global_index(&self) -> GlobalIndex<ChunkKey, IndexKey> {
+        let mut global_index = GlobalIndex::<ChunkKey, IndexKey>::default();
+
+        for (chunk_key, summarize) in self.index.iter() {
+            for index_key in summarize.peek().reverse_index.index_keys() {
+                global_index
+                    .entry(index_key.clone())
+                    .or_default()
+                    .insert(chunk_key.clone());
+            }
+        }
+
+        global_index
+    }
+
+    fn unmark(
+        global_index: &mut GlobalIndex<ChunkKey, IndexKey>,
+        index_key: &IndexKey,
+        chunk_key: &ChunkKey,
+    ) {
+        if let Some(chunk_keys) = global_index.get_mut(index_key) {
+            chunk_keys.remove(chunk_key);
+
+            if chunk_keys.is_empty() {
+                global_index.remove(index_key);
+            }
+        }
     }
 
     pub(crate) fn gc<ItemKey>(&mut self, parent: &Storage<ChunkKey, ItemKey, Element>)
@@ -262,7 +593,15 @@ where
         ItemKey::Owned: ValidKey,
         Element: Record<ChunkKey, ItemKey>,
     {
-        parent.gc(&mut self.gc_chunk_list, &mut self.index);
+        let removed = parent.gc(&mut self.gc_chunk_list, &mut self.index);
+
+        if let Some(global_index) = self.global_index.as_mut() {
+            for (chunk_key, summarize) in removed {
+                for index_key in summarize.peek().reverse_index.index_keys() {
+                    Self::unmark(global_index, index_key.borrow(), chunk_key.borrow());
+                }
+            }
+        }
     }
 
     /// Panic if this storage is malformed or broken in any way.
@@ -278,6 +617,11 @@ where
         for chunk_key in self.index.keys() {
             assert!(parent.internal_idx_of(chunk_key.borrow()).is_some());
         }
+
+        if self.global_index.is_some() {
+            self.update_changed_chunks(parent);
+            assert!(self.global_index == Some(self.build_global_index()));
+        }
     }
 }
 
@@ -316,21 +660,44 @@ where
     for<'k> IndexKeys: Clone + Debug + Default + Eq + KeySet<'k, IndexKey>,
     Q: Query<ChunkKey, ItemKey, Element> + Clone,
 {
-    type ChunkIdxSet = Q::ChunkIdxSet;
+    type ChunkIdxSet = Bitset;
     type ItemIdxSet = Intersection<Q::ItemIdxSet, Option<Bitset>>;
 
     fn chunk_idxs(&self, storage: &Storage<ChunkKey, ItemKey, Element>) -> Self::ChunkIdxSet {
         let mut secondary_index_impl = self.secondary_index.0.write().unwrap();
         assert_eq!(secondary_index_impl.parent_id, storage.id(), "Id mismatch: a secondary index may only be used with it's parent Storage, never any other Storage");
-        let result = self.query.chunk_idxs(storage);
+        let query_idxs = self.query.chunk_idxs(storage);
 
         secondary_index_impl.gc(storage);
-        for idx in result.clone().into_idx_iter().flatten() {
-            let chunk_key = secondary_index_impl.gc_chunk_list[idx]
+
+        if secondary_index_impl.global_index.is_some() {
+            secondary_index_impl.update_changed_chunks(storage);
+
+            let key_idxs: Bitset = secondary_index_impl
+                .global_index
                 .as_ref()
-                .cloned()
-                .expect("gc_chunk_list should not contain None immediately after gc");
-            secondary_index_impl.update_chunk(chunk_key.borrow(), &storage.internal_rvec()[idx]);
+                .and_then(|global_index| global_index.get(self.index_key.borrow()))
+                .into_iter()
+                .flatten()
+                .filter_map(|chunk_key| storage.internal_idx_of(chunk_key.borrow()))
+                .collect();
+
+            return IdxSet::intersection(query_idxs, key_idxs)
+                .into_idx_iter()
+                .flatten()
+                .collect();
+        }
+
+        let mut result = Bitset::default();
+
+        for idx in query_idxs.into_idx_iter().flatten() {
+            if secondary_index_impl
+                .update_chunk_idx(storage, idx)
+                .idxs(&*self.index_key)
+                .is_some()
+            {
+                result.set(idx);
+            }
         }
 
         result
@@ -344,14 +711,8 @@ where
         let secondary_index_impl = self.secondary_index.0.read().unwrap();
         let parent_idxs = self.query.item_idxs(chunk_key, chunk_storage);
         let ours_idxs: Option<Bitset> = secondary_index_impl
-            .index
-            .get(chunk_key)
-            .and_then(|map_summarize| {
-                map_summarize
-                    .peek()
-                    .reverse_index
-                    .get(self.index_key.borrow())
-            })
+            .reverse_index(chunk_key)
+            .and_then(|reverse_index| reverse_index.idxs(&*self.index_key))
             .cloned();
 
         IdxSet::intersection(parent_idxs, ours_idxs)
@@ -360,89 +721,32 @@ where
     fn test(&self, element: &Element) -> bool {
         self.query.test(element)
     }
-}
-
-#[cfg(test)]
-mod test {
-    #[test]
-    fn test_kitten_removal() {
-        use crate::queries::everything::*;
-        use crate::queries::secondary_index::*;
-        use std::borrow::Cow;
-
-        struct Kitten {
-            name: String,
-            colors: HashSet<String>,
-        }
-
-        impl Record<(), str> for Kitten {
-            fn chunk_key(self: &Kitten) -> Cow<()> {
-                Cow::Owned(())
-            }
-
-            fn item_key(self: &Kitten) -> Cow<str> {
-                Cow::Borrowed(&self.name)
-            }
-        }
-
-        let mut storage: Storage<(), str, Kitten> = Storage::new();
-        let by_color: SecondaryIndex<(), Kitten, HashSet<String>, str> =
-            SecondaryIndex::new(&storage, |kitten: &Kitten| Cow::Borrowed(&kitten.colors));
-
-        storage.add(Kitten {
-            name: String::from("mittens"),
-            colors: vec![String::from("black"), String::from("white")]
-                .into_iter()
-                .collect(),
-        });
 
-        storage.add(Kitten {
-            name: String::from("furball"),
-            colors: vec![String::from("orange")].into_iter().collect(),
-        });
-
-        storage.add(Kitten {
-            name: String::from("midnight"),
-            colors: vec![String::from("black")].into_iter().collect(),
-        });
-
-        storage.validate();
-        by_color.validate(&storage);
-
-        storage.remove(
-            &Everything.matching(&by_color, Cow::Borrowed("orange")),
-            std::mem::drop,
-        );
-
-        storage.validate();
-        by_color.validate(&storage);
-
-        storage.remove(
-            &Everything.matching(&by_color, Cow::Borrowed("white")),
-            std::mem::drop,
-        );
-
-        storage.validate();
-        by_color.validate(&storage);
-
-        storage.remove(
-            &Everything.matching(&by_color, Cow::Borrowed("black")),
-            std::mem::drop,
-        );
+    fn test_chunk(&self, chunk_key: &ChunkKey) -> bool {
+        self.query.test_chunk(chunk_key)
+    }
 
-        storage.validate();
-        by_color.validate(&storage);
+    fn explain(&self, storage: &Storage<ChunkKey, ItemKey, Element>) -> Plan {
+        Plan::measure(
+            self,
+            storage,
+            "MatchingSecondaryIndex",
+            false,
+            vec![self.query.explain(storage)],
+        )
     }
 }
 
-impl<IndexKey> Default for ChunkSecondaryIndex<IndexKey>
+impl<IndexKey, Map> Default for ChunkSecondaryIndex<IndexKey, Map>
 where
     IndexKey: BorrowedKey + ?Sized,
     IndexKey::Owned: ValidKey,
+    Map: ReverseIndex<IndexKey>,
 {
     fn default() -> Self {
         ChunkSecondaryIndex {
-            reverse_index: HashMap::default(),
+            reverse_index: Map::default(),
+            key_changes: Vec::new(),
         }
     }
 }
@@ -511,15 +815,16 @@ where
     }
 }
 
-impl<IndexKey> MemoryUser for ChunkSecondaryIndex<IndexKey>
+impl<IndexKey, Map> MemoryUser for ChunkSecondaryIndex<IndexKey, Map>
 where
     IndexKey: BorrowedKey + ?Sized,
     IndexKey::Owned: ValidKey,
+    Map: ReverseIndex<IndexKey>,
 {
     fn memory_usage(&self) -> MemoryUsage {
         let mut result = self.reverse_index.memory_usage();
 
-        for bs in self.reverse_index.values() {
+        for bs in self.reverse_index.idx_sets() {
             result = MemoryUsage::merge(result, bs.memory_usage());
         }
 
@@ -529,33 +834,46 @@ where
     fn shrink_with<F: Fn(&MemoryUsage) -> Option<usize>>(&mut self, f: F) {
         self.reverse_index.shrink_with(&f);
 
-        for bs in self.reverse_index.values_mut() {
+        for bs in self.reverse_index.idx_sets_mut() {
             bs.shrink_with(&f);
         }
     }
 }
 
-impl<ChunkKey, Element, IndexKeys, IndexKey> MemoryUser
-    for SecondaryIndexImpl<ChunkKey, Element, IndexKeys, IndexKey>
+impl<ChunkKey, Element, IndexKeys, IndexKey, Map> MemoryUser
+    for SecondaryIndexImpl<ChunkKey, Element, IndexKeys, IndexKey, Map>
 where
     ChunkKey: BorrowedKey + ?Sized,
     ChunkKey::Owned: ValidKey,
     IndexKey: BorrowedKey + ?Sized,
     IndexKey::Owned: ValidKey,
     for<'k> IndexKeys: Clone + Debug + Default + Eq + KeySet<'k, IndexKey>,
+    Map: ReverseIndex<IndexKey>,
 {
     fn memory_usage(&self) -> MemoryUsage {
-        let mut result = self.gc_chunk_list.memory_usage();
+        let mut result = MemoryUsage::merge(
+            self.gc_chunk_list.memory_usage(),
+            self.changed_chunks.memory_usage(),
+        );
 
         for s in self.index.values() {
             result = MemoryUsage::merge(result, s.memory_usage());
         }
 
+        if let Some(global_index) = self.global_index.as_ref() {
+            result = MemoryUsage::merge(result, global_index.memory_usage());
+        }
+
         result
     }
 
     fn shrink_with<F: Fn(&MemoryUsage) -> Option<usize>>(&mut self, f: F) {
         self.gc_chunk_list.shrink_with(&f);
+        self.changed_chunks.shrink_with(&f);
+
+        if let Some(global_index) = self.global_index.as_mut() {
+            global_index.shrink_with(&f);
+        }
 
         for i in self.index.values_mut() {
             i.shrink_with(&f);
@@ -580,3 +898,76 @@ where
         self.0.write().unwrap().shrink_with(f)
     }
 }
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn test_kitten_removal() {
+        use crate::queries::everything::*;
+        use crate::queries::secondary_index::*;
+        use std::borrow::Cow;
+
+        struct Kitten {
+            name: String,
+            colors: HashSet<String>,
+        }
+
+        impl Record<(), str> for Kitten {
+            fn chunk_key(self: &Kitten) -> Cow<'_, ()> {
+                Cow::Owned(())
+            }
+
+            fn item_key(self: &Kitten) -> Cow<'_, str> {
+                Cow::Borrowed(&self.name)
+            }
+        }
+
+        let mut storage: Storage<(), str, Kitten> = Storage::new();
+        let by_color: SecondaryIndex<(), Kitten, HashSet<String>, str> =
+            SecondaryIndex::new(&storage, |kitten: &Kitten| Cow::Borrowed(&kitten.colors));
+
+        storage.add(Kitten {
+            name: String::from("mittens"),
+            colors: vec![String::from("black"), String::from("white")]
+                .into_iter()
+                .collect(),
+        });
+
+        storage.add(Kitten {
+            name: String::from("furball"),
+            colors: vec![String::from("orange")].into_iter().collect(),
+        });
+
+        storage.add(Kitten {
+            name: String::from("midnight"),
+            colors: vec![String::from("black")].into_iter().collect(),
+        });
+
+        storage.validate();
+        by_color.validate(&storage);
+
+        storage.remove(
+            Everything.matching(&by_color, Cow::Borrowed("orange")),
+            std::mem::drop,
+        );
+
+        storage.validate();
+        by_color.validate(&storage);
+
+        storage.remove(
+            Everything.matching(&by_color, Cow::Borrowed("white")),
+            std::mem::drop,
+        );
+
+        storage.validate();
+        by_color.validate(&storage);
+
+        storage.remove(
+            Everything.matching(&by_color, Cow::Borrowed("black")),
+            std::mem::drop,
+        );
+
+        storage.validate();
+        by_color.validate(&storage);
+    }
+}
@@ -0,0 +1,342 @@
+use crate::bits::Bitset;
+use crate::idxsets::intersection::Intersection;
+use crate::internal::bounds;
+use crate::queries::secondary_index::{KeySet, SecondaryIndexImpl, SharedImpl};
+use crate::traits::idxset::IdxSet;
+use crate::traits::memory_usage::MemoryUsage;
+use crate::traits::memory_usage::MemoryUser;
+use crate::traits::query::Query;
+use crate::traits::record::Record;
+use crate::traits::valid_key::{BorrowedKey, ValidKey};
+use crate::types::chunk_storage::ChunkStorage;
+use crate::types::plan::Plan;
+use crate::types::storage::Storage;
+use std::borrow::Borrow;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Debug;
+use std::ops::Bound;
+use std::sync::Arc;
+use std::sync::RwLock;
+
+/// A Query matching a range of keys in a `SortedIndex`. Construct using `Query::in_range`,
+/// `Query::at_least` or `Query::at_most`.
+///
+/// # Type Parameters
+///
+/// Most of these type parameters match the same parameters of the backing `SortedIndex`.
+///
+/// * `Q`: A `Query`.
+/// * `ChunkKey`: Chunk key of the backing `Storage`.
+/// * `Element`: Element of the backing `Storage`.
+/// * `IndexKeys`: An non-order-preserving collection of elements of type `IndexKey`.
+/// * `IndexKey`: The indexing key of the backing `SortedIndex`.
+///
+pub struct InRange<'a, Q, ChunkKey, Element, IndexKeys, IndexKey>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+    IndexKey: BorrowedKey + ?Sized,
+    IndexKey::Owned: ValidKey + Borrow<IndexKey>,
+    for<'k> IndexKeys: Clone + Debug + Default + Eq + KeySet<'k, IndexKey>,
+{
+    query: Q,
+    sorted_index: SortedIndex<ChunkKey, Element, IndexKeys, IndexKey>,
+    start: Bound<Cow<'a, IndexKey>>,
+    end: Bound<Cow<'a, IndexKey>>,
+}
+
+impl<'a, Q, ChunkKey, Element, IndexKeys, IndexKey> Clone
+    for InRange<'a, Q, ChunkKey, Element, IndexKeys, IndexKey>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+    IndexKey: BorrowedKey + ?Sized,
+    IndexKey::Owned: ValidKey + Borrow<IndexKey>,
+    for<'k> IndexKeys: Clone + Debug + Default + Eq + KeySet<'k, IndexKey>,
+    Q: Clone,
+{
+    fn clone(&self) -> Self {
+        InRange {
+            query: self.query.clone(),
+            sorted_index: self.sorted_index.clone(),
+            start: self.start.clone(),
+            end: self.end.clone(),
+        }
+    }
+}
+
+type SortedReverseIndex<IndexKey> = BTreeMap<<IndexKey as ToOwned>::Owned, Bitset>;
+
+/// A sorted secondary index of the records in a `Storage`. A `SortedIndex` works just like a
+/// `SecondaryIndex`, except that it keeps its keys in order, so that you can query for a
+/// range of keys using `Query::in_range`, `Query::at_least` and `Query::at_most`.
+///
+/// # Type Parameters
+///
+/// * `ChunkKey`: The chunk key type of the `Storage`.
+/// * `Element`: The element type of the `Storage`.
+/// * `IndexKeys`: A collection containing the type parameter `IndexKey`. This could be an `Option`, `HashSet`, etc.
+/// * `IndexKey`: The type of the sorted index key. This is the key you'll use to look up `Elements` via this `SortedIndex`.
+///
+/// ```
+/// use retriever::prelude::*;
+/// use std::borrow::Cow;
+///
+/// // Note that (A,B,C) implements Record<A,B>.
+/// let mut storage : Storage<u64, &'static str, (u64, &'static str, u32)> = Storage::new();
+/// let by_age : SortedIndex<u64, (u64, &'static str, u32), Option<u32>, u32> =
+///   SortedIndex::new(&storage, |puppy: &(u64, &'static str, u32)| Cow::Owned(Some(puppy.2)));
+///
+/// storage.add((1, "Snoopy", 70));
+/// storage.add((1, "Odie", 52));
+/// storage.add((2, "Marmaduke", 66));
+/// storage.add((2, "Scooby-Doo", 51));
+///
+/// let mut names : Vec<&str> = storage.query(Everything.in_range(&by_age, 52..70)).map(|x| x.1).collect();
+/// names.sort();
+/// assert_eq!(vec!["Marmaduke", "Odie"], names);
+///
+/// assert_eq!(3, storage.query(Everything.at_least(&by_age, Cow::Owned(52))).count());
+/// assert_eq!(1, storage.query(Everything.at_most(&by_age, Cow::Owned(51))).count());
+///
+/// // Visit every puppy in order of age.
+/// let names : Vec<&str> = Everything.at_least(&by_age, Cow::Owned(0)).sorted(&storage).map(|x| x.1).collect();
+/// assert_eq!(vec!["Scooby-Doo", "Odie", "Marmaduke", "Snoopy"], names);
+///
+/// # storage.validate();
+/// # by_age.validate(&storage);
+/// ```
+///
+/// # Panic
+///
+/// A `SortedIndex` is associated with exactly one storage.
+/// If you attempt to use a `SortedIndex` with a `Storage` other than the one it was
+/// initialized with, it will panic.
+pub struct SortedIndex<ChunkKey, Element, IndexKeys, IndexKey>(
+    SharedImpl<ChunkKey, Element, IndexKeys, IndexKey, SortedReverseIndex<IndexKey>>,
+)
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+    IndexKey: BorrowedKey + ?Sized,
+    IndexKey::Owned: ValidKey,
+    for<'k> IndexKeys: Clone + Debug + Default + Eq + KeySet<'k, IndexKey>;
+
+impl<ChunkKey, Element, IndexKeys, IndexKey> Clone
+    for SortedIndex<ChunkKey, Element, IndexKeys, IndexKey>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+    IndexKey: BorrowedKey + ?Sized,
+    IndexKey::Owned: ValidKey,
+    for<'k> IndexKeys: Clone + Debug + Default + Eq + KeySet<'k, IndexKey>,
+{
+    fn clone(&self) -> Self {
+        SortedIndex(Arc::clone(&self.0))
+    }
+}
+
+impl<ChunkKey, Element, IndexKeys, IndexKey> SortedIndex<ChunkKey, Element, IndexKeys, IndexKey>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+    IndexKey: BorrowedKey + ?Sized,
+    IndexKey::Owned: ValidKey,
+    for<'k> IndexKeys: Clone + Debug + Default + Eq + KeySet<'k, IndexKey>,
+{
+    /// Create a new SortedIndex of a storage.
+    ///
+    /// The indexing rule needs to return a collection of 0 or more `IndexKeys` for each `Element`.
+    /// Collection types that will work well include: `Option`, `HashSet`, and `BTreeSet`.
+    ///
+    /// Try to re-use `SortedIndices` as much as possible. If you drop a `SortedIndex` and then
+    /// re-create it, the index has to be rebuilt, which might take a long time.
+    pub fn new<ItemKey, F>(storage: &Storage<ChunkKey, ItemKey, Element>, f: F) -> Self
+    where
+        ItemKey: BorrowedKey + ?Sized,
+        ItemKey::Owned: ValidKey,
+        Element: Record<ChunkKey, ItemKey>,
+        F: Fn(&Element) -> Cow<IndexKeys> + Clone + Send + Sync + 'static,
+    {
+        SortedIndex(Arc::new(RwLock::new(SecondaryIndexImpl::new(storage, f))))
+    }
+
+    /// Panic if this storage is malformed or broken in any detectable way.
+    /// This is a slow operation and you shouldn't use it unless you suspect a problem.
+    pub fn validate<ItemKey>(&self, parent: &Storage<ChunkKey, ItemKey, Element>)
+    where
+        ItemKey: BorrowedKey + ?Sized,
+        ItemKey::Owned: ValidKey,
+        Element: Record<ChunkKey, ItemKey>,
+    {
+        self.0.write().unwrap().validate(parent);
+    }
+}
+
+impl<'a, Q, ChunkKey, Element, IndexKeys, IndexKey>
+    InRange<'a, Q, ChunkKey, Element, IndexKeys, IndexKey>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+    IndexKey: BorrowedKey + ?Sized,
+    IndexKey::Owned: ValidKey + Borrow<IndexKey>,
+    for<'k> IndexKeys: Clone + Debug + Default + Eq + KeySet<'k, IndexKey>,
+{
+    pub(crate) fn new(
+        query: Q,
+        sorted_index: &SortedIndex<ChunkKey, Element, IndexKeys, IndexKey>,
+        start: Bound<Cow<'a, IndexKey>>,
+        end: Bound<Cow<'a, IndexKey>>,
+    ) -> Self {
+        InRange {
+            query,
+            sorted_index: sorted_index.clone(),
+            start,
+            end,
+        }
+    }
+
+    /// The bounds of this range, or `None` if the range is empty.
+    fn bounds(&self) -> Option<(Bound<&IndexKey>, Bound<&IndexKey>)> {
+        let start = bounds::borrowed(bounds::as_ref(&self.start));
+        let end = bounds::borrowed(bounds::as_ref(&self.end));
+
+        bounds::nonempty(start, end)
+    }
+
+    /// Iterate over every element that matches this query, in order of index key. An element
+    /// that has several index keys within the range is visited only once, at the least of them.
+    pub fn sorted<'s, ItemKey>(
+        &self,
+        storage: &'s Storage<ChunkKey, ItemKey, Element>,
+    ) -> impl Iterator<Item = &'s Element>
+    where
+        ItemKey: BorrowedKey + ?Sized,
+        ItemKey::Owned: ValidKey,
+        Element: Record<ChunkKey, ItemKey>,
+        Q: Query<ChunkKey, ItemKey, Element> + Clone,
+    {
+        let chunks = storage.internal_rvec();
+        let mut found: Vec<(IndexKey::Owned, usize, usize)> = Vec::new();
+
+        for chunk_idx in self.chunk_idxs(storage).into_idx_iter().flatten() {
+            let chunk = &chunks[chunk_idx];
+            let item_idxs: Bitset = self
+                .item_idxs(chunk.chunk_key(), chunk)
+                .into_idx_iter()
+                .flatten()
+                .filter(|idx| self.query.test(chunk.get_idx(*idx)))
+                .collect();
+
+            let sorted_index_impl = self.sorted_index.0.read().unwrap();
+            let reverse_index = sorted_index_impl.reverse_index(chunk.chunk_key());
+
+            if let (Some(reverse_index), Some(bounds)) = (reverse_index, self.bounds()) {
+                for (index_key, idxs) in reverse_index.range::<IndexKey, _>(bounds) {
+                    for idx in idxs.iter().flatten() {
+                        if item_idxs.get(idx) {
+                            found.push((index_key.clone(), chunk_idx, idx));
+                        }
+                    }
+                }
+            }
+        }
+
+        found.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut visited = HashSet::new();
+
+        found
+            .into_iter()
+            .filter(move |(_, chunk_idx, idx)| visited.insert((*chunk_idx, *idx)))
+            .map(move |(_, chunk_idx, idx)| chunks[chunk_idx].get_idx(idx))
+    }
+}
+
+impl<'a, Q, ChunkKey, ItemKey, Element, IndexKeys, IndexKey> Query<ChunkKey, ItemKey, Element>
+    for InRange<'a, Q, ChunkKey, Element, IndexKeys, IndexKey>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+    ItemKey: BorrowedKey + ?Sized,
+    ItemKey::Owned: ValidKey,
+    IndexKey: BorrowedKey + ?Sized,
+    IndexKey::Owned: ValidKey,
+    Element: Record<ChunkKey, ItemKey>,
+    for<'k> IndexKeys: Clone + Debug + Default + Eq + KeySet<'k, IndexKey>,
+    Q: Query<ChunkKey, ItemKey, Element> + Clone,
+{
+    type ChunkIdxSet = Q::ChunkIdxSet;
+    type ItemIdxSet = Intersection<Q::ItemIdxSet, Option<Bitset>>;
+
+    fn chunk_idxs(&self, storage: &Storage<ChunkKey, ItemKey, Element>) -> Self::ChunkIdxSet {
+        let mut sorted_index_impl = self.sorted_index.0.write().unwrap();
+        assert_eq!(sorted_index_impl.parent_id(), storage.id(), "Id mismatch: a sorted index may only be used with it's parent Storage, never any other Storage");
+        let result = self.query.chunk_idxs(storage);
+
+        sorted_index_impl.gc(storage);
+        for idx in result.clone().into_idx_iter().flatten() {
+            sorted_index_impl.update_chunk_idx(storage, idx);
+        }
+
+        result
+    }
+
+    fn item_idxs(
+        &self,
+        chunk_key: &ChunkKey,
+        chunk_storage: &ChunkStorage<ChunkKey, ItemKey, Element>,
+    ) -> Self::ItemIdxSet {
+        let sorted_index_impl = self.sorted_index.0.read().unwrap();
+        let parent_idxs = self.query.item_idxs(chunk_key, chunk_storage);
+        let ours_idxs: Option<Bitset> =
+            match (sorted_index_impl.reverse_index(chunk_key), self.bounds()) {
+                (Some(reverse_index), Some(bounds)) => Some(
+                    reverse_index
+                        .range::<IndexKey, _>(bounds)
+                        .flat_map(|(_, idxs)| idxs.iter().flatten())
+                        .collect(),
+                ),
+                _ => None,
+            };
+
+        IdxSet::intersection(parent_idxs, ours_idxs)
+    }
+
+    fn test(&self, element: &Element) -> bool {
+        self.query.test(element)
+    }
+
+    fn test_chunk(&self, chunk_key: &ChunkKey) -> bool {
+        self.query.test_chunk(chunk_key)
+    }
+
+    fn explain(&self, storage: &Storage<ChunkKey, ItemKey, Element>) -> Plan {
+        Plan::measure(
+            self,
+            storage,
+            "InRange",
+            false,
+            vec![self.query.explain(storage)],
+        )
+    }
+}
+
+impl<ChunkKey, Element, IndexKeys, IndexKey> MemoryUser
+    for SortedIndex<ChunkKey, Element, IndexKeys, IndexKey>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+    IndexKey: BorrowedKey + ?Sized,
+    IndexKey::Owned: ValidKey,
+    for<'k> IndexKeys: Clone + Debug + Default + Eq + KeySet<'k, IndexKey>,
+{
+    fn memory_usage(&self) -> MemoryUsage {
+        self.0.read().unwrap().memory_usage()
+    }
+
+    fn shrink_with<F: Fn(&MemoryUsage) -> Option<usize>>(&mut self, f: F) {
+        self.0.write().unwrap().shrink_with(f)
+    }
+}
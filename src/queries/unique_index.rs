@@ -0,0 +1,290 @@
+use crate::queries::secondary_index::{KeySet, SecondaryIndex};
+use crate::traits::query::Query;
+use crate::traits::record::Record;
+use crate::traits::valid_key::{BorrowedKey, ValidKey};
+use crate::types::error::Error;
+use crate::types::id::Id;
+use crate::types::storage::Storage;
+use std::borrow::Borrow;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
+use std::sync::Arc;
+
+type IndexingRule<Element, IndexKeys> = Arc<dyn Fn(&Element) -> Cow<IndexKeys> + Send + Sync>;
+
+/// A `SecondaryIndex` whose keys are unique across an entire `Storage`, such as email addresses
+/// or serial numbers. Each key of a `UniqueIndex` belongs to at most one element, which you can
+/// look up directly using `UniqueIndex::get_by`. A `UniqueIndex` enables the global index of
+/// its `SecondaryIndex`, so each lookup only visits the chunk that contains the key. If the
+/// global index is later disabled, lookups still work, but visit every chunk.
+///
+/// Uniqueness is enforced by `UniqueIndex::try_add` and `UniqueIndex::try_modify`, which report
+/// an `Error::KeyConflict` instead of changing the `Storage`. A `UniqueIndex` can't prevent
+/// conflicts that are introduced through the methods of `Storage` itself, but
+/// `UniqueIndex::validate` will detect them.
+///
+/// # Type Parameters
+///
+/// * `ChunkKey`: The chunk key type of the `Storage`.
+/// * `Element`: The element type of the `Storage`.
+/// * `IndexKeys`: A collection containing the type parameter `IndexKey`. This could be an `Option`, `HashSet`, etc.
+/// * `IndexKey`: The type of the unique index key. This is the key you'll use to look up `Elements` via this `UniqueIndex`.
+///
+/// ```
+/// use retriever::prelude::*;
+/// use retriever::Error;
+/// use std::borrow::Cow;
+///
+/// // Each user has a chunk, a user id and an email address.
+/// type User = (u64, u64, &'static str);
+///
+/// let mut storage : Storage<u64, u64, User> = Storage::new();
+/// let by_email : UniqueIndex<u64, User, Option<&'static str>, &'static str> =
+///   UniqueIndex::new(&storage, |user: &User| Cow::Owned(Some(user.2)));
+///
+/// by_email.try_add(&mut storage, (1, 1, "snoopy@example.com")).unwrap();
+/// by_email.try_add(&mut storage, (2, 1, "odie@example.com")).unwrap();
+///
+/// assert_eq!(Some(&(2, 1, "odie@example.com")), by_email.get_by(&storage, &"odie@example.com"));
+/// assert_eq!(None, by_email.get_by(&storage, &"garfield@example.com"));
+///
+/// // A second user can't sign up with the same email address, even in a different chunk.
+/// assert_eq!(
+///   Err(Error::KeyConflict { existing: ID.chunk(1).item(1), found: ID.chunk(3).item(1) }),
+///   by_email.try_add(&mut storage, (3, 1, "snoopy@example.com")).map(|_| ())
+/// );
+///
+/// // Or change their email address to one that is already taken.
+/// assert_eq!(
+///   Err(Error::KeyConflict { existing: ID.chunk(2).item(1), found: ID.chunk(1).item(1) }),
+///   by_email.try_modify(&mut storage, ID.chunk(1).item(1), |user| user.2 = "odie@example.com")
+/// );
+///
+/// assert_eq!(2, storage.iter().count());
+///
+/// # storage.validate();
+/// # by_email.validate(&storage);
+/// ```
+///
+/// # Panic
+///
+/// A `UniqueIndex` is associated with exactly one storage.
+/// If you attempt to use a `UniqueIndex` with a `Storage` other than the one it was
+/// initialized with, it will panic.
+pub struct UniqueIndex<ChunkKey, Element, IndexKeys, IndexKey>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+    IndexKey: BorrowedKey + ?Sized,
+    IndexKey::Owned: ValidKey,
+    for<'k> IndexKeys: Clone + Debug + Default + Eq + KeySet<'k, IndexKey>,
+{
+    secondary_index: SecondaryIndex<ChunkKey, Element, IndexKeys, IndexKey>,
+    rule: IndexingRule<Element, IndexKeys>,
+}
+
+impl<ChunkKey, Element, IndexKeys, IndexKey> Clone
+    for UniqueIndex<ChunkKey, Element, IndexKeys, IndexKey>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+    IndexKey: BorrowedKey + ?Sized,
+    IndexKey::Owned: ValidKey,
+    for<'k> IndexKeys: Clone + Debug + Default + Eq + KeySet<'k, IndexKey>,
+{
+    fn clone(&self) -> Self {
+        UniqueIndex {
+            secondary_index: self.secondary_index.clone(),
+            rule: Arc::clone(&self.rule),
+        }
+    }
+}
+
+impl<ChunkKey, Element, IndexKeys, IndexKey> UniqueIndex<ChunkKey, Element, IndexKeys, IndexKey>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+    IndexKey: BorrowedKey + ?Sized,
+    IndexKey::Owned: ValidKey,
+    for<'k> IndexKeys: Clone + Debug + Default + Eq + KeySet<'k, IndexKey>,
+{
+    /// Create a new UniqueIndex of a storage.
+    ///
+    /// The indexing rule works exactly as it does for `SecondaryIndex::new`.
+    pub fn new<ItemKey, F>(storage: &Storage<ChunkKey, ItemKey, Element>, f: F) -> Self
+    where
+        ItemKey: BorrowedKey + ?Sized,
+        ItemKey::Owned: ValidKey,
+        Element: Record<ChunkKey, ItemKey>,
+        F: Fn(&Element) -> Cow<IndexKeys> + Clone + Send + Sync + 'static,
+    {
+        let mut secondary_index = SecondaryIndex::new(storage, f.clone());
+        secondary_index.set_global_index(true);
+
+        UniqueIndex {
+            secondary_index,
+            rule: Arc::new(f),
+        }
+    }
+
+    /// The `SecondaryIndex` backing this `UniqueIndex`. Use it with `Query::matching` to combine
+    /// a lookup with other queries.
+    pub fn secondary_index(&self) -> &SecondaryIndex<ChunkKey, Element, IndexKeys, IndexKey> {
+        &self.secondary_index
+    }
+
+    /// Get the element with the given key, if there is one. If the uniqueness of this index has
+    /// been violated, any one of the elements with the given key is returned.
+    pub fn get_by<'a, ItemKey>(
+        &self,
+        storage: &'a Storage<ChunkKey, ItemKey, Element>,
+        key: &IndexKey,
+    ) -> Option<&'a Element>
+    where
+        ItemKey: BorrowedKey + ?Sized,
+        ItemKey::Owned: ValidKey,
+        Element: Record<ChunkKey, ItemKey>,
+    {
+        self.secondary_index
+            .locate(storage, key)
+            .into_iter()
+            .next()
+            .map(|(idx, item_idx)| storage.internal_rvec()[idx].get_idx(item_idx))
+    }
+
+    /// Add the given element to a `Storage`, or report an error if an element with the same id
+    /// already exists (`Error::DuplicateId`), or if another element already has any of the
+    /// element's keys (`Error::KeyConflict`). On error, the `Storage` is untouched.
+    #[allow(clippy::type_complexity)]
+    pub fn try_add<'a, ItemKey>(
+        &self,
+        storage: &'a mut Storage<ChunkKey, ItemKey, Element>,
+        element: Element,
+    ) -> Result<&'a mut Storage<ChunkKey, ItemKey, Element>, Error<ChunkKey::Owned, ItemKey::Owned>>
+    where
+        ItemKey: BorrowedKey + ?Sized,
+        ItemKey::Owned: ValidKey,
+        Element: Record<ChunkKey, ItemKey>,
+    {
+        if storage.get(&element).is_some() {
+            return Err(Error::DuplicateId(Id::cloned(&element)));
+        }
+
+        self.check(storage, &HashSet::new(), std::iter::once(&element))?;
+
+        Ok(storage.add(element))
+    }
+
+    /// Modify every element that matches a query, as `Storage::try_modify()`, or report an
+    /// `Error::KeyConflict` if the modified elements would share any key with each other or with
+    /// any other element. On `Error::KeyConflict`, the `Storage` is untouched. An
+    /// `Error::IdCollision` is handled exactly as it is by `Storage::try_modify()`.
+    ///
+    /// The callback is applied to a clone of each matching element, so that conflicts can be
+    /// found before anything is changed.
+    pub fn try_modify<ItemKey, Q, F>(
+        &self,
+        storage: &mut Storage<ChunkKey, ItemKey, Element>,
+        query: Q,
+        f: F,
+    ) -> Result<(), Error<ChunkKey::Owned, ItemKey::Owned>>
+    where
+        ItemKey: BorrowedKey + ?Sized,
+        ItemKey::Owned: ValidKey,
+        Element: Record<ChunkKey, ItemKey> + Clone,
+        Q: Query<ChunkKey, ItemKey, Element> + Clone,
+        F: Fn(&mut Element),
+    {
+        let modified: HashMap<Id<ChunkKey::Owned, ItemKey::Owned>, Element> = storage
+            .query(query.clone())
+            .map(|element| {
+                let mut modified = element.clone();
+                f(&mut modified);
+                (Id::cloned(element), modified)
+            })
+            .collect();
+        let ids = modified.keys().cloned().collect();
+
+        self.check(storage, &ids, modified.values())?;
+
+        storage.try_modify(query, |mut editor| {
+            let id: Id<ChunkKey::Owned, ItemKey::Owned> = Id::cloned(editor.get());
+
+            if let Some(element) = modified.get(&id) {
+                *editor.get_mut() = element.clone();
+            }
+        })
+    }
+
+    /// Report an `Error::KeyConflict` if any of the given elements would share a key with each
+    /// other, or with any element in the `Storage` other than those listed in `replaced`.
+    fn check<'e, ItemKey, I>(
+        &self,
+        storage: &Storage<ChunkKey, ItemKey, Element>,
+        replaced: &HashSet<Id<ChunkKey::Owned, ItemKey::Owned>>,
+        elements: I,
+    ) -> Result<(), Error<ChunkKey::Owned, ItemKey::Owned>>
+    where
+        ItemKey: BorrowedKey + ?Sized,
+        ItemKey::Owned: ValidKey,
+        Element: Record<ChunkKey, ItemKey> + 'e,
+        I: IntoIterator<Item = &'e Element>,
+    {
+        let mut claimed: HashMap<IndexKey::Owned, Id<ChunkKey::Owned, ItemKey::Owned>> =
+            HashMap::new();
+
+        for element in elements {
+            let found: Id<ChunkKey::Owned, ItemKey::Owned> = Id::cloned(element);
+            let index_keys = (self.rule)(element);
+
+            for index_key in index_keys.iter_keys() {
+                for (idx, item_idx) in self.secondary_index.locate(storage, index_key.borrow()) {
+                    let existing = Id::cloned(storage.internal_rvec()[idx].get_idx(item_idx));
+
+                    if !replaced.contains(&existing) {
+                        return Err(Error::KeyConflict { existing, found });
+                    }
+                }
+
+                if let Some(existing) = claimed.insert(index_key.into_owned(), found.clone()) {
+                    return Err(Error::KeyConflict { existing, found });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Panic if this index is malformed or broken in any detectable way, including if any key
+    /// belongs to more than one element.
+    /// This is a slow operation and you shouldn't use it unless you suspect a problem.
+    pub fn validate<ItemKey>(&self, parent: &Storage<ChunkKey, ItemKey, Element>)
+    where
+        ItemKey: BorrowedKey + ?Sized,
+        ItemKey::Owned: ValidKey,
+        Element: Record<ChunkKey, ItemKey>,
+    {
+        self.secondary_index.validate(parent);
+
+        let mut claimed: HashMap<IndexKey::Owned, Id<ChunkKey::Owned, ItemKey::Owned>> =
+            HashMap::new();
+
+        for element in parent.iter() {
+            let index_keys = (self.rule)(element);
+
+            for index_key in index_keys.iter_keys() {
+                if let Some(existing) = claimed.insert(index_key.into_owned(), Id::cloned(element))
+                {
+                    panic!(
+                        "{}",
+                        Error::KeyConflict {
+                            existing,
+                            found: Id::cloned(element),
+                        }
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use crate::reductions::GROUP_SIZE;
+use crate::traits::record::Record;
+use crate::traits::valid_key::{BorrowedKey, ValidKey};
+use crate::types::reduction::Reduction;
+use crate::types::storage::Storage;
+
+impl<ChunkKey, Element> Reduction<ChunkKey, Element, usize>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+{
+    /// Create a `Reduction` that counts the elements matching a predicate.
+    ///
+    /// ```
+    /// use retriever::prelude::*;
+    ///
+    /// let mut storage : Storage<u64, u64, (u64, u64, &'static str)> = Storage::new();
+    /// let mut puppies = Reduction::count_where(&storage, |x: &(u64, u64, &'static str)| x.2 == "puppy");
+    ///
+    /// storage.add((0, 1, "puppy"));
+    /// storage.add((0, 2, "kitten"));
+    /// storage.add((1, 3, "puppy"));
+    ///
+    /// assert_eq!(Some(&2), puppies.reduce(&storage));
+    /// ```
+    pub fn count_where<ItemKey, F>(
+        storage: &Storage<ChunkKey, ItemKey, Element>,
+        predicate: F,
+    ) -> Self
+    where
+        ItemKey: BorrowedKey + ?Sized,
+        ItemKey::Owned: ValidKey,
+        Element: Record<ChunkKey, ItemKey>,
+        F: Fn(&Element) -> bool + Clone + Send + Sync + 'static,
+    {
+        Reduction::new(
+            storage,
+            GROUP_SIZE,
+            move |element: &Element, was: &usize| {
+                let count = if predicate(element) { 1 } else { 0 };
+
+                if count != *was {
+                    Some(count)
+                } else {
+                    None
+                }
+            },
+            |counts: &[usize], was: &usize| {
+                let total = counts.iter().sum();
+
+                if total != *was {
+                    Some(total)
+                } else {
+                    None
+                }
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::prelude::*;
+    use crate::reductions::test::{check_reduction, Element};
+
+    #[test]
+    fn test_count_where() {
+        check_reduction(
+            |storage| Reduction::count_where(storage, |x: &Element| x.2 % 2 != 0),
+            |elements, count| {
+                assert_eq!(elements.iter().filter(|x| x.2 % 2 != 0).count(), *count);
+            },
+        );
+    }
+}
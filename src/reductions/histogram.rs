@@ -0,0 +1,97 @@
+use crate::reductions::GROUP_SIZE;
+use crate::traits::record::Record;
+use crate::traits::valid_key::{BorrowedKey, ValidKey};
+use crate::types::reduction::Reduction;
+use crate::types::storage::Storage;
+use std::collections::BTreeMap;
+
+impl<ChunkKey, Element, K> Reduction<ChunkKey, Element, BTreeMap<K, usize>>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+    K: Clone + Ord + Send + Sync + 'static,
+{
+    /// Create a `Reduction` that counts the elements with each key.
+    /// Keys that don't belong to any element are left out of the histogram entirely.
+    ///
+    /// ```
+    /// use retriever::prelude::*;
+    /// use std::collections::BTreeMap;
+    ///
+    /// // Each puppy has a litter, a name and a breed.
+    /// type Puppy = (u64, &'static str, &'static str);
+    ///
+    /// let mut storage : Storage<u64, &'static str, Puppy> = Storage::new();
+    /// let mut by_breed = Reduction::histogram_by(&storage, |puppy: &Puppy| puppy.2);
+    ///
+    /// storage.add((1, "Snoopy", "beagle"));
+    /// storage.add((1, "Odie", "dachshund"));
+    /// storage.add((2, "Shiloh", "beagle"));
+    ///
+    /// let histogram = by_breed.reduce(&storage).unwrap();
+    /// assert_eq!(Some(&2), histogram.get("beagle"));
+    /// assert_eq!(Some(&1), histogram.get("dachshund"));
+    /// assert_eq!(None, histogram.get("collie"));
+    /// ```
+    pub fn histogram_by<ItemKey, F>(storage: &Storage<ChunkKey, ItemKey, Element>, f: F) -> Self
+    where
+        ItemKey: BorrowedKey + ?Sized,
+        ItemKey::Owned: ValidKey,
+        Element: Record<ChunkKey, ItemKey>,
+        F: Fn(&Element) -> K + Clone + Send + Sync + 'static,
+    {
+        Reduction::new(
+            storage,
+            GROUP_SIZE,
+            move |element: &Element, was: &BTreeMap<K, usize>| {
+                let key = f(element);
+
+                if was.len() == 1 && was.get(&key) == Some(&1) {
+                    return None;
+                }
+
+                let mut histogram = BTreeMap::new();
+                histogram.insert(key, 1);
+                Some(histogram)
+            },
+            |histograms: &[BTreeMap<K, usize>], was: &BTreeMap<K, usize>| {
+                let mut total = BTreeMap::new();
+
+                for histogram in histograms {
+                    for (key, count) in histogram {
+                        *total.entry(key.clone()).or_insert(0) += count;
+                    }
+                }
+
+                if total != *was {
+                    Some(total)
+                } else {
+                    None
+                }
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::prelude::*;
+    use crate::reductions::test::{check_reduction, Element};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_histogram_by() {
+        check_reduction(
+            |storage| Reduction::histogram_by(storage, |x: &Element| x.2 % 5),
+            |elements, histogram| {
+                let mut expected = BTreeMap::new();
+
+                for x in elements {
+                    *expected.entry(x.2 % 5).or_insert(0) += 1;
+                }
+
+                assert_eq!(&expected, histogram);
+            },
+        );
+    }
+}
@@ -0,0 +1,90 @@
+use crate::reductions::GROUP_SIZE;
+use crate::traits::record::Record;
+use crate::traits::valid_key::{BorrowedKey, ValidKey};
+use crate::types::id::Id;
+use crate::types::reduction::Reduction;
+use crate::types::storage::Storage;
+
+impl<ChunkKey, Element, K, I> Reduction<ChunkKey, Element, (K, Id<ChunkKey::Owned, I>)>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey + Default,
+    I: ValidKey + Default,
+    K: Clone + Default + Ord,
+{
+    /// Create a `Reduction` that finds the largest key of any element, along with the `Id` of
+    /// the element with that key. If several elements share the largest key, any one of them
+    /// might be chosen.
+    ///
+    /// The key, chunk key and item key all need a `Default` value, which is never reported.
+    ///
+    /// ```
+    /// use retriever::prelude::*;
+    ///
+    /// // Each puppy has a litter, a name and an age in weeks.
+    /// type Puppy = (u64, &'static str, u64);
+    ///
+    /// let mut storage : Storage<u64, &'static str, Puppy> = Storage::new();
+    /// let mut oldest = Reduction::max_by_key(&storage, |puppy: &Puppy| puppy.2);
+    ///
+    /// storage.add((1, "Snoopy", 12));
+    /// storage.add((1, "Odie", 10));
+    /// storage.add((2, "Shiloh", 8));
+    ///
+    /// assert_eq!(Some(&(12, ID.chunk(1).item("Snoopy"))), oldest.reduce(&storage));
+    /// ```
+    pub fn max_by_key<ItemKey, F>(storage: &Storage<ChunkKey, ItemKey, Element>, f: F) -> Self
+    where
+        ItemKey: BorrowedKey + ToOwned<Owned = I> + ?Sized,
+        Element: Record<ChunkKey, ItemKey>,
+        F: Fn(&Element) -> K + Clone + Send + Sync + 'static,
+    {
+        Reduction::new(
+            storage,
+            GROUP_SIZE,
+            move |element: &Element, was: &(K, Id<ChunkKey::Owned, I>)| {
+                let largest = (
+                    f(element),
+                    Id::new(
+                        element.chunk_key().into_owned(),
+                        element.item_key().into_owned(),
+                    ),
+                );
+
+                if largest != *was {
+                    Some(largest)
+                } else {
+                    None
+                }
+            },
+            |summaries: &[(K, Id<ChunkKey::Owned, I>)], was: &(K, Id<ChunkKey::Owned, I>)| {
+                let largest = summaries.iter().max_by(|a, b| a.0.cmp(&b.0))?;
+
+                if largest != was {
+                    Some(largest.clone())
+                } else {
+                    None
+                }
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::prelude::*;
+    use crate::reductions::test::{check_reduction, Element};
+
+    #[test]
+    fn test_max_by_key() {
+        check_reduction(
+            |storage| Reduction::max_by_key(storage, |x: &Element| x.2),
+            |elements, (key, id)| {
+                assert_eq!(elements.iter().map(|x| x.2).max(), Some(*key));
+                assert!(elements
+                    .iter()
+                    .any(|x| ID.chunk(x.0).item(x.1) == *id && x.2 == *key));
+            },
+        );
+    }
+}
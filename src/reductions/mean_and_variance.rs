@@ -0,0 +1,198 @@
+use crate::reductions::GROUP_SIZE;
+use crate::traits::record::Record;
+use crate::traits::valid_key::{BorrowedKey, ValidKey};
+use crate::types::reduction::Reduction;
+use crate::types::storage::Storage;
+
+/// The count, mean and variance of some value of a collection of elements, maintained
+/// using Welford's online algorithm, which is numerically stable even for large collections.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MeanAndVariance {
+    count: u64,
+    mean: f64,
+    // the sum of squared differences from the mean
+    m2: f64,
+}
+
+impl MeanAndVariance {
+    /// The summary of a single value.
+    pub fn of(value: f64) -> Self {
+        MeanAndVariance {
+            count: 1,
+            mean: value,
+            m2: 0.0,
+        }
+    }
+
+    /// Combine two summaries into the summary of all of their values.
+    pub fn merge(self, other: Self) -> Self {
+        if self.count == 0 {
+            return other;
+        }
+
+        if other.count == 0 {
+            return self;
+        }
+
+        let count = self.count + other.count;
+        let delta = other.mean - self.mean;
+        let self_count = self.count as f64;
+        let other_count = other.count as f64;
+
+        MeanAndVariance {
+            count,
+            mean: self.mean + delta * other_count / count as f64,
+            m2: self.m2 + other.m2 + delta * delta * self_count * other_count / count as f64,
+        }
+    }
+
+    /// The number of values.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// The mean of all values, or `None` if there are no values.
+    pub fn mean(&self) -> Option<f64> {
+        if self.count > 0 {
+            Some(self.mean)
+        } else {
+            None
+        }
+    }
+
+    /// The population variance of all values, or `None` if there are no values.
+    pub fn variance(&self) -> Option<f64> {
+        if self.count > 0 {
+            Some(self.m2 / self.count as f64)
+        } else {
+            None
+        }
+    }
+
+    /// The sample variance of all values, or `None` if there are fewer than two values.
+    pub fn sample_variance(&self) -> Option<f64> {
+        if self.count > 1 {
+            Some(self.m2 / (self.count - 1) as f64)
+        } else {
+            None
+        }
+    }
+}
+
+impl<ChunkKey, Element> Reduction<ChunkKey, Element, MeanAndVariance>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+{
+    /// Create a `Reduction` that finds the count, mean and variance of some value of
+    /// every element.
+    ///
+    /// ```
+    /// use retriever::prelude::*;
+    ///
+    /// // Each puppy has a litter, a name and a weight in kilograms.
+    /// type Puppy = (u64, &'static str, f64);
+    ///
+    /// let mut storage : Storage<u64, &'static str, Puppy> = Storage::new();
+    /// let mut weight = Reduction::mean_and_variance(&storage, |puppy: &Puppy| puppy.2);
+    ///
+    /// storage.add((1, "Snoopy", 4.0));
+    /// storage.add((1, "Odie", 6.0));
+    /// storage.add((2, "Shiloh", 8.0));
+    ///
+    /// let summary = weight.reduce(&storage).unwrap();
+    /// assert_eq!(3, summary.count());
+    /// assert_eq!(Some(6.0), summary.mean());
+    /// assert_eq!(Some(4.0), summary.sample_variance());
+    /// ```
+    pub fn mean_and_variance<ItemKey, F>(
+        storage: &Storage<ChunkKey, ItemKey, Element>,
+        f: F,
+    ) -> Self
+    where
+        ItemKey: BorrowedKey + ?Sized,
+        ItemKey::Owned: ValidKey,
+        Element: Record<ChunkKey, ItemKey>,
+        F: Fn(&Element) -> f64 + Clone + Send + Sync + 'static,
+    {
+        Reduction::new(
+            storage,
+            GROUP_SIZE,
+            move |element: &Element, was: &MeanAndVariance| {
+                let summary = MeanAndVariance::of(f(element));
+
+                if summary != *was {
+                    Some(summary)
+                } else {
+                    None
+                }
+            },
+            |summaries: &[MeanAndVariance], was: &MeanAndVariance| {
+                let summary = summaries
+                    .iter()
+                    .fold(MeanAndVariance::default(), |total, summary| {
+                        total.merge(*summary)
+                    });
+
+                if summary != *was {
+                    Some(summary)
+                } else {
+                    None
+                }
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::reductions::test::{check_reduction, Element};
+
+    fn assert_close(expected: f64, actual: Option<f64>) {
+        let actual = actual.unwrap();
+        assert!(
+            (expected - actual).abs() <= 1e-9 * expected.abs().max(1.0),
+            "expected {}, found {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn test_mean_and_variance() {
+        // The offset makes a naive sum of squares lose precision.
+        check_reduction(
+            |storage| Reduction::mean_and_variance(storage, |x: &Element| x.2 as f64 + 1e6),
+            |elements, summary| {
+                let values: Vec<f64> = elements.iter().map(|x| x.2 as f64 + 1e6).collect();
+                let count = values.len() as f64;
+                let mean = values.iter().sum::<f64>() / count;
+                let m2: f64 = values.iter().map(|x| (x - mean) * (x - mean)).sum();
+
+                assert_eq!(values.len() as u64, summary.count());
+                assert_close(mean, summary.mean());
+                assert_close(m2 / count, summary.variance());
+                assert_close(m2 / (count - 1.0), summary.sample_variance());
+            },
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        let a = [1.0, 2.0, 3.0]
+            .iter()
+            .fold(MeanAndVariance::default(), |total, x| {
+                total.merge(MeanAndVariance::of(*x))
+            });
+        let b = MeanAndVariance::of(10.0);
+
+        assert_eq!(None, MeanAndVariance::default().mean());
+        assert_eq!(None, MeanAndVariance::of(1.0).sample_variance());
+        assert_eq!(a, a.merge(MeanAndVariance::default()));
+        assert_eq!(a, MeanAndVariance::default().merge(a));
+        assert_eq!(4, a.merge(b).count());
+        assert_close(4.0, a.merge(b).mean());
+        assert_close(12.5, a.merge(b).variance());
+    }
+}
@@ -1,5 +1,76 @@
-//pub mod sum;
-//pub mod smallest;
-//pub mod largest;
+/// Module for counting the elements that match a predicate.
+pub mod count;
+/// Module for counting the elements with each key.
+pub mod histogram;
+/// Module for finding the element with the largest key.
+pub mod largest;
+/// Module for finding the mean and variance of some value of the elements.
+pub mod mean_and_variance;
+/// Module for finding the element with the smallest key.
+pub mod smallest;
+/// Module for adding up some value of the elements.
+pub mod sum;
 //pub mod any;
 //pub mod set;
+
+/// The group size of the built-in reductions.
+const GROUP_SIZE: usize = 16;
+
+#[cfg(test)]
+mod test {
+    use crate::prelude::*;
+
+    /// The element type of the built-in reduction tests: a chunk key, an item key and a value.
+    pub(crate) type Element = (u64, u64, i64);
+
+    /// Put a reduction through a series of additions, modifications and removals. After each
+    /// one, `check` compares the summary against the elements that remain, unless there are no
+    /// elements left, in which case there must be no summary at all.
+    pub(crate) fn check_reduction<Summary, New, Check>(new: New, check: Check)
+    where
+        Summary: Default + Clone,
+        New: FnOnce(&Storage<u64, u64, Element>) -> Reduction<u64, Element, Summary>,
+        Check: Fn(&[Element], &Summary),
+    {
+        let mut storage: Storage<u64, u64, Element> = Storage::new();
+        let mut reduction = new(&storage);
+
+        let mut step = |storage: &Storage<u64, u64, Element>| {
+            let elements: Vec<Element> = storage.iter().cloned().collect();
+
+            match reduction.reduce(storage) {
+                Some(summary) => check(&elements, summary),
+                None => assert!(elements.is_empty()),
+            }
+        };
+
+        step(&storage);
+
+        for i in 0..100 {
+            storage.add((i % 4, i, (i * i % 17) as i64 - 8));
+        }
+
+        step(&storage);
+
+        storage.remove(ID.chunk(0).item(0), std::mem::drop);
+        storage.remove(ID.chunk(1).item(5), std::mem::drop);
+        step(&storage);
+
+        storage.modify(ID.chunk(2).item(6), |mut x| x.get_mut().2 = 1000);
+        step(&storage);
+
+        storage.modify(ID.chunk(3).item(7), |mut x| x.get_mut().2 = -1000);
+        step(&storage);
+
+        storage.remove_chunk(&2);
+        step(&storage);
+
+        storage.remove_chunk(&0);
+        storage.remove_chunk(&1);
+        step(&storage);
+
+        storage.remove(Everything, std::mem::drop);
+        step(&storage);
+        assert_eq!(0, storage.iter().count());
+    }
+}
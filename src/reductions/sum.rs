@@ -0,0 +1,78 @@
+use crate::reductions::GROUP_SIZE;
+use crate::traits::record::Record;
+use crate::traits::valid_key::{BorrowedKey, ValidKey};
+use crate::types::reduction::Reduction;
+use crate::types::storage::Storage;
+use std::ops::Add;
+
+impl<ChunkKey, Element, T> Reduction<ChunkKey, Element, T>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+    T: Add<Output = T> + Clone + Default + PartialEq + Send + Sync + 'static,
+{
+    /// Create a `Reduction` that adds up some value of every element.
+    ///
+    /// ```
+    /// use retriever::prelude::*;
+    ///
+    /// // Each order has a customer, an order number and a price.
+    /// let mut storage : Storage<u64, u64, (u64, u64, u64)> = Storage::new();
+    /// let mut revenue = Reduction::sum_by(&storage, |order: &(u64, u64, u64)| order.2);
+    ///
+    /// storage.add((0, 1, 100));
+    /// storage.add((0, 2, 25));
+    /// storage.add((1, 3, 40));
+    ///
+    /// assert_eq!(Some(&165), revenue.reduce(&storage));
+    /// ```
+    pub fn sum_by<ItemKey, F>(storage: &Storage<ChunkKey, ItemKey, Element>, f: F) -> Self
+    where
+        ItemKey: BorrowedKey + ?Sized,
+        ItemKey::Owned: ValidKey,
+        Element: Record<ChunkKey, ItemKey>,
+        F: Fn(&Element) -> T + Clone + Send + Sync + 'static,
+    {
+        Reduction::new(
+            storage,
+            GROUP_SIZE,
+            move |element: &Element, was: &T| {
+                let value = f(element);
+
+                if value != *was {
+                    Some(value)
+                } else {
+                    None
+                }
+            },
+            |values: &[T], was: &T| {
+                let total = values
+                    .iter()
+                    .cloned()
+                    .fold(T::default(), |total, value| total + value);
+
+                if total != *was {
+                    Some(total)
+                } else {
+                    None
+                }
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::prelude::*;
+    use crate::reductions::test::{check_reduction, Element};
+
+    #[test]
+    fn test_sum_by() {
+        check_reduction(
+            |storage| Reduction::sum_by(storage, |x: &Element| x.2),
+            |elements, sum| {
+                assert_eq!(elements.iter().map(|x| x.2).sum::<i64>(), *sum);
+            },
+        );
+    }
+}
@@ -0,0 +1,215 @@
+use crate::traits::chunk_store::ChunkStore;
+use crate::traits::valid_key::{BorrowedKey, ValidKey};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const EXTENSION: &str = "json";
+
+/// A `ChunkStore` that keeps each chunk in its own file inside a single directory.
+///
+/// Each file is named after the chunk key it contains, and holds the elements of
+/// that chunk as a JSON array. Saving a chunk writes a temporary file and renames it
+/// over the old one, so a crash part way through a save never leaves a truncated chunk behind.
+///
+/// Chunk keys are hex-encoded into file names, so very long chunk keys might exceed the
+/// file name limits of some file systems.
+///
+/// Requires the `serde` feature.
+///
+/// ```
+/// use retriever::prelude::*;
+/// use retriever::stores::filesystem::FileSystemChunkStore;
+///
+/// let dir = std::env::temp_dir().join(format!("retriever-doctest-{}", std::process::id()));
+/// let mut store = FileSystemChunkStore::new(&dir).unwrap();
+///
+/// let mut storage : Storage<u64, u64, (u64, u64, String)> = Storage::new();
+/// storage.add((1, 1, String::from("hello")));
+/// storage.add((2, 1, String::from("goodbye")));
+///
+/// // Write chunk 1 to disk and drop it from memory.
+/// storage.unload_chunk(&mut store, &1).unwrap();
+/// assert!(storage.get(&ID.chunk(1).item(1)).is_none());
+///
+/// // Read it back again.
+/// assert!(storage.load_chunk(&store, &1).unwrap());
+/// assert_eq!(Some(&(1, 1, String::from("hello"))), storage.get(&ID.chunk(1).item(1)));
+///
+/// # storage.validate();
+/// # std::fs::remove_dir_all(&dir).unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct FileSystemChunkStore {
+    path: PathBuf,
+}
+
+impl FileSystemChunkStore {
+    /// Construct a new `FileSystemChunkStore` in the given directory, creating the directory if
+    /// it does not already exist.
+    pub fn new<P: Into<PathBuf>>(path: P) -> io::Result<Self> {
+        let path = path.into();
+        fs::create_dir_all(&path)?;
+        Ok(FileSystemChunkStore { path })
+    }
+
+    /// The directory containing this `FileSystemChunkStore`.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn file_of<ChunkKey>(&self, chunk_key: &ChunkKey) -> io::Result<PathBuf>
+    where
+        ChunkKey: Serialize + ?Sized,
+    {
+        let bytes = serde_json::to_vec(chunk_key).map_err(invalid_data)?;
+        let mut file_name = String::with_capacity(bytes.len() * 2 + EXTENSION.len() + 1);
+
+        for byte in bytes {
+            write!(file_name, "{:02x}", byte).expect("writing to a String can't fail");
+        }
+
+        file_name.push('.');
+        file_name.push_str(EXTENSION);
+
+        Ok(self.path.join(file_name))
+    }
+
+    fn chunk_key_of<K>(file: &Path) -> Option<K>
+    where
+        K: DeserializeOwned,
+    {
+        if file.extension()? != EXTENSION {
+            return None;
+        }
+
+        let hex = file.file_stem()?.to_str()?;
+
+        if hex.len() % 2 != 0 {
+            return None;
+        }
+
+        let bytes = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+            .collect::<Option<Vec<u8>>>()?;
+
+        serde_json::from_slice(&bytes).ok()
+    }
+}
+
+fn invalid_data(e: serde_json::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+impl<ChunkKey, Element> ChunkStore<ChunkKey, Element> for FileSystemChunkStore
+where
+    ChunkKey: BorrowedKey + Serialize + ?Sized,
+    ChunkKey::Owned: ValidKey + DeserializeOwned,
+    Element: Serialize + DeserializeOwned,
+{
+    type Error = io::Error;
+
+    fn load(&self, chunk_key: &ChunkKey) -> Result<Option<Vec<Element>>, Self::Error> {
+        let data = match fs::read(self.file_of(chunk_key)?) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        serde_json::from_slice(&data)
+            .map(Some)
+            .map_err(invalid_data)
+    }
+
+    fn save(&mut self, chunk_key: &ChunkKey, elements: &[Element]) -> Result<(), Self::Error> {
+        let file = self.file_of(chunk_key)?;
+        let temp_file = file.with_extension("tmp");
+
+        fs::write(
+            &temp_file,
+            serde_json::to_vec(elements).map_err(invalid_data)?,
+        )?;
+        fs::rename(&temp_file, &file)
+    }
+
+    fn list(&self) -> Result<Vec<ChunkKey::Owned>, Self::Error> {
+        let mut result = Vec::new();
+
+        for entry in fs::read_dir(&self.path)? {
+            if let Some(chunk_key) = Self::chunk_key_of(&entry?.path()) {
+                result.push(chunk_key);
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn delete(&mut self, chunk_key: &ChunkKey) -> Result<(), Self::Error> {
+        match fs::remove_file(self.file_of(chunk_key)?) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    fn test_list_and_delete() {
+        let dir = std::env::temp_dir().join(format!(
+            "retriever-test-list-and-delete-{}",
+            std::process::id()
+        ));
+        let mut store = FileSystemChunkStore::new(&dir).unwrap();
+        let mut storage: Storage<String, u64, (String, u64, u64)> = Storage::new();
+
+        storage.add((String::from("north"), 1, 10));
+        storage.add((String::from("south"), 1, 20));
+        storage.add((String::from("south"), 2, 30));
+
+        storage
+            .flush_chunk(&mut store, &String::from("north"))
+            .unwrap();
+        storage
+            .flush_chunk(&mut store, &String::from("south"))
+            .unwrap();
+
+        let mut chunk_keys: Vec<String> =
+            ChunkStore::<String, (String, u64, u64)>::list(&store).unwrap();
+        chunk_keys.sort();
+        assert_eq!(
+            vec![String::from("north"), String::from("south")],
+            chunk_keys
+        );
+
+        // Flushing an empty chunk deletes it from the store.
+        storage.remove_chunk(&String::from("north"));
+        storage
+            .flush_chunk(&mut store, &String::from("north"))
+            .unwrap();
+
+        let chunk_keys: Vec<String> =
+            ChunkStore::<String, (String, u64, u64)>::list(&store).unwrap();
+        assert_eq!(vec![String::from("south")], chunk_keys);
+
+        let south: Option<Vec<(String, u64, u64)>> = store.load(&String::from("south")).unwrap();
+        assert_eq!(2, south.unwrap().len());
+
+        ChunkStore::<String, (String, u64, u64)>::delete(&mut store, &String::from("south"))
+            .unwrap();
+        ChunkStore::<String, (String, u64, u64)>::delete(&mut store, &String::from("south"))
+            .unwrap();
+        assert!(ChunkStore::<String, (String, u64, u64)>::list(&store)
+            .unwrap()
+            .is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
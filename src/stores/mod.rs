@@ -0,0 +1,3 @@
+/// Store each chunk as a separate file in the file system.
+#[cfg(feature = "serde")]
+pub mod filesystem;
@@ -0,0 +1,62 @@
+use crate::traits::valid_key::{BorrowedKey, ValidKey};
+use std::borrow::Borrow;
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+
+/// A `ChunkStore` is a backend that persists whole chunks, keyed by their chunk key.
+/// Use a `ChunkStore` with `Storage::load_chunk()`, `Storage::flush_chunk()` and
+/// `Storage::unload_chunk()` to page chunks in and out of an otherwise in-memory `Storage`.
+///
+/// # Type Parameters
+///
+/// * `ChunkKey`: The chunk key type of the `Storage`.
+/// * `Element`: The element type of the `Storage`.
+pub trait ChunkStore<ChunkKey, Element>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+{
+    /// The type of error produced by this `ChunkStore`.
+    type Error;
+
+    /// Load all of the elements of a chunk. Returns `None` if the chunk does not exist in this
+    /// `ChunkStore`.
+    fn load(&self, chunk_key: &ChunkKey) -> Result<Option<Vec<Element>>, Self::Error>;
+
+    /// Save all of the elements of a chunk, replacing anything previously saved for that chunk.
+    fn save(&mut self, chunk_key: &ChunkKey, elements: &[Element]) -> Result<(), Self::Error>;
+
+    /// List the chunk keys of every chunk in this `ChunkStore`.
+    fn list(&self) -> Result<Vec<ChunkKey::Owned>, Self::Error>;
+
+    /// Delete a chunk from this `ChunkStore`. Deleting a chunk that does not exist is not an error.
+    fn delete(&mut self, chunk_key: &ChunkKey) -> Result<(), Self::Error>;
+}
+
+/// A `BTreeMap` is a simple in-memory `ChunkStore`, mostly useful for testing.
+impl<ChunkKey, Element> ChunkStore<ChunkKey, Element> for BTreeMap<ChunkKey::Owned, Vec<Element>>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey + Borrow<ChunkKey>,
+    Element: Clone,
+{
+    type Error = Infallible;
+
+    fn load(&self, chunk_key: &ChunkKey) -> Result<Option<Vec<Element>>, Self::Error> {
+        Ok(self.get(chunk_key).cloned())
+    }
+
+    fn save(&mut self, chunk_key: &ChunkKey, elements: &[Element]) -> Result<(), Self::Error> {
+        self.insert(chunk_key.to_owned(), elements.to_vec());
+        Ok(())
+    }
+
+    fn list(&self) -> Result<Vec<ChunkKey::Owned>, Self::Error> {
+        Ok(self.keys().cloned().collect())
+    }
+
+    fn delete(&mut self, chunk_key: &ChunkKey) -> Result<(), Self::Error> {
+        self.remove(chunk_key);
+        Ok(())
+    }
+}
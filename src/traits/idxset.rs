@@ -1,5 +1,7 @@
 use crate::bits::Bitfield;
+use crate::idxsets::difference::Difference;
 use crate::idxsets::intersection::Intersection;
+use crate::idxsets::union::Union;
 use std::iter::Flatten;
 
 /// A set of `usize` indices.
@@ -21,6 +23,22 @@ pub trait IdxSet: Sized + Clone {
     {
         Intersection::new(self, b)
     }
+
+    /// Construct the union of this `IdxSet` with another `IdxSet`.
+    fn union<B>(self, b: B) -> Union<Self, B>
+    where
+        B: IdxSet,
+    {
+        Union::new(self, b)
+    }
+
+    /// Construct the difference of this `IdxSet` and another `IdxSet`.
+    fn difference<B>(self, b: B) -> Difference<Self, B>
+    where
+        B: IdxSet,
+    {
+        Difference::new(self, b)
+    }
 }
 
 impl<T> IdxSet for Option<T>
@@ -1,3 +1,5 @@
+/// Module for a trait that persists whole chunks to some backend.
+pub mod chunk_store;
 /// Module for a trait that represents internal index sets.
 pub mod idxset;
 /// Module for a trait that measures memory usage and provides for cleanup of unused allocation.
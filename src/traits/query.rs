@@ -1,12 +1,16 @@
+use crate::internal::bounds;
 use crate::queries::secondary_index::KeySet;
+use crate::queries::sorted_index::{InRange, SortedIndex};
 use crate::traits::idxset::IdxSet;
 use crate::traits::record::Record;
 use crate::traits::valid_key::{BorrowedKey, ValidKey};
 use crate::types::chunk_storage::ChunkStorage;
+use crate::types::plan::Plan;
 use crate::types::storage::Storage;
 use std::borrow::Borrow;
 use std::borrow::Cow;
 use std::fmt::Debug;
+use std::ops::{Bound, RangeBounds};
 use std::rc::Rc;
 use std::sync::Arc;
 
@@ -37,6 +41,69 @@ where
     /// Test whether or not a particular data element actually belongs to this `Query`.
     fn test(&self, element: &Element) -> bool;
 
+    /// Test whether or not a particular chunk could belong to this `Query`. This must be true
+    /// of every chunk visited by `chunk_idxs()`, and should be false of every other chunk.
+    ///
+    /// `Storage` only ever visits chunks from `chunk_idxs()`, and `item_idxs()` and `test()`
+    /// may assume they are never called for any other chunk. Combinators such as `Query::or()`
+    /// use this method to find out which of their branches apply to a chunk.
+    ///
+    /// There is deliberately no default implementation, since a query that answered `true` for
+    /// every chunk would match elements outside of its own chunks when combined with `Query::or()`,
+    /// `Query::and_not()` or `Not`. Requiring this method is a breaking change as of version 0.1.0.
+    fn test_chunk(&self, chunk_key: &ChunkKey) -> bool;
+
+    /// Describe how this `Query` is evaluated against a `Storage`, including how many chunks
+    /// and elements it examines and how many of those actually match. See `Plan`.
+    ///
+    /// Custom queries should override this with a call to `Plan::measure()`, to report
+    /// a readable name and their children.
+    ///
+    /// ```
+    /// use retriever::prelude::*;
+    /// use std::borrow::Cow;
+    ///
+    /// let mut storage : Storage<u64, u64, (u64, u64, u64)> = Storage::new();
+    /// let index : SecondaryIndex<u64, (u64, u64, u64), Option<bool>, bool> =
+    ///   SecondaryIndex::new(&storage, |x : &(u64, u64, u64)| Cow::Owned(Some(x.2 % 2 == 0)));
+    ///
+    /// for i in 0..100 {
+    ///   storage.add((i % 10, i, i * i));
+    /// }
+    ///
+    /// let query = Chunks([1, 2])
+    ///   .matching(&index, Cow::Owned(true))
+    ///   .filter(|x : &(u64, u64, u64)| x.2 > 1000);
+    /// let plan = query.explain(&storage);
+    ///
+    /// assert_eq!("Filter", plan.query);
+    /// assert!(plan.per_element_test);
+    /// assert_eq!(10, plan.items);
+    /// assert_eq!(7, plan.matches);
+    ///
+    /// let matching = &plan.children[0];
+    /// assert_eq!("MatchingSecondaryIndex", matching.query);
+    /// // Chunk 1 only contains odd numbers, so it is skipped.
+    /// assert_eq!(1, matching.chunks);
+    /// assert_eq!(10, matching.items);
+    ///
+    /// let chunks = &matching.children[0];
+    /// assert_eq!("Chunks", chunks.query);
+    /// assert_eq!(20, chunks.items);
+    /// ```
+    fn explain(&self, storage: &Storage<ChunkKey, ItemKey, Element>) -> Plan
+    where
+        Element: Record<ChunkKey, ItemKey>,
+    {
+        Plan::measure(
+            self,
+            storage,
+            std::any::type_name::<Self>(),
+            true,
+            Vec::new(),
+        )
+    }
+
     /// Filter this `Query` according to some predicate.
     fn filter<F>(self, f: F) -> crate::queries::filter::Filter<Self, F>
     where
@@ -46,6 +113,61 @@ where
         crate::queries::filter::Filter::new(self, f)
     }
 
+    /// Visit every element that matches either this `Query` or another `Query`.
+    ///
+    /// Both queries are combined at the level of their index sets, so combining two
+    /// `SecondaryIndex` lookups never visits elements that match neither of them.
+    ///
+    /// ```
+    /// use retriever::prelude::*;
+    ///
+    /// let mut storage : Storage<u64, u64, (u64, u64, &'static str)> = Storage::new();
+    ///
+    /// storage.add((0, 0, "apple"));
+    /// storage.add((0, 1, "banana"));
+    /// storage.add((1, 0, "cherry"));
+    /// storage.add((2, 0, "date"));
+    ///
+    /// let mut fruits : Vec<_> = storage.query(ID.chunk(0).item(1).or(Chunks([2]))).map(|x| x.2).collect();
+    /// fruits.sort();
+    /// assert_eq!(vec!["banana", "date"], fruits);
+    ///
+    /// let mut fruits : Vec<_> = storage.query(Chunks([0, 1]).and_not(ID.chunk(0).item(0))).map(|x| x.2).collect();
+    /// fruits.sort();
+    /// assert_eq!(vec!["banana", "cherry"], fruits);
+    ///
+    /// let mut fruits : Vec<_> = storage.query(Not(Chunks([0]))).map(|x| x.2).collect();
+    /// fruits.sort();
+    /// assert_eq!(vec!["cherry", "date"], fruits);
+    /// ```
+    fn or<B>(self, other: B) -> crate::queries::or::Or<Self, B, Element>
+    where
+        Self: Sized,
+        B: Query<ChunkKey, ItemKey, Element>,
+    {
+        crate::queries::or::Or::new(self, other)
+    }
+
+    /// Visit every element that matches this `Query` but not another `Query`.
+    /// See `Query::or` for an example.
+    fn and_not<B>(self, other: B) -> crate::queries::and_not::AndNot<Self, B, Element>
+    where
+        Self: Sized,
+        B: Query<ChunkKey, ItemKey, Element>,
+    {
+        crate::queries::and_not::AndNot::new(self, other)
+    }
+
+    /// Erase the type of this `Query`, so that it can be chosen at runtime or stored in a
+    /// struct field. See `DynQuery` for an example.
+    fn boxed<'a>(self) -> crate::queries::dyn_query::DynQuery<'a, ChunkKey, ItemKey, Element>
+    where
+        Self: Sized + Send + Sync + 'a,
+        Element: Record<ChunkKey, ItemKey>,
+    {
+        crate::queries::dyn_query::DynQuery::new(self)
+    }
+
     /// Filter this `Query` by matching against a `SecondaryIndex`.
     ///
     /// ```
@@ -123,10 +245,64 @@ where
     {
         crate::queries::secondary_index::MatchingSecondaryIndex::new(self, secondary_index, key)
     }
+
+    /// Filter this `Query` to elements with a key in the given range of a `SortedIndex`.
+    /// See `SortedIndex` for an example.
+    fn in_range<'a, IndexKeys, IndexKey, R>(
+        self,
+        sorted_index: &'a SortedIndex<ChunkKey, Element, IndexKeys, IndexKey>,
+        range: R,
+    ) -> InRange<'a, Self, ChunkKey, Element, IndexKeys, IndexKey>
+    where
+        Self: Sized,
+        IndexKey: BorrowedKey + ?Sized,
+        IndexKey::Owned: ValidKey,
+        Element: Record<ChunkKey, ItemKey>,
+        for<'k> IndexKeys: Clone + Debug + Default + Eq + KeySet<'k, IndexKey>,
+        R: RangeBounds<IndexKey::Owned>,
+    {
+        let start = bounds::owned(range.start_bound());
+        let end = bounds::owned(range.end_bound());
+
+        InRange::new(self, sorted_index, start, end)
+    }
+
+    /// Filter this `Query` to elements with a key greater than or equal to the given key of a
+    /// `SortedIndex`. See `SortedIndex` for an example.
+    fn at_least<'a, IndexKeys, IndexKey>(
+        self,
+        sorted_index: &'a SortedIndex<ChunkKey, Element, IndexKeys, IndexKey>,
+        key: Cow<'a, IndexKey>,
+    ) -> InRange<'a, Self, ChunkKey, Element, IndexKeys, IndexKey>
+    where
+        Self: Sized,
+        IndexKey: BorrowedKey + ?Sized,
+        IndexKey::Owned: ValidKey,
+        Element: Record<ChunkKey, ItemKey>,
+        for<'k> IndexKeys: Clone + Debug + Default + Eq + KeySet<'k, IndexKey>,
+    {
+        InRange::new(self, sorted_index, Bound::Included(key), Bound::Unbounded)
+    }
+
+    /// Filter this `Query` to elements with a key less than or equal to the given key of a
+    /// `SortedIndex`. See `SortedIndex` for an example.
+    fn at_most<'a, IndexKeys, IndexKey>(
+        self,
+        sorted_index: &'a SortedIndex<ChunkKey, Element, IndexKeys, IndexKey>,
+        key: Cow<'a, IndexKey>,
+    ) -> InRange<'a, Self, ChunkKey, Element, IndexKeys, IndexKey>
+    where
+        Self: Sized,
+        IndexKey: BorrowedKey + ?Sized,
+        IndexKey::Owned: ValidKey,
+        Element: Record<ChunkKey, ItemKey>,
+        for<'k> IndexKeys: Clone + Debug + Default + Eq + KeySet<'k, IndexKey>,
+    {
+        InRange::new(self, sorted_index, Bound::Unbounded, Bound::Included(key))
+    }
 }
 
-impl<'a, Q, ChunkKey: ToOwned, ItemKey: ToOwned, Element> Query<ChunkKey, ItemKey, Element>
-    for &'a Q
+impl<Q, ChunkKey: ToOwned, ItemKey: ToOwned, Element> Query<ChunkKey, ItemKey, Element> for &Q
 where
     Q: Query<ChunkKey, ItemKey, Element>,
     ChunkKey: BorrowedKey + ?Sized,
@@ -152,6 +328,17 @@ where
     fn test(&self, element: &Element) -> bool {
         Q::test(self, element)
     }
+
+    fn test_chunk(&self, chunk_key: &ChunkKey) -> bool {
+        Q::test_chunk(self, chunk_key)
+    }
+
+    fn explain(&self, storage: &Storage<ChunkKey, ItemKey, Element>) -> Plan
+    where
+        Element: Record<ChunkKey, ItemKey>,
+    {
+        Q::explain(self, storage)
+    }
 }
 
 impl<Q, ChunkKey: ToOwned, ItemKey: ToOwned, Element> Query<ChunkKey, ItemKey, Element> for Rc<Q>
@@ -180,6 +367,17 @@ where
     fn test(&self, element: &Element) -> bool {
         Q::test(Rc::as_ref(self), element)
     }
+
+    fn test_chunk(&self, chunk_key: &ChunkKey) -> bool {
+        Q::test_chunk(Rc::as_ref(self), chunk_key)
+    }
+
+    fn explain(&self, storage: &Storage<ChunkKey, ItemKey, Element>) -> Plan
+    where
+        Element: Record<ChunkKey, ItemKey>,
+    {
+        Q::explain(Rc::as_ref(self), storage)
+    }
 }
 
 impl<Q, ChunkKey: ToOwned, ItemKey: ToOwned, Element> Query<ChunkKey, ItemKey, Element> for Arc<Q>
@@ -208,6 +406,17 @@ where
     fn test(&self, element: &Element) -> bool {
         Q::test(Arc::as_ref(self), element)
     }
+
+    fn test_chunk(&self, chunk_key: &ChunkKey) -> bool {
+        Q::test_chunk(Arc::as_ref(self), chunk_key)
+    }
+
+    fn explain(&self, storage: &Storage<ChunkKey, ItemKey, Element>) -> Plan
+    where
+        Element: Record<ChunkKey, ItemKey>,
+    {
+        Q::explain(Arc::as_ref(self), storage)
+    }
 }
 
 impl<'a, Q, ChunkKey, ItemKey, Element> Query<ChunkKey, ItemKey, Element> for Cow<'a, Q>
@@ -236,4 +445,15 @@ where
     fn test(&self, element: &Element) -> bool {
         Q::test(Cow::borrow(self), element)
     }
+
+    fn test_chunk(&self, chunk_key: &ChunkKey) -> bool {
+        Q::test_chunk(Cow::borrow(self), chunk_key)
+    }
+
+    fn explain(&self, storage: &Storage<ChunkKey, ItemKey, Element>) -> Plan
+    where
+        Element: Record<ChunkKey, ItemKey>,
+    {
+        Q::explain(Cow::borrow(self), storage)
+    }
 }
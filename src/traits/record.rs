@@ -15,10 +15,10 @@ where
     /// share the same chunk key. `Records` with the same chunk key are stored physically together.
     /// It is easy to iterate over all `Records` in a single chunk, and it's possible to remove an entire
     /// chunk in constant time.
-    fn chunk_key(&self) -> Cow<ChunkKey>;
+    fn chunk_key(&self) -> Cow<'_, ChunkKey>;
 
     /// Provide a item key for this record. The item key must be unique within each chunk.
-    fn item_key(&self) -> Cow<ItemKey>;
+    fn item_key(&self) -> Cow<'_, ItemKey>;
 }
 
 impl<ChunkKey, ItemKey, R> Record<ChunkKey, ItemKey> for &R
@@ -27,11 +27,11 @@ where
     ItemKey: ValidKey,
     R: Record<ChunkKey, ItemKey>,
 {
-    fn chunk_key(&self) -> Cow<ChunkKey> {
+    fn chunk_key(&self) -> Cow<'_, ChunkKey> {
         (*self).chunk_key()
     }
 
-    fn item_key(&self) -> Cow<ItemKey> {
+    fn item_key(&self) -> Cow<'_, ItemKey> {
         (*self).item_key()
     }
 }
@@ -40,11 +40,11 @@ impl<ItemKey, R> Record<(), ItemKey> for (ItemKey, R)
 where
     ItemKey: ValidKey,
 {
-    fn chunk_key(&self) -> Cow<()> {
+    fn chunk_key(&self) -> Cow<'_, ()> {
         Cow::Owned(())
     }
 
-    fn item_key(&self) -> Cow<ItemKey> {
+    fn item_key(&self) -> Cow<'_, ItemKey> {
         Cow::Borrowed(&self.0)
     }
 }
@@ -54,11 +54,11 @@ where
     ChunkKey: ValidKey,
     ItemKey: ValidKey,
 {
-    fn chunk_key(&self) -> Cow<ChunkKey> {
+    fn chunk_key(&self) -> Cow<'_, ChunkKey> {
         Cow::Borrowed(&self.0)
     }
 
-    fn item_key(&self) -> Cow<ItemKey> {
+    fn item_key(&self) -> Cow<'_, ItemKey> {
         Cow::Borrowed(&self.1)
     }
 }
@@ -0,0 +1,223 @@
+use super::id::Id;
+use super::storage::Storage;
+use crate::internal::hasher::HasherImpl;
+use crate::internal::mr::rvec::RVec;
+use crate::traits::record::Record;
+use crate::traits::valid_key::{BorrowedKey, ValidKey};
+use std::borrow::Borrow;
+use std::collections::{HashMap, HashSet};
+
+/// A change to a single element of a `Storage`, as reported by `ChangeCursor::changes_since()`.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Change<ChunkKey, ItemKey> {
+    /// An element with this id was added.
+    Added(Id<ChunkKey, ItemKey>),
+    /// The element with this id might have been modified.
+    Modified(Id<ChunkKey, ItemKey>),
+    /// The element with this id was removed.
+    Removed(Id<ChunkKey, ItemKey>),
+}
+
+impl<ChunkKey, ItemKey> Change<ChunkKey, ItemKey> {
+    /// The id of the changed element.
+    pub fn id(&self) -> &Id<ChunkKey, ItemKey> {
+        match self {
+            Change::Added(id) | Change::Modified(id) | Change::Removed(id) => id,
+        }
+    }
+}
+
+/// A `ChangeCursor` remembers a point in the history of a `Storage`, and reports which
+/// elements have been added, modified or removed since then.
+///
+/// Change tracking is coarse: when any element is mutably borrowed, neighboring elements in
+/// the same chunk might be reported as `Change::Modified` too. `Change::Added` and
+/// `Change::Removed` are always exact. An element that was removed and then re-added is
+/// reported as `Change::Modified`.
+///
+/// # Type Parameters
+///
+/// * `ChunkKey`: The chunk key type of the `Storage`
+/// * `ItemKey`: The item key type of the `Storage`
+/// * `Element`: The element type of the `Storage`
+///
+/// # Example
+///
+/// ```
+/// use retriever::prelude::*;
+/// use retriever::types::change_cursor::{Change, ChangeCursor};
+///
+/// let mut storage : Storage<u64, u64, (u64, u64, &'static str)> = Storage::new();
+/// storage.add((1, 1, "apple"));
+/// storage.add((1, 2, "banana"));
+///
+/// let mut cursor = ChangeCursor::new(&storage);
+/// assert!(cursor.changes_since(&storage).is_empty());
+///
+/// storage.add((2, 1, "cherry"));
+/// storage.remove(ID.chunk(1).item(2), std::mem::drop);
+///
+/// // (1,1) was untouched, but it is reported as possibly modified because it is
+/// // stored right next to (1,2).
+/// assert_eq!(
+///   vec![
+///     Change::Modified(ID.chunk(1).item(1)),
+///     Change::Removed(ID.chunk(1).item(2)),
+///     Change::Added(ID.chunk(2).item(1)),
+///   ],
+///   cursor.changes_since(&storage)
+/// );
+///
+/// // The cursor has moved forward, so there's nothing new to report.
+/// assert!(cursor.changes_since(&storage).is_empty());
+/// ```
+pub struct ChangeCursor<ChunkKey: ?Sized, ItemKey: ?Sized, Element>
+where
+    ChunkKey: BorrowedKey,
+    ChunkKey::Owned: ValidKey,
+    ItemKey: BorrowedKey,
+    ItemKey::Owned: ValidKey,
+{
+    parent_id: u64,
+    chunk_list: RVec<Option<ChunkKey::Owned>>,
+    chunks: HashMap<ChunkKey::Owned, ChunkCursor<ItemKey::Owned>, HasherImpl>,
+    _marker: std::marker::PhantomData<fn(&Element)>,
+}
+
+// The item keys of a single chunk, as of the last time we looked.
+struct ChunkCursor<ItemKey> {
+    parent_id: u64,
+    item_list: RVec<Option<ItemKey>>,
+}
+
+impl<ChunkKey, ItemKey, Element> ChangeCursor<ChunkKey, ItemKey, Element>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+    ItemKey: BorrowedKey + ?Sized,
+    ItemKey::Owned: ValidKey,
+    Element: Record<ChunkKey, ItemKey>,
+{
+    /// Create a new `ChangeCursor` positioned at the current state of the given `Storage`.
+    pub fn new(storage: &Storage<ChunkKey, ItemKey, Element>) -> Self {
+        let mut result = ChangeCursor {
+            parent_id: storage.id(),
+            chunk_list: RVec::default(),
+            chunks: HashMap::with_hasher(HasherImpl::default()),
+            _marker: std::marker::PhantomData,
+        };
+
+        result.changes_since(storage);
+
+        result
+    }
+
+    /// List every element that has been added, modified or removed since this `ChangeCursor`
+    /// was created, or since the last call to this method. The changes are sorted by id.
+    pub fn changes_since(
+        &mut self,
+        storage: &Storage<ChunkKey, ItemKey, Element>,
+    ) -> Vec<Change<ChunkKey::Owned, ItemKey::Owned>> {
+        assert_eq!(
+            self.parent_id,
+            storage.id(),
+            "Id mismatch: a ChangeCursor may only be used with it's parent Storage, never any other Storage"
+        );
+
+        let mut chunk_keys: HashSet<ChunkKey::Owned, HasherImpl> =
+            HashSet::with_hasher(HasherImpl::default());
+
+        self.chunk_list.reduce(
+            storage.internal_rvec(),
+            1,
+            |chunk_storages, prev_chunk_key, _| {
+                if let Some(chunk_key) = prev_chunk_key.as_ref() {
+                    chunk_keys.insert(chunk_key.clone());
+                }
+
+                let chunk_key = chunk_storages.first()?.chunk_key();
+                chunk_keys.insert(chunk_key.to_owned());
+
+                if Some(chunk_key) != prev_chunk_key.as_ref().map(Borrow::borrow) {
+                    Some(Some(chunk_key.to_owned()))
+                } else {
+                    None
+                }
+            },
+        );
+
+        let mut result = Vec::new();
+
+        for chunk_key in chunk_keys {
+            let mut before: HashSet<ItemKey::Owned, HasherImpl> =
+                HashSet::with_hasher(HasherImpl::default());
+            let mut after: HashSet<ItemKey::Owned, HasherImpl> =
+                HashSet::with_hasher(HasherImpl::default());
+
+            match storage.internal_idx_of(chunk_key.borrow()) {
+                Some(idx) => {
+                    let data = storage.internal_rvec()[idx].internal_rvec();
+                    let chunk_cursor =
+                        self.chunks
+                            .entry(chunk_key.clone())
+                            .or_insert_with(|| ChunkCursor {
+                                parent_id: data.id(),
+                                item_list: RVec::default(),
+                            });
+
+                    // This chunk was removed and then created again.
+                    if chunk_cursor.parent_id != data.id() {
+                        before.extend(chunk_cursor.item_list.iter().flatten().cloned());
+                        chunk_cursor.parent_id = data.id();
+                        chunk_cursor.item_list = RVec::default();
+                    }
+
+                    chunk_cursor
+                        .item_list
+                        .reduce(data, 1, |elements, prev_item_key, _| {
+                            if let Some(item_key) = prev_item_key.as_ref() {
+                                before.insert(item_key.clone());
+                            }
+
+                            let item_key = elements.first()?.item_key();
+                            after.insert(item_key.clone().into_owned());
+
+                            if Some(item_key.borrow()) != prev_item_key.as_ref().map(Borrow::borrow)
+                            {
+                                Some(Some(item_key.into_owned()))
+                            } else {
+                                None
+                            }
+                        });
+                }
+                None => {
+                    if let Some(chunk_cursor) = self.chunks.remove(chunk_key.borrow()) {
+                        before.extend(chunk_cursor.item_list.iter().flatten().cloned());
+                    }
+                }
+            }
+
+            for item_key in before.iter() {
+                if !after.contains::<ItemKey::Owned>(item_key) {
+                    result.push(Change::Removed(Id::new(
+                        chunk_key.clone(),
+                        item_key.clone(),
+                    )));
+                }
+            }
+
+            for item_key in after {
+                let id = Id::new(chunk_key.clone(), item_key);
+
+                if before.contains::<ItemKey::Owned>(&id.1) {
+                    result.push(Change::Modified(id));
+                } else {
+                    result.push(Change::Added(id));
+                }
+            }
+        }
+
+        result.sort_by(|a, b| a.id().cmp(b.id()));
+        result
+    }
+}
@@ -1,5 +1,6 @@
 use super::entry::Entry;
 use super::id::Id;
+use crate::bits::Bitset;
 use crate::internal::hasher::HasherImpl;
 use crate::internal::mr::rvec::RVec;
 use crate::traits::idxset::IdxSet;
@@ -14,7 +15,6 @@ use std::hash::Hash;
 
 /// A chunk of storage containing all elements with a common chunk key.
 /// End users will rarely if ever interact with this type.
-#[derive(Clone)]
 pub struct ChunkStorage<ChunkKey, ItemKey, Element>
 where
     ChunkKey: BorrowedKey + ?Sized,
@@ -25,6 +25,9 @@ where
     chunk_key: ChunkKey::Owned,
     data: RVec<Element>,
     index: HashMap<ItemKey::Owned, usize, HasherImpl>,
+    // Item keys of elements that have been added, removed or mutably borrowed since the
+    // last time they were written to the journal. `None` unless the `Storage` is journaled.
+    touched: Option<Vec<ItemKey::Owned>>,
 }
 
 impl<ChunkKey, ItemKey, Element> ChunkStorage<ChunkKey, ItemKey, Element>
@@ -40,6 +43,35 @@ where
             chunk_key,
             data: RVec::default(),
             index: HashMap::with_hasher(crate::internal::hasher::HasherImpl::default()),
+            touched: None,
+        }
+    }
+
+    /// Begin or end tracking which elements of this `ChunkStorage` have been touched.
+    pub(crate) fn set_journaled(&mut self, journaled: bool) {
+        match (journaled, self.touched.is_some()) {
+            (true, false) => self.touched = Some(Vec::new()),
+            (false, true) => self.touched = None,
+            _ => {}
+        }
+    }
+
+    /// Take the item keys of all elements touched since the last call, without duplicates.
+    #[cfg(feature = "serde")]
+    pub(crate) fn take_touched(&mut self) -> Vec<ItemKey::Owned> {
+        let mut touched = self
+            .touched
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default();
+        touched.sort_unstable();
+        touched.dedup();
+        touched
+    }
+
+    fn touch(&mut self, idx: usize) {
+        if let Some(touched) = self.touched.as_mut() {
+            touched.push(self.data[idx].item_key().into_owned());
         }
     }
 
@@ -70,6 +102,7 @@ where
         assert!(old_key.is_none(), "duplicate item key within chunk");
         let idx = self.data.len();
         self.data.push(element);
+        self.touch(idx);
         idx
     }
 
@@ -94,7 +127,7 @@ where
         R: Record<ChunkKey, ItemKey>,
     {
         assert_eq!(self.chunk_key.borrow(), unique_id.chunk_key().borrow());
-        Some(&self.get_idx(*self.index.get(unique_id.item_key().borrow())?))
+        Some(self.get_idx(*self.index.get(unique_id.item_key().borrow())?))
     }
 
     pub(crate) fn entry<'a, R>(
@@ -110,6 +143,7 @@ where
     }
 
     pub(crate) fn get_idx_mut(&mut self, idx: usize) -> &mut Element {
+        self.touch(idx);
         &mut self.data[idx]
     }
 
@@ -122,22 +156,50 @@ where
         Q: Query<ChunkKey, ItemKey, Element> + Clone + 'a,
     {
         query
-            .item_idxs(self.chunk_key.borrow(), &self)
+            .item_idxs(self.chunk_key.borrow(), self)
             .into_idx_iter()
             .flatten()
             .map(move |idx| self.get_idx(idx))
             .filter(move |element| query.test(element))
     }
 
-    pub(crate) fn modify<'a, Q, F>(&'a mut self, query: &Q, f: F)
+    /// The indices of every element that matches a query, or nothing if the query doesn't
+    /// cover this chunk at all. Used to evaluate one branch of a compound query.
+    pub(crate) fn matching_idxs<Q>(&self, query: &Q) -> Bitset
+    where
+        Q: Query<ChunkKey, ItemKey, Element>,
+    {
+        if !query.test_chunk(self.chunk_key.borrow()) {
+            return Bitset::default();
+        }
+
+        query
+            .item_idxs(self.chunk_key.borrow(), self)
+            .into_idx_iter()
+            .flatten()
+            .filter(|idx| query.test(self.get_idx(*idx)))
+            .collect()
+    }
+
+    /// Modify every element that matches a query. Elements whose keys were changed are left
+    /// where they are, so this returns the index and original item key of each one, along with
+    /// a backup taken before it was modified. The caller must relocate them.
+    pub(crate) fn modify<Q, F, B, T>(
+        &mut self,
+        query: &Q,
+        f: F,
+        backup: B,
+    ) -> Vec<(usize, ItemKey::Owned, T)>
     where
         Q: Query<ChunkKey, ItemKey, Element>,
         F: Fn(Editor<ChunkKey, ItemKey, Element>),
+        B: Fn(&Element) -> T,
     {
         let chunk_key: ChunkKey::Owned = self.chunk_key.clone();
+        let mut moved = Vec::new();
 
         for idx in query
-            .item_idxs(self.chunk_key.borrow(), &self)
+            .item_idxs(self.chunk_key.borrow(), self)
             .into_idx_iter()
             .flatten()
         {
@@ -147,24 +209,63 @@ where
                 continue;
             }
 
+            let backup = backup(&self.data[idx]);
+
             f(Editor::new(
                 Id::new(chunk_key.borrow(), item_key.borrow()),
                 idx,
                 self,
             ));
 
-            assert_eq!(chunk_key.borrow(), self.data[idx].chunk_key().borrow());
-            assert_eq!(item_key.borrow(), self.data[idx].item_key().borrow());
+            if chunk_key.borrow() != self.data[idx].chunk_key().borrow()
+                || item_key.borrow() != self.data[idx].item_key().borrow()
+            {
+                moved.push((idx, item_key, backup));
+            }
         }
+
+        moved
     }
 
-    pub(crate) fn remove<'a, Q, F>(&'a mut self, query: &Q, f: &F)
+    /// Touch every element that matches a query, and then borrow all of them mutably at once.
+    pub(crate) fn query_mut<Q>(
+        &mut self,
+        query: &Q,
+    ) -> (&ChunkKey, impl Iterator<Item = &mut Element>)
+    where
+        Q: Query<ChunkKey, ItemKey, Element>,
+    {
+        let idxs: Vec<usize> = query
+            .item_idxs(self.chunk_key.borrow(), self)
+            .into_idx_iter()
+            .flatten()
+            .filter(|idx| query.test(&self.data[*idx]))
+            .collect();
+        let mut selected = vec![false; self.data.len()];
+
+        for idx in idxs.iter() {
+            selected[*idx] = true;
+            self.touch(*idx);
+        }
+
+        let elements = self
+            .data
+            .touch_many(&idxs)
+            .iter_mut()
+            .zip(selected)
+            .filter(|(_, selected)| *selected)
+            .map(|(element, _)| element);
+
+        (self.chunk_key.borrow(), elements)
+    }
+
+    pub(crate) fn remove<Q, F>(&mut self, query: &Q, f: &F)
     where
         F: Fn(Element),
         Q: Query<ChunkKey, ItemKey, Element>,
     {
         let mut last_removed_idx = self.data.len();
-        let idxs = query.item_idxs(self.chunk_key.borrow(), &self);
+        let idxs = query.item_idxs(self.chunk_key.borrow(), self);
 
         for idx in idxs.into_idx_iter().flatten().rev() {
             if query.test(&self.data[idx]) {
@@ -175,8 +276,24 @@ where
         }
     }
 
+    /// Remove an element whose item key was changed in place, given the item key it is
+    /// indexed by. Any other changed elements must have lower indices.
+    pub(crate) fn remove_moved(&mut self, idx: usize, item_key: &ItemKey) -> Element {
+        self.touch(idx);
+        let result = self.data.swap_remove(idx);
+        self.index.remove(item_key);
+
+        if idx < self.data.len() {
+            self.index
+                .insert(self.data[idx].item_key().into_owned(), idx);
+        }
+
+        result
+    }
+
     /// Remove the specified element and return it
     pub(crate) fn remove_idx(&mut self, idx: usize) -> Element {
+        self.touch(idx);
         let result = self.data.swap_remove(idx);
         self.index.remove(result.item_key().borrow());
 
@@ -224,15 +341,33 @@ where
     }
 }
 
-impl<ChunkKey, ItemKey, Element> Into<Vec<Element>> for ChunkStorage<ChunkKey, ItemKey, Element>
+impl<ChunkKey, ItemKey, Element> Clone for ChunkStorage<ChunkKey, ItemKey, Element>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+    ItemKey: BorrowedKey + ?Sized,
+    ItemKey::Owned: ValidKey,
+    Element: Clone,
+{
+    fn clone(&self) -> Self {
+        ChunkStorage {
+            chunk_key: self.chunk_key.clone(),
+            data: self.data.clone(),
+            index: self.index.clone(),
+            touched: None,
+        }
+    }
+}
+
+impl<ChunkKey, ItemKey, Element> From<ChunkStorage<ChunkKey, ItemKey, Element>> for Vec<Element>
 where
     ChunkKey: BorrowedKey + ?Sized,
     ChunkKey::Owned: ValidKey,
     ItemKey: BorrowedKey + ?Sized,
     ItemKey::Owned: ValidKey,
 {
-    fn into(self) -> Vec<Element> {
-        self.data.into()
+    fn from(val: ChunkStorage<ChunkKey, ItemKey, Element>) -> Self {
+        val.data.into()
     }
 }
 
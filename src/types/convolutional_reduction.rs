@@ -0,0 +1,328 @@
+use crate::internal::hasher::HasherImpl;
+use crate::internal::mr::reduce::*;
+use crate::internal::mr::rvec::RVec;
+use crate::traits::memory_usage::MemoryUsage;
+use crate::traits::memory_usage::MemoryUser;
+use crate::traits::record::Record;
+use crate::traits::valid_key::{BorrowedKey, ValidKey};
+use crate::types::storage::Storage;
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+type SourcesRule<OutputKey, ChunkKey> =
+    Arc<dyn Fn(&OutputKey) -> Vec<<ChunkKey as ToOwned>::Owned> + Send + Sync>;
+type FoldRule<Summary> = Arc<dyn Fn(&[Summary], &Summary) -> Option<Summary> + Send + Sync>;
+
+/// Summarize several chunks at once, such as "every reading in this hour and the two hours
+/// before it," or "every monster in this grid square and the eight grid squares around it."
+///
+/// Each output key of a `ConvolutionalReduction` maps onto a set of zero or more source chunk
+/// keys. The `Summary` of an output key is only re-computed when one of its source chunks has
+/// changed, and each source chunk is summarized just once no matter how many output keys
+/// it contributes to.
+///
+/// # Type Parameters
+///
+/// * `ChunkKey`: matches the `ChunkKey` of the `Storage`.
+/// * `Element`: matches the `Element` of the `Storage`.
+/// * `OutputKey`: the key that identifies each `Summary`.
+/// * `Summary`: this is the type of the result of summarizing all of the `Elements` in
+///   the source chunks of an output key.
+///
+/// # Example
+///
+/// ```
+/// use retriever::prelude::*;
+///
+/// // Each reading has an hour (which is also its chunk key), an id, and a value.
+/// type Reading = (u64, u64, u64);
+///
+/// let mut storage : Storage<u64, u64, Reading> = Storage::new();
+/// let mut last_three_hours : ConvolutionalReduction<u64, Reading, u64, u64> =
+///   ConvolutionalReduction::new(
+///     &storage,
+///     16,
+///     |hour: &u64| (hour.saturating_sub(2)..=*hour).collect(),
+///     |reading: &Reading, was: &u64| if reading.2 != *was { Some(reading.2) } else { None },
+///     |values: &[u64], was: &u64| {
+///       let total = values.iter().sum();
+///       if total != *was { Some(total) } else { None }
+///     },
+///   );
+///
+/// storage.add((1, 0, 10));
+/// storage.add((2, 0, 20));
+/// storage.add((3, 0, 30));
+/// storage.add((4, 0, 40));
+///
+/// assert_eq!(Some(&30), last_three_hours.reduce(&storage, &2));
+/// assert_eq!(Some(&90), last_three_hours.reduce(&storage, &4));
+/// assert_eq!(None, last_three_hours.reduce(&storage, &9));
+///
+/// storage.add((2, 1, 5));
+///
+/// assert_eq!(Some(&35), last_three_hours.reduce(&storage, &2));
+/// assert_eq!(Some(&95), last_three_hours.reduce(&storage, &4));
+/// # storage.validate();
+/// ```
+///
+/// # Panic
+///
+/// A `ConvolutionalReduction` is associated with exactly one storage.
+/// If you attempt to use a `ConvolutionalReduction` with a `Storage` other than the one it was
+/// initialized with, it will panic.
+pub struct ConvolutionalReduction<ChunkKey, Element, OutputKey, Summary>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+    OutputKey: BorrowedKey + ?Sized,
+    OutputKey::Owned: ValidKey,
+{
+    parent_id: u64,
+    group_size: usize,
+    gc_chunk_list: RVec<Option<ChunkKey::Owned>>,
+    // tracks which chunks have changed since the last update
+    changed_chunks: RVec<()>,
+    sources: SourcesRule<OutputKey, ChunkKey>,
+    fold: FoldRule<Summary>,
+    rules: ReduceRules<Element, Summary>,
+    // incremented every time any chunk changes
+    generation: u64,
+    chunks: HashMap<ChunkKey::Owned, ChunkSummary<Element, Summary>, HasherImpl>,
+    outputs: HashMap<OutputKey::Owned, Output<ChunkKey, Summary>, HasherImpl>,
+}
+
+/// The summary of a single source chunk.
+struct ChunkSummary<Element, Summary> {
+    // the generation in which this chunk last changed
+    generation: u64,
+    reduction: Reduce<Element, Summary>,
+}
+
+/// The summary of a single output key.
+struct Output<ChunkKey, Summary>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+{
+    sources: Vec<ChunkKey::Owned>,
+    // the generation of each source chunk as of the last time this output was summarized,
+    // or None if that chunk didn't exist
+    generations: Vec<Option<u64>>,
+    summary: Option<Summary>,
+}
+
+impl<ChunkKey, Element, OutputKey, Summary>
+    ConvolutionalReduction<ChunkKey, Element, OutputKey, Summary>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+    OutputKey: BorrowedKey + ?Sized,
+    OutputKey::Owned: ValidKey,
+    Summary: Default + Clone,
+{
+    /// Create a new `ConvolutionalReduction` on a `Storage`.
+    ///
+    /// The `Map` and `Fold` rules are exactly as for `Reduction::new()`. The additional
+    /// `Sources` rule lists the source chunk keys of each output key. It's fine to list chunks
+    /// that don't exist. The `Sources` rule should always give the same answer for the same
+    /// output key, because it is only consulted once for each output key.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `ItemKey`: this is the `ItemKey` matching the `Storage`.
+    /// * `Sources`: this operation lists the source chunk keys of a single output key.
+    /// * `Map`: this operation produces a `Summary` of a single `Element`. If the result `Summary`
+    ///   has not changed since the last `Summary`, return `None`.
+    /// * `Fold`: this operations folds several `Summaries` into one `Summary`. If the result
+    ///   `Summary` has not changed since the last `Summary`, return `None`.
+    pub fn new<ItemKey, Sources, Map, Fold>(
+        storage: &Storage<ChunkKey, ItemKey, Element>,
+        group_size: usize,
+        sources: Sources,
+        map: Map,
+        fold: Fold,
+    ) -> Self
+    where
+        ItemKey: BorrowedKey + ?Sized,
+        ItemKey::Owned: ValidKey,
+        Element: Record<ChunkKey, ItemKey>,
+        Sources: Fn(&OutputKey) -> Vec<ChunkKey::Owned> + Send + Sync + 'static,
+        Map: Fn(&Element, &Summary) -> Option<Summary> + Send + Sync + 'static,
+        Fold: Fn(&[Summary], &Summary) -> Option<Summary> + Clone + Send + Sync + 'static,
+    {
+        ConvolutionalReduction {
+            parent_id: storage.id(),
+            group_size,
+            gc_chunk_list: RVec::default(),
+            changed_chunks: RVec::default(),
+            sources: Arc::new(sources),
+            fold: Arc::new(fold.clone()),
+            rules: ReduceRules::new(move |e, s, _| map(e, s), fold),
+            generation: 0,
+            chunks: HashMap::with_hasher(HasherImpl::default()),
+            outputs: HashMap::with_hasher(HasherImpl::default()),
+        }
+    }
+
+    /// Reduce all of the elements of the source chunks of the given output key down to
+    /// a single value, or `None` if none of those chunks contain any elements.
+    pub fn reduce<ItemKey>(
+        &mut self,
+        storage: &Storage<ChunkKey, ItemKey, Element>,
+        output_key: &OutputKey,
+    ) -> Option<&Summary>
+    where
+        Element: Record<ChunkKey, ItemKey>,
+        ItemKey: BorrowedKey + ?Sized,
+        ItemKey::Owned: ValidKey,
+    {
+        self.update(storage);
+
+        let sources = &self.sources;
+        let fold = &self.fold;
+        let chunks = &mut self.chunks;
+        let output = self
+            .outputs
+            .entry(output_key.to_owned())
+            .or_insert_with(|| {
+                let sources = (sources)(output_key);
+                let generations = vec![None; sources.len()];
+
+                Output {
+                    sources,
+                    generations,
+                    summary: None,
+                }
+            });
+
+        let generations: Vec<Option<u64>> = output
+            .sources
+            .iter()
+            .map(|chunk_key| {
+                chunks
+                    .get(chunk_key.borrow())
+                    .map(|chunk_summary| chunk_summary.generation)
+            })
+            .collect();
+
+        // If none of the source chunks have changed, neither has the summary. This includes the
+        // first evaluation of an output key whose source chunks don't exist.
+        if generations != output.generations {
+            let mut summaries = Vec::with_capacity(output.sources.len());
+
+            for chunk_key in output.sources.iter() {
+                let idx = match storage.internal_idx_of(chunk_key.borrow()) {
+                    Some(idx) => idx,
+                    None => continue,
+                };
+
+                let chunk_summary = match chunks.get_mut(chunk_key.borrow()) {
+                    Some(chunk_summary) => chunk_summary,
+                    None => continue,
+                };
+
+                if let Some(summary) = chunk_summary
+                    .reduction
+                    .update(storage.internal_rvec()[idx].internal_rvec())
+                {
+                    summaries.push(summary.clone());
+                }
+            }
+
+            output.generations = generations;
+            output.summary = if summaries.is_empty() {
+                None
+            } else {
+                let old_summary = output.summary.take().unwrap_or_default();
+                Some((fold)(&summaries, &old_summary).unwrap_or(old_summary))
+            };
+        }
+
+        output.summary.as_ref()
+    }
+
+    /// Note which chunks have changed since the last update.
+    fn update<ItemKey>(&mut self, storage: &Storage<ChunkKey, ItemKey, Element>)
+    where
+        ItemKey: BorrowedKey + ?Sized,
+        ItemKey::Owned: ValidKey,
+        Element: Record<ChunkKey, ItemKey>,
+    {
+        assert_eq!(
+      self.parent_id,
+      storage.id(),
+      "Id mismatch: a ConvolutionalReduction may only be used with it's parent Storage, never any other Storage"
+    );
+
+        storage.gc(&mut self.gc_chunk_list, &mut self.chunks);
+
+        let chunks = &mut self.chunks;
+        let generation = &mut self.generation;
+        let group_size = self.group_size;
+        let rules = &self.rules;
+
+        self.changed_chunks
+            .reduce(storage.internal_rvec(), 1, |chunk_storages, _, _| {
+                let chunk_storage = chunk_storages.first()?;
+
+                *generation += 1;
+
+                chunks
+                    .entry(chunk_storage.chunk_key().to_owned())
+                    .or_insert_with(|| ChunkSummary {
+                        generation: 0,
+                        reduction: Reduce::new(
+                            chunk_storage.internal_rvec(),
+                            group_size,
+                            rules.clone(),
+                        ),
+                    })
+                    .generation = *generation;
+
+                None
+            });
+    }
+}
+
+impl<ChunkKey, Element, OutputKey, Summary> MemoryUser
+    for ConvolutionalReduction<ChunkKey, Element, OutputKey, Summary>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+    OutputKey: BorrowedKey + ?Sized,
+    OutputKey::Owned: ValidKey,
+{
+    fn memory_usage(&self) -> MemoryUsage {
+        let mut result = MemoryUsage::merge(
+            self.gc_chunk_list.memory_usage(),
+            self.changed_chunks.memory_usage(),
+        );
+
+        for chunk_summary in self.chunks.values() {
+            result = MemoryUsage::merge(result, chunk_summary.reduction.memory_usage());
+        }
+
+        for output in self.outputs.values() {
+            result = MemoryUsage::merge(result, output.sources.memory_usage());
+            result = MemoryUsage::merge(result, output.generations.memory_usage());
+        }
+
+        result
+    }
+
+    fn shrink_with<F: Fn(&MemoryUsage) -> Option<usize>>(&mut self, f: F) {
+        self.gc_chunk_list.shrink_with(&f);
+        self.changed_chunks.shrink_with(&f);
+
+        for chunk_summary in self.chunks.values_mut() {
+            chunk_summary.reduction.shrink_with(&f);
+        }
+
+        for output in self.outputs.values_mut() {
+            output.sources.shrink_with(&f);
+            output.generations.shrink_with(&f);
+        }
+    }
+}
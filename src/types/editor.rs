@@ -2,9 +2,12 @@ use super::chunk_storage::ChunkStorage;
 use super::id::Id;
 use crate::traits::record::Record;
 use crate::traits::valid_key::{BorrowedKey, ValidKey};
+use std::borrow::Borrow;
 
 /// An Editor for an element. An instance of `Editor` is proof that the backing element
 /// exists in `Storage`, and allows unlimited mutation (but not removal) of that element.
+///
+/// Changing the chunk key or item key of the element moves it. See `Storage::modify()`.
 pub struct Editor<'a, ChunkKey: ?Sized, ItemKey: ?Sized, Element>
 where
     ChunkKey: BorrowedKey,
@@ -69,3 +72,86 @@ where
         self.storage.get_idx_mut(self.idx)
     }
 }
+
+/// A mutable reference to an element, as produced by `Storage::query_mut()`.
+///
+/// Unlike an `Editor`, any number of `ElementMuts` may be alive at once, so they can be
+/// collected, zipped, or held across loop iterations. The price is that every element produced
+/// by `Storage::query_mut()` is treated as modified, whether or not it actually changes.
+///
+/// # Panic
+///
+/// Unlike an `Editor`, an `ElementMut` can't move its element. It panics when it is dropped if
+/// the chunk key or item key of its element has changed.
+pub struct ElementMut<'a, ChunkKey: ?Sized, ItemKey: ?Sized, Element>
+where
+    ChunkKey: BorrowedKey,
+    ChunkKey::Owned: ValidKey,
+    ItemKey: BorrowedKey,
+    ItemKey::Owned: ValidKey,
+    Element: Record<ChunkKey, ItemKey>,
+{
+    chunk_key: &'a ChunkKey,
+    item_key: ItemKey::Owned,
+    element: &'a mut Element,
+}
+
+impl<'a, ChunkKey, ItemKey, Element> ElementMut<'a, ChunkKey, ItemKey, Element>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+    ItemKey: BorrowedKey + ?Sized,
+    ItemKey::Owned: ValidKey,
+    Element: Record<ChunkKey, ItemKey>,
+{
+    pub(super) fn new(chunk_key: &'a ChunkKey, element: &'a mut Element) -> Self {
+        ElementMut {
+            chunk_key,
+            item_key: element.item_key().into_owned(),
+            element,
+        }
+    }
+
+    /// Returns this element's unique `Id`.
+    pub fn id(&self) -> Id<&ChunkKey, &ItemKey> {
+        Id::new(self.chunk_key, self.item_key.borrow())
+    }
+
+    /// Modify this element.
+    pub fn modify<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(&mut Element),
+    {
+        f(self.get_mut());
+        self
+    }
+
+    /// Get a reference to this element.
+    pub fn get(&self) -> &Element {
+        self.element
+    }
+
+    /// Get a mutable reference to this element.
+    pub fn get_mut(&mut self) -> &mut Element {
+        self.element
+    }
+}
+
+impl<'a, ChunkKey, ItemKey, Element> Drop for ElementMut<'a, ChunkKey, ItemKey, Element>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+    ItemKey: BorrowedKey + ?Sized,
+    ItemKey::Owned: ValidKey,
+    Element: Record<ChunkKey, ItemKey>,
+{
+    fn drop(&mut self) {
+        // Don't turn an unrelated panic into an abort.
+        if std::thread::panicking() {
+            return;
+        }
+
+        assert_eq!(self.chunk_key, self.element.chunk_key().borrow());
+        assert_eq!(self.item_key.borrow(), self.element.item_key().borrow());
+    }
+}
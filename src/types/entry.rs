@@ -1,4 +1,6 @@
 use super::chunk_storage::ChunkStorage;
+use super::error::Error;
+use super::id::Id;
 use crate::traits::record::Record;
 use crate::traits::valid_key::{BorrowedKey, ValidKey};
 use std::borrow::Cow;
@@ -51,31 +53,68 @@ where
     }
 
     /// Insert a record at this entry if it does not already exist.
-    pub fn or_insert_with<F>(mut self, f: F) -> &'a mut Element
+    ///
+    /// # Panic
+    ///
+    /// This method panics if the inserted record does not have the same id as this `Entry`.
+    /// See `Entry::try_or_insert_with()` for a version that doesn't panic.
+    pub fn or_insert_with<F>(self, f: F) -> &'a mut Element
+    where
+        F: FnOnce() -> Element,
+    {
+        self.try_or_insert_with(f)
+            .unwrap_or_else(|e| panic!("entry: {}", e))
+    }
+
+    /// Insert a record at this entry if it does not already exist. If the inserted record
+    /// does not have the same id as this `Entry`, it is discarded and an `Error::WrongId` is
+    /// returned.
+    ///
+    /// ```
+    /// use retriever::prelude::*;
+    /// use retriever::Error;
+    ///
+    /// let mut storage : Storage<u64, u64, (u64, u64, &'static str)> = Storage::new();
+    ///
+    /// assert_eq!(
+    ///   Err(Error::WrongId { expected: ID.chunk(1).item(1), found: ID.chunk(1).item(2) }),
+    ///   storage.entry(ID.chunk(1).item(1)).try_or_insert_with(|| (1, 2, "wrong")),
+    /// );
+    /// assert_eq!(
+    ///   Ok(&mut (1, 1, "right")),
+    ///   storage.entry(ID.chunk(1).item(1)).try_or_insert_with(|| (1, 1, "right")),
+    /// );
+    ///
+    /// # storage.validate();
+    /// ```
+    pub fn try_or_insert_with<F>(
+        mut self,
+        f: F,
+    ) -> Result<&'a mut Element, Error<ChunkKey::Owned, ItemKey::Owned>>
     where
         F: FnOnce() -> Element,
     {
         if let Some(idx) = self.idx {
-            self.storage.get_idx_mut(idx)
-        } else {
-            let new_value: Element = f();
-            let new_chunk_key: Cow<ChunkKey> = new_value.chunk_key();
-            let old_chunk_key: Cow<ChunkKey> = Record::<ChunkKey, ItemKey>::chunk_key(&self.id);
-            let new_item_key: Cow<ItemKey> = new_value.item_key();
-            let old_item_key: Cow<ItemKey> = Record::<ChunkKey, ItemKey>::item_key(&self.id);
-            assert_eq!(
-                new_chunk_key, old_chunk_key,
-                "entry: inserted chunk key does not match entry chunk key"
-            );
-            assert_eq!(
-                new_item_key, old_item_key,
-                "entry: inserted item key does not match entry item key"
-            );
-            let idx = self.storage.add(new_value);
-            self.idx = Some(idx);
-
-            self.storage.get_idx_mut(idx)
+            return Ok(self.storage.get_idx_mut(idx));
         }
+
+        let new_value: Element = f();
+        let new_chunk_key: Cow<ChunkKey> = new_value.chunk_key();
+        let old_chunk_key: Cow<ChunkKey> = Record::<ChunkKey, ItemKey>::chunk_key(&self.id);
+        let new_item_key: Cow<ItemKey> = new_value.item_key();
+        let old_item_key: Cow<ItemKey> = Record::<ChunkKey, ItemKey>::item_key(&self.id);
+
+        if new_chunk_key != old_chunk_key || new_item_key != old_item_key {
+            return Err(Error::WrongId {
+                expected: Id::new(old_chunk_key.into_owned(), old_item_key.into_owned()),
+                found: Id::new(new_chunk_key.into_owned(), new_item_key.into_owned()),
+            });
+        }
+
+        let idx = self.storage.add(new_value);
+        self.idx = Some(idx);
+
+        Ok(self.storage.get_idx_mut(idx))
     }
 
     /// Modify this element if it exists. If the element does not exist, nothing happens.
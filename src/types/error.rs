@@ -0,0 +1,111 @@
+use super::id::Id;
+use std::fmt;
+
+/// An error from one of the `try_` methods, which report bad records instead of panicking.
+///
+/// # Type Parameters
+///
+/// * `ChunkKey`: The owned chunk key type of the `Storage`
+/// * `ItemKey`: The owned item key type of the `Storage`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Error<ChunkKey, ItemKey> {
+    /// An element with this id already exists.
+    DuplicateId(Id<ChunkKey, ItemKey>),
+    /// An element did not have the id that was expected of it. For example, an element
+    /// inserted into an `Entry` has a different id than the `Entry`.
+    WrongId {
+        /// The id that the element should have had.
+        expected: Id<ChunkKey, ItemKey>,
+        /// The id that the element actually had.
+        found: Id<ChunkKey, ItemKey>,
+    },
+    /// A modification tried to move an element onto the id of another element.
+    IdCollision {
+        /// The id of the element before the modification.
+        before: Id<ChunkKey, ItemKey>,
+        /// The id that the modification tried to give the element.
+        after: Id<ChunkKey, ItemKey>,
+    },
+    /// An element would have shared a key of a `UniqueIndex` with another element.
+    KeyConflict {
+        /// The id of the element that already has the key.
+        existing: Id<ChunkKey, ItemKey>,
+        /// The id of the element that would also have had the key.
+        found: Id<ChunkKey, ItemKey>,
+    },
+}
+
+impl<ChunkKey, ItemKey> fmt::Display for Error<ChunkKey, ItemKey>
+where
+    ChunkKey: fmt::Debug,
+    ItemKey: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::DuplicateId(id) => write!(f, "retriever: duplicate id {:?}", id),
+            Error::WrongId { expected, found } => write!(
+                f,
+                "retriever: expected an element with id {:?}, but found {:?}",
+                expected, found
+            ),
+            Error::IdCollision { before, after } => write!(
+                f,
+                "retriever: modification moved {:?} onto existing id {:?}",
+                before, after
+            ),
+            Error::KeyConflict { existing, found } => write!(
+                f,
+                "retriever: {:?} has the same unique index key as existing element {:?}",
+                found, existing
+            ),
+        }
+    }
+}
+
+impl<ChunkKey, ItemKey> std::error::Error for Error<ChunkKey, ItemKey>
+where
+    ChunkKey: fmt::Debug,
+    ItemKey: fmt::Debug,
+{
+}
+
+/// An error from `Storage::load_chunk()`. Either the `ChunkStore` itself failed, or it returned
+/// elements that can't all belong to the requested chunk.
+///
+/// # Type Parameters
+///
+/// * `ChunkKey`: The owned chunk key type of the `Storage`
+/// * `ItemKey`: The owned item key type of the `Storage`
+/// * `StoreError`: The error type of the `ChunkStore`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum LoadChunkError<ChunkKey, ItemKey, StoreError> {
+    /// The `ChunkStore` reported an error.
+    Store(StoreError),
+    /// A loaded element was rejected. `Error::WrongId` means that it had a different chunk key
+    /// than the requested chunk, and `Error::DuplicateId` means that two loaded elements had the
+    /// same item key.
+    Element(Error<ChunkKey, ItemKey>),
+}
+
+impl<ChunkKey, ItemKey, StoreError> fmt::Display for LoadChunkError<ChunkKey, ItemKey, StoreError>
+where
+    ChunkKey: fmt::Debug,
+    ItemKey: fmt::Debug,
+    StoreError: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadChunkError::Store(e) => write!(f, "retriever: chunk store error: {}", e),
+            LoadChunkError::Element(e) => e.fmt(f),
+        }
+    }
+}
+
+impl<ChunkKey, ItemKey, StoreError> std::error::Error
+    for LoadChunkError<ChunkKey, ItemKey, StoreError>
+where
+    ChunkKey: fmt::Debug,
+    ItemKey: fmt::Debug,
+    StoreError: fmt::Debug + fmt::Display,
+{
+}
@@ -0,0 +1,308 @@
+use crate::bits::Bitset;
+use crate::internal::hasher::HasherImpl;
+use crate::internal::mr::reduce::*;
+use crate::internal::mr::rvec::RVec;
+use crate::traits::idxset::IdxSet;
+use crate::traits::memory_usage::MemoryUsage;
+use crate::traits::memory_usage::MemoryUser;
+use crate::traits::query::Query;
+use crate::traits::record::Record;
+use crate::traits::valid_key::{BorrowedKey, ValidKey};
+use crate::types::storage::Storage;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+type MapRule<Element, Summary> = Arc<dyn Fn(&Element, &Summary) -> Option<Summary> + Send + Sync>;
+
+/// Summarize only the elements of a `Storage` that match a `Query`, such as
+/// "only adopted puppies." A `FilteredReduction` works just like a `Reduction`, and
+/// repeated evaluations will only re-compute the parts of the reduction that have changed.
+///
+/// The `Query` must always give the same answer for the same elements. In practice this means
+/// that it shouldn't depend on anything other than the `Storage` itself and the `SecondaryIndexes`
+/// or other indexes of that `Storage`.
+///
+/// # Type Parameters
+///
+/// * `ChunkKey`: matches the `ChunkKey` of the `Storage`.
+/// * `Element`: matches the `Element` of the `Storage`.
+/// * `Summary`: this is the type of the result of summarizing all of the matching `Elements`.
+/// * `Q`: the type of the `Query`.
+///
+/// # Example
+///
+/// ```
+/// use retriever::prelude::*;
+/// use std::borrow::Cow;
+///
+/// struct Puppy {
+///   name: &'static str,
+///   adopted: bool,
+///   toys: u64,
+/// }
+///
+/// impl Record<(), str> for Puppy {
+///   fn chunk_key(&self) -> Cow<()> {
+///     Cow::Owned(())
+///   }
+///
+///   fn item_key(&self) -> Cow<str> {
+///     Cow::Borrowed(self.name)
+///   }
+/// }
+///
+/// let mut storage : Storage<(), str, Puppy> = Storage::new();
+/// let mut adopted_toys = FilteredReduction::new(
+///   &storage,
+///   Everything.filter(|puppy: &Puppy| puppy.adopted),
+///   16,
+///   |puppy: &Puppy, was: &u64| if puppy.toys != *was { Some(puppy.toys) } else { None },
+///   |toys: &[u64], was: &u64| {
+///     let total = toys.iter().sum();
+///     if total != *was { Some(total) } else { None }
+///   },
+/// );
+///
+/// storage.add(Puppy { name: "Snoopy", adopted: true, toys: 3 });
+/// storage.add(Puppy { name: "Odie", adopted: false, toys: 2 });
+/// storage.add(Puppy { name: "Shiloh", adopted: false, toys: 4 });
+///
+/// assert_eq!(Some(&3), adopted_toys.reduce(&storage));
+///
+/// storage.modify(ID.item("Shiloh"), |mut puppy| puppy.get_mut().adopted = true);
+///
+/// assert_eq!(Some(&7), adopted_toys.reduce(&storage));
+///
+/// storage.modify(Everything, |mut puppy| puppy.get_mut().adopted = false);
+///
+/// assert_eq!(None, adopted_toys.reduce(&storage));
+/// # storage.validate();
+/// ```
+///
+/// # Panic
+///
+/// A `FilteredReduction` is associated with exactly one storage.
+/// If you attempt to use a `FilteredReduction` with a `Storage` other than the one it was
+/// initialized with, it will panic.
+pub struct FilteredReduction<ChunkKey, Element, Summary, Q>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+{
+    parent_id: u64,
+    group_size: usize,
+    gc_chunk_list: RVec<Option<ChunkKey::Owned>>,
+    query: Q,
+    map: MapRule<Element, Summary>,
+    rules: ReduceRules<Option<Summary>, Option<Summary>>,
+    chunkwise_reductions: HashMap<ChunkKey::Owned, ChunkReduction<Summary>, HasherImpl>,
+    chunkwise_summaries: RVec<Option<Summary>>,
+    reduction: Reduce<Option<Summary>, Option<Summary>>,
+}
+
+/// The reduction of a single chunk.
+struct ChunkReduction<Summary> {
+    // the summary of each element of the chunk, or None if it doesn't match the query
+    matches: RVec<Option<Summary>>,
+    reduction: Reduce<Option<Summary>, Option<Summary>>,
+}
+
+impl<ChunkKey, Element, Summary, Q> FilteredReduction<ChunkKey, Element, Summary, Q>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+    Summary: Default + Clone,
+{
+    /// Create a new `FilteredReduction` on a `Storage`.
+    ///
+    /// The `Map` and `Fold` rules are exactly as for `Reduction::new()`, except that they only
+    /// ever see elements that match the `Query`.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `ItemKey`: this is the `ItemKey` matching the `Storage`.
+    /// * `Map`: this operation produces a `Summary` of a single `Element`. If the result `Summary`
+    ///   has not changed since the last `Summary`, return `None`.
+    /// * `Fold`: this operations folds several `Summaries` into one `Summary`. If the result
+    ///   `Summary` has not changed since the last `Summary`, return `None`.
+    pub fn new<ItemKey, Map, Fold>(
+        storage: &Storage<ChunkKey, ItemKey, Element>,
+        query: Q,
+        group_size: usize,
+        map: Map,
+        fold: Fold,
+    ) -> Self
+    where
+        ItemKey: BorrowedKey + ?Sized,
+        ItemKey::Owned: ValidKey,
+        Element: Record<ChunkKey, ItemKey>,
+        Q: Query<ChunkKey, ItemKey, Element>,
+        Map: Fn(&Element, &Summary) -> Option<Summary> + Send + Sync + 'static,
+        Fold: Fn(&[Summary], &Summary) -> Option<Summary> + Send + Sync + 'static,
+    {
+        let chunkwise_summaries = RVec::default();
+        let rules = Self::rules(fold);
+        let reduction = Reduce::new(&chunkwise_summaries, group_size, rules.clone());
+
+        FilteredReduction {
+            parent_id: storage.id(),
+            group_size,
+            gc_chunk_list: RVec::default(),
+            query,
+            map: Arc::new(map),
+            rules,
+            chunkwise_reductions: HashMap::with_hasher(HasherImpl::default()),
+            chunkwise_summaries,
+            reduction,
+        }
+    }
+
+    /// The `Query` selecting the elements of this `FilteredReduction`.
+    pub fn query(&self) -> &Q {
+        &self.query
+    }
+
+    /// Lift a `Fold` rule to work on summaries that might be missing, because none of the
+    /// elements they summarize match the query.
+    fn rules<Fold>(fold: Fold) -> ReduceRules<Option<Summary>, Option<Summary>>
+    where
+        Fold: Fn(&[Summary], &Summary) -> Option<Summary> + Send + Sync + 'static,
+    {
+        ReduceRules::new(
+            |summary: &Option<Summary>, _, _| Some(summary.clone()),
+            move |summaries: &[Option<Summary>], was: &Option<Summary>| {
+                let summaries: Vec<Summary> = summaries.iter().flatten().cloned().collect();
+
+                if summaries.is_empty() {
+                    return was.as_ref().map(|_| None);
+                }
+
+                match was {
+                    Some(was) => fold(&summaries, was).map(Some),
+                    None => Some(Some(
+                        fold(&summaries, &Summary::default()).unwrap_or_default(),
+                    )),
+                }
+            },
+        )
+    }
+
+    /// Reduce all of the elements of the given `Storage` that match the `Query` down to a
+    /// single value, or `None` if no elements match.
+    pub fn reduce<ItemKey>(
+        &mut self,
+        storage: &Storage<ChunkKey, ItemKey, Element>,
+    ) -> Option<&Summary>
+    where
+        Element: Record<ChunkKey, ItemKey>,
+        ItemKey: BorrowedKey + ?Sized,
+        ItemKey::Owned: ValidKey,
+        Q: Query<ChunkKey, ItemKey, Element>,
+    {
+        assert_eq!(
+      self.parent_id,
+      storage.id(),
+      "Id mismatch: a FilteredReduction may only be used with it's parent Storage, never any other Storage"
+    );
+
+        storage.gc(&mut self.gc_chunk_list, &mut self.chunkwise_reductions);
+
+        let query = &self.query;
+        let map = &self.map;
+        let rules = &self.rules;
+        let group_size = self.group_size;
+        let chunkwise_reductions = &mut self.chunkwise_reductions;
+        let chunk_idxs: Bitset = query
+            .chunk_idxs(storage)
+            .into_idx_iter()
+            .flatten()
+            .collect();
+
+        self.chunkwise_summaries
+            .reduce(storage.internal_rvec(), 1, |chunks, was, idx| {
+                let chunk = chunks.first()?;
+
+                if !chunk_idxs.get(idx) {
+                    return was.as_ref().map(|_| None);
+                }
+
+                let internal_storage = chunk.internal_rvec();
+                let item_idxs: Bitset = query
+                    .item_idxs(chunk.chunk_key(), chunk)
+                    .into_idx_iter()
+                    .flatten()
+                    .collect();
+
+                let chunk_reduction = chunkwise_reductions
+                    .entry(chunk.chunk_key().to_owned())
+                    .or_insert_with(|| {
+                        let matches = RVec::default();
+                        let reduction = Reduce::new(&matches, group_size, rules.clone());
+
+                        ChunkReduction { matches, reduction }
+                    });
+
+                chunk_reduction
+                    .matches
+                    .reduce(internal_storage, 1, |elements, was, idx| {
+                        let element = elements.first()?;
+
+                        if !item_idxs.get(idx) || !query.test(element) {
+                            return was.as_ref().map(|_| None);
+                        }
+
+                        match was {
+                            Some(was) => map(element, was).map(Some),
+                            None => {
+                                Some(Some(map(element, &Summary::default()).unwrap_or_default()))
+                            }
+                        }
+                    });
+
+                Some(
+                    chunk_reduction
+                        .reduction
+                        .update(&chunk_reduction.matches)
+                        .cloned()
+                        .flatten(),
+                )
+            });
+
+        self.reduction
+            .update(&self.chunkwise_summaries)
+            .and_then(Option::as_ref)
+    }
+}
+
+impl<ChunkKey, Element, Summary, Q> MemoryUser for FilteredReduction<ChunkKey, Element, Summary, Q>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+{
+    fn memory_usage(&self) -> MemoryUsage {
+        let mut result = MemoryUsage::merge(
+            self.gc_chunk_list.memory_usage(),
+            self.chunkwise_summaries.memory_usage(),
+        );
+
+        result = MemoryUsage::merge(result, self.reduction.memory_usage());
+
+        for chunk_reduction in self.chunkwise_reductions.values() {
+            result = MemoryUsage::merge(result, chunk_reduction.matches.memory_usage());
+            result = MemoryUsage::merge(result, chunk_reduction.reduction.memory_usage());
+        }
+
+        result
+    }
+
+    fn shrink_with<F: Fn(&MemoryUsage) -> Option<usize>>(&mut self, f: F) {
+        self.gc_chunk_list.shrink_with(&f);
+        self.chunkwise_summaries.shrink_with(&f);
+        self.reduction.shrink_with(&f);
+
+        for chunk_reduction in self.chunkwise_reductions.values_mut() {
+            chunk_reduction.matches.shrink_with(&f);
+            chunk_reduction.reduction.shrink_with(&f);
+        }
+    }
+}
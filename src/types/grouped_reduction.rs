@@ -0,0 +1,458 @@
+use crate::internal::hasher::HasherImpl;
+use crate::internal::mr::reduce::*;
+use crate::internal::mr::rvec::RVec;
+use crate::traits::memory_usage::MemoryUsage;
+use crate::traits::memory_usage::MemoryUser;
+use crate::traits::record::Record;
+use crate::traits::valid_key::{BorrowedKey, ValidKey};
+use crate::types::chunk_storage::ChunkStorage;
+use crate::types::storage::Storage;
+use std::borrow::Borrow;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+type GroupKeyRule<Element, GroupKey> = Arc<dyn Fn(&Element) -> Cow<GroupKey> + Send + Sync>;
+type MapRule<Element, Summary> = Arc<dyn Fn(&Element, &Summary) -> Option<Summary> + Send + Sync>;
+
+/// Summarize groups of elements that share a key, such as "the number of toys belonging to
+/// puppies of each breed," without making that key the chunk key. A `GroupedReduction` works
+/// just like a `Reduction`, except that it maintains one `Summary` for each group.
+///
+/// Repeated evaluations will only re-compute the parts of each group that have changed.
+///
+/// # Type Parameters
+///
+/// * `ChunkKey`: matches the `ChunkKey` of the `Storage`.
+/// * `Element`: matches the `Element` of the `Storage`.
+/// * `GroupKey`: the key that decides which group each `Element` belongs to.
+/// * `Summary`: this is the type of the result of summarizing all of the `Elements` in a group.
+///
+/// # Example
+///
+/// ```
+/// use retriever::prelude::*;
+/// use std::borrow::Cow;
+///
+/// struct Puppy {
+///   name: &'static str,
+///   breed: &'static str,
+///   toys: u64,
+/// }
+///
+/// impl Record<(), str> for Puppy {
+///   fn chunk_key(&self) -> Cow<()> {
+///     Cow::Owned(())
+///   }
+///
+///   fn item_key(&self) -> Cow<str> {
+///     Cow::Borrowed(self.name)
+///   }
+/// }
+///
+/// let mut storage : Storage<(), str, Puppy> = Storage::new();
+/// let mut toys_by_breed : GroupedReduction<(), Puppy, str, u64> = GroupedReduction::new(
+///   &storage,
+///   16,
+///   |puppy: &Puppy| Cow::Borrowed(puppy.breed),
+///   |puppy: &Puppy, was: &u64| if puppy.toys != *was { Some(puppy.toys) } else { None },
+///   |toys: &[u64], was: &u64| {
+///     let total = toys.iter().sum();
+///     if total != *was { Some(total) } else { None }
+///   },
+/// );
+///
+/// storage.add(Puppy { name: "Snoopy", breed: "beagle", toys: 3 });
+/// storage.add(Puppy { name: "Odie", breed: "dachshund", toys: 2 });
+/// storage.add(Puppy { name: "Shiloh", breed: "beagle", toys: 4 });
+///
+/// assert_eq!(Some(&7), toys_by_breed.get(&storage, "beagle"));
+/// assert_eq!(Some(&2), toys_by_breed.get(&storage, "dachshund"));
+/// assert_eq!(None, toys_by_breed.get(&storage, "collie"));
+///
+/// storage.modify(ID.item("Odie"), |mut puppy| puppy.get_mut().breed = "beagle");
+///
+/// let groups : Vec<(&str, u64)> = toys_by_breed.iter(&storage).map(|(k, v)| (k, *v)).collect();
+/// assert_eq!(vec![("beagle", 9)], groups);
+///
+/// # storage.validate();
+/// ```
+pub struct GroupedReduction<ChunkKey, Element, GroupKey, Summary>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+    GroupKey: BorrowedKey + ?Sized,
+    GroupKey::Owned: ValidKey,
+{
+    parent_id: u64,
+    group_size: usize,
+    gc_chunk_list: RVec<Option<ChunkKey::Owned>>,
+    // tracks which chunks have changed since the last update
+    changed_chunks: RVec<()>,
+    group_key: GroupKeyRule<Element, GroupKey>,
+    map: MapRule<Element, Summary>,
+    rules: ReduceRules<Summary, Summary>,
+    // the group of each element of each chunk
+    chunks: HashMap<ChunkKey::Owned, ChunkGroups<GroupKey>, HasherImpl>,
+    groups: HashMap<GroupKey::Owned, Group<ChunkKey, Summary>, HasherImpl>,
+}
+
+/// The group key of each element of a single chunk.
+struct ChunkGroups<GroupKey>
+where
+    GroupKey: BorrowedKey + ?Sized,
+    GroupKey::Owned: ValidKey,
+{
+    // id of the RVec these tokens were computed from, so that we notice if the chunk is replaced
+    source_id: u64,
+    tokens: RVec<Option<GroupKey::Owned>>,
+}
+
+/// The summaries of every element in a single group, in no particular order.
+struct Group<ChunkKey, Summary>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+{
+    members: RVec<Summary>,
+    // the chunk and element index of each member
+    owners: Vec<(ChunkKey::Owned, usize)>,
+    // the position in `members` of each chunk and element index
+    slots: HashMap<ChunkKey::Owned, HashMap<usize, usize, HasherImpl>, HasherImpl>,
+    reduction: Reduce<Summary, Summary>,
+}
+
+impl<ChunkKey, Element, GroupKey, Summary> GroupedReduction<ChunkKey, Element, GroupKey, Summary>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+    GroupKey: BorrowedKey + ?Sized,
+    GroupKey::Owned: ValidKey,
+    Summary: Default + Clone,
+{
+    /// Create a new `GroupedReduction` on a `Storage`.
+    ///
+    /// The `Map` and `Fold` rules are exactly as for `Reduction::new()`. The additional
+    /// `GroupKey` rule decides which group each element belongs to.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `ItemKey`: this is the `ItemKey` matching the `Storage`.
+    /// * `KeyFn`: this operation produces the group key of a single `Element`.
+    /// * `Map`: this operation produces a `Summary` of a single `Element`. If the result `Summary`
+    ///   has not changed since the last `Summary`, return `None`.
+    /// * `Fold`: this operations folds several `Summaries` into one `Summary`. If the result
+    ///   `Summary` has not changed since the last `Summary`, return `None`.
+    pub fn new<ItemKey, KeyFn, Map, Fold>(
+        storage: &Storage<ChunkKey, ItemKey, Element>,
+        group_size: usize,
+        group_key: KeyFn,
+        map: Map,
+        fold: Fold,
+    ) -> Self
+    where
+        ItemKey: BorrowedKey + ?Sized,
+        ItemKey::Owned: ValidKey,
+        Element: Record<ChunkKey, ItemKey>,
+        KeyFn: Fn(&Element) -> Cow<GroupKey> + Send + Sync + 'static,
+        Map: Fn(&Element, &Summary) -> Option<Summary> + Send + Sync + 'static,
+        Fold: Fn(&[Summary], &Summary) -> Option<Summary> + Clone + Send + Sync + 'static,
+    {
+        assert!(group_size > 1);
+
+        let reduce = fold.clone();
+
+        GroupedReduction {
+            parent_id: storage.id(),
+            group_size,
+            gc_chunk_list: RVec::default(),
+            changed_chunks: RVec::default(),
+            group_key: Arc::new(group_key),
+            map: Arc::new(map),
+            rules: ReduceRules::new(move |ss, s, _| reduce(std::slice::from_ref(ss), s), fold),
+            chunks: HashMap::with_hasher(HasherImpl::default()),
+            groups: HashMap::with_hasher(HasherImpl::default()),
+        }
+    }
+
+    /// Get the `Summary` of all of the elements in one group, or `None` if no element belongs to
+    /// that group.
+    pub fn get<ItemKey>(
+        &mut self,
+        storage: &Storage<ChunkKey, ItemKey, Element>,
+        group_key: &GroupKey,
+    ) -> Option<&Summary>
+    where
+        ItemKey: BorrowedKey + ?Sized,
+        ItemKey::Owned: ValidKey,
+        Element: Record<ChunkKey, ItemKey>,
+    {
+        self.update(storage);
+
+        let group = self.groups.get_mut(group_key)?;
+        group.reduction.update(&group.members)
+    }
+
+    /// Iterate over the `Summary` of every group, in no particular order.
+    pub fn iter<ItemKey>(
+        &mut self,
+        storage: &Storage<ChunkKey, ItemKey, Element>,
+    ) -> impl Iterator<Item = (&GroupKey, &Summary)>
+    where
+        ItemKey: BorrowedKey + ?Sized,
+        ItemKey::Owned: ValidKey,
+        Element: Record<ChunkKey, ItemKey>,
+    {
+        self.update(storage);
+
+        for group in self.groups.values_mut() {
+            group.reduction.update(&group.members);
+        }
+
+        self.groups
+            .iter()
+            .filter_map(|(group_key, group)| Some((group_key.borrow(), group.reduction.peek()?)))
+    }
+
+    /// Bring the group of every element up to date.
+    fn update<ItemKey>(&mut self, storage: &Storage<ChunkKey, ItemKey, Element>)
+    where
+        ItemKey: BorrowedKey + ?Sized,
+        ItemKey::Owned: ValidKey,
+        Element: Record<ChunkKey, ItemKey>,
+    {
+        assert_eq!(
+      self.parent_id,
+      storage.id(),
+      "Id mismatch: a GroupedReduction may only be used with it's parent Storage, never any other Storage"
+    );
+
+        for (chunk_key, chunk_groups) in storage.gc(&mut self.gc_chunk_list, &mut self.chunks) {
+            Self::remove_chunk(&mut self.groups, chunk_key.borrow(), &chunk_groups.tokens);
+        }
+
+        let mut changed = Vec::new();
+
+        self.changed_chunks
+            .reduce(storage.internal_rvec(), 1, |chunk_storages, _, idx| {
+                if !chunk_storages.is_empty() {
+                    changed.push(idx);
+                }
+
+                None
+            });
+
+        for idx in changed {
+            self.update_chunk(&storage.internal_rvec()[idx]);
+        }
+    }
+
+    fn update_chunk<ItemKey>(&mut self, chunk_storage: &ChunkStorage<ChunkKey, ItemKey, Element>)
+    where
+        ItemKey: BorrowedKey + ?Sized,
+        ItemKey::Owned: ValidKey,
+        Element: Record<ChunkKey, ItemKey>,
+    {
+        let chunk_key = chunk_storage.chunk_key();
+        let internal_storage = chunk_storage.internal_rvec();
+        let groups = &mut self.groups;
+        let group_key_rule = &self.group_key;
+        let map = &self.map;
+        let rules = &self.rules;
+        let group_size = self.group_size;
+
+        let chunk_groups = self
+            .chunks
+            .entry(chunk_key.to_owned())
+            .or_insert_with(|| ChunkGroups {
+                source_id: internal_storage.id(),
+                tokens: RVec::default(),
+            });
+
+        // The chunk was removed and replaced since we last saw it.
+        if chunk_groups.source_id != internal_storage.id() {
+            Self::remove_chunk(groups, chunk_key, &chunk_groups.tokens);
+            chunk_groups.source_id = internal_storage.id();
+            chunk_groups.tokens = RVec::default();
+        }
+
+        chunk_groups
+            .tokens
+            .reduce(internal_storage, 1, |elements, old_group_key, idx| {
+                let element = match elements.first() {
+                    Some(element) => element,
+                    None => {
+                        if let Some(old_group_key) = old_group_key {
+                            Self::remove_member(groups, old_group_key.borrow(), chunk_key, idx);
+                        }
+
+                        return None;
+                    }
+                };
+
+                let group_key = (group_key_rule)(element);
+
+                if old_group_key.as_ref().map(Borrow::borrow) == Some(group_key.as_ref()) {
+                    groups
+                        .get_mut(group_key.as_ref())
+                        .expect("retriever: element missing from its group")
+                        .update(chunk_key, idx, |old_summary| (map)(element, old_summary));
+
+                    return None;
+                }
+
+                if let Some(old_group_key) = old_group_key {
+                    Self::remove_member(groups, old_group_key.borrow(), chunk_key, idx);
+                }
+
+                let summary = (map)(element, &Summary::default()).unwrap_or_default();
+                groups
+                    .entry(group_key.as_ref().to_owned())
+                    .or_insert_with(|| Group::new(group_size, rules.clone()))
+                    .insert(chunk_key, idx, summary);
+
+                Some(Some(group_key.into_owned()))
+            });
+    }
+
+    fn remove_chunk(
+        groups: &mut HashMap<GroupKey::Owned, Group<ChunkKey, Summary>, HasherImpl>,
+        chunk_key: &ChunkKey,
+        tokens: &RVec<Option<GroupKey::Owned>>,
+    ) {
+        for (idx, group_key) in tokens.iter().enumerate() {
+            if let Some(group_key) = group_key {
+                Self::remove_member(groups, group_key.borrow(), chunk_key, idx);
+            }
+        }
+    }
+
+    fn remove_member(
+        groups: &mut HashMap<GroupKey::Owned, Group<ChunkKey, Summary>, HasherImpl>,
+        group_key: &GroupKey,
+        chunk_key: &ChunkKey,
+        idx: usize,
+    ) {
+        if let Some(group) = groups.get_mut(group_key) {
+            group.remove(chunk_key, idx);
+
+            if group.members.is_empty() {
+                groups.remove(group_key);
+            }
+        }
+    }
+}
+
+impl<ChunkKey, Summary> Group<ChunkKey, Summary>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+    Summary: Default,
+{
+    fn new(group_size: usize, rules: ReduceRules<Summary, Summary>) -> Self {
+        let members = RVec::default();
+        let reduction = Reduce::new(&members, group_size, rules);
+
+        Group {
+            members,
+            owners: Vec::new(),
+            slots: HashMap::with_hasher(HasherImpl::default()),
+            reduction,
+        }
+    }
+
+    fn insert(&mut self, chunk_key: &ChunkKey, idx: usize, summary: Summary) {
+        let slot = self.members.len();
+
+        self.members.push(summary);
+        self.owners.push((chunk_key.to_owned(), idx));
+        self.slots
+            .entry(chunk_key.to_owned())
+            .or_insert_with(|| HashMap::with_hasher(HasherImpl::default()))
+            .insert(idx, slot);
+    }
+
+    fn update<F>(&mut self, chunk_key: &ChunkKey, idx: usize, f: F)
+    where
+        F: FnOnce(&Summary) -> Option<Summary>,
+    {
+        let slot = self.slots[chunk_key][&idx];
+
+        if let Some(summary) = f(&self.members[slot]) {
+            self.members[slot] = summary;
+        }
+    }
+
+    fn remove(&mut self, chunk_key: &ChunkKey, idx: usize) {
+        let chunk_slots = match self.slots.get_mut(chunk_key) {
+            Some(chunk_slots) => chunk_slots,
+            None => return,
+        };
+
+        let slot = match chunk_slots.remove(&idx) {
+            Some(slot) => slot,
+            None => return,
+        };
+
+        if chunk_slots.is_empty() {
+            self.slots.remove(chunk_key);
+        }
+
+        self.members.swap_remove(slot);
+        self.owners.swap_remove(slot);
+
+        // Another member was moved into the vacated slot.
+        if let Some((moved_chunk_key, moved_idx)) = self.owners.get(slot) {
+            if let Some(moved_slot) = self
+                .slots
+                .get_mut(moved_chunk_key.borrow())
+                .and_then(|chunk_slots| chunk_slots.get_mut(moved_idx))
+            {
+                *moved_slot = slot;
+            }
+        }
+    }
+}
+
+impl<ChunkKey, Element, GroupKey, Summary> MemoryUser
+    for GroupedReduction<ChunkKey, Element, GroupKey, Summary>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+    GroupKey: BorrowedKey + ?Sized,
+    GroupKey::Owned: ValidKey,
+{
+    fn memory_usage(&self) -> MemoryUsage {
+        let mut result = MemoryUsage::merge(
+            self.gc_chunk_list.memory_usage(),
+            self.changed_chunks.memory_usage(),
+        );
+
+        for chunk_groups in self.chunks.values() {
+            result = MemoryUsage::merge(result, chunk_groups.tokens.memory_usage());
+        }
+
+        for group in self.groups.values() {
+            result = MemoryUsage::merge(result, group.members.memory_usage());
+            result = MemoryUsage::merge(result, group.owners.memory_usage());
+            result = MemoryUsage::merge(result, group.reduction.memory_usage());
+        }
+
+        result
+    }
+
+    fn shrink_with<F: Fn(&MemoryUsage) -> Option<usize>>(&mut self, f: F) {
+        self.gc_chunk_list.shrink_with(&f);
+        self.changed_chunks.shrink_with(&f);
+
+        for chunk_groups in self.chunks.values_mut() {
+            chunk_groups.tokens.shrink_with(&f);
+        }
+
+        for group in self.groups.values_mut() {
+            group.members.shrink_with(&f);
+            group.owners.shrink_with(&f);
+            group.reduction.shrink_with(&f);
+        }
+    }
+}
@@ -4,6 +4,7 @@ use crate::traits::record::Record;
 use crate::traits::valid_key::BorrowedKey;
 use crate::traits::valid_key::ValidKey;
 use crate::types::chunk_storage::ChunkStorage;
+use crate::types::plan::Plan;
 use crate::types::storage::Storage;
 use std::borrow::Borrow;
 use std::borrow::Cow;
@@ -53,6 +54,7 @@ pub const ID: Id<(), ()> = Id((), ());
 /// ```
 ///
 #[derive(Copy, Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Id<C, I>(pub C, pub I);
 
 impl<C, I> Id<C, I> {
@@ -120,16 +122,16 @@ where
     C: ValidKey + Borrow<ChunkKey>,
     I: ValidKey + Borrow<ItemKey>,
 {
-    fn chunk_key(&self) -> Cow<ChunkKey> {
+    fn chunk_key(&self) -> Cow<'_, ChunkKey> {
         Cow::Borrowed(self.0.borrow())
     }
 
-    fn item_key(&self) -> Cow<ItemKey> {
+    fn item_key(&self) -> Cow<'_, ItemKey> {
         Cow::Borrowed(self.1.borrow())
     }
 }
 
-impl<'a, ChunkKey, ItemKey, Element, C, I> Query<ChunkKey, ItemKey, Element> for Id<C, I>
+impl<ChunkKey, ItemKey, Element, C, I> Query<ChunkKey, ItemKey, Element> for Id<C, I>
 where
     Id<C, I>: Record<ChunkKey, ItemKey>,
     Element: Record<ChunkKey, ItemKey>,
@@ -160,4 +162,12 @@ where
         assert_eq!(self.item_key(), element.item_key());
         true
     }
+
+    fn test_chunk(&self, chunk_key: &ChunkKey) -> bool {
+        self.chunk_key().as_ref() == chunk_key
+    }
+
+    fn explain(&self, storage: &Storage<ChunkKey, ItemKey, Element>) -> Plan {
+        Plan::measure(self, storage, "Id", false, Vec::new())
+    }
 }
@@ -0,0 +1,281 @@
+use crate::internal::hasher::HasherImpl;
+use crate::internal::mr::rvec::RVec;
+use crate::internal::mr::summarize::{Summarize, SummaryRules};
+use crate::traits::memory_usage::MemoryUsage;
+use crate::traits::memory_usage::MemoryUser;
+use crate::traits::record::Record;
+use crate::traits::valid_key::{BorrowedKey, ValidKey};
+use crate::types::chunk_storage::ChunkStorage;
+use crate::types::storage::Storage;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+type InverseRule<Summary> = Arc<dyn Fn(&mut Summary, &Summary) + Send + Sync>;
+
+/// Summarize a `Storage` using a pair of operations that can add and subtract the contribution
+/// of any single element, such as a sum or a count. An `InvertibleReduction` only does a
+/// constant amount of work for each element that has changed, no matter how many elements there
+/// are, while a `Reduction` has to re-compute a few summaries for each element that has changed.
+///
+/// The `add` and `subtract` operations must be exact inverses of each other, and the
+/// order in which elements are added and subtracted must not matter. `Summary::default()` must
+/// be the summary of zero elements, so that adding or subtracting it changes nothing. Integer
+/// sums and counts satisfy all of these rules; floating point sums might slowly accumulate
+/// rounding errors.
+///
+/// # Type Parameters
+///
+/// * `ChunkKey`: matches the `ChunkKey` of the `Storage`.
+/// * `Element`: matches the `Element` of the `Storage`.
+/// * `Summary`: this is the type of the result of summarizing all of the `Elements` in `Storage`.
+///
+/// # Example
+///
+/// ```
+/// use retriever::prelude::*;
+///
+/// // Each order has a customer, an order number and a price.
+/// type Order = (u64, u64, i64);
+///
+/// let mut storage : Storage<u64, u64, Order> = Storage::new();
+/// let mut revenue : InvertibleReduction<u64, Order, i64> = InvertibleReduction::new(
+///   &storage,
+///   |order: &Order| order.2,
+///   |total: &mut i64, price: &i64| *total += price,
+///   |total: &mut i64, price: &i64| *total -= price,
+/// );
+///
+/// storage.add((1, 1, 100));
+/// storage.add((1, 2, 25));
+/// storage.add((2, 3, 40));
+///
+/// assert_eq!(&165, revenue.reduce(&storage));
+/// assert_eq!(Some(&125), revenue.reduce_chunk(&storage, &1));
+///
+/// storage.remove(ID.chunk(1).item(1), std::mem::drop);
+///
+/// assert_eq!(&65, revenue.reduce(&storage));
+/// # storage.validate();
+/// ```
+///
+/// # Panic
+///
+/// An `InvertibleReduction` is associated with exactly one storage.
+/// If you attempt to use an `InvertibleReduction` with a `Storage` other than the one it was
+/// initialized with, it will panic.
+pub struct InvertibleReduction<ChunkKey, Element, Summary>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+{
+    parent_id: u64,
+    gc_chunk_list: RVec<Option<ChunkKey::Owned>>,
+    // tracks which chunks have changed since the last update
+    changed_chunks: RVec<()>,
+    rules: Arc<SummaryRules<Element, Summary, Summary>>,
+    add: InverseRule<Summary>,
+    subtract: InverseRule<Summary>,
+    chunkwise_summaries: HashMap<ChunkKey::Owned, ChunkSummary<Element, Summary>, HasherImpl>,
+    summary: Summary,
+}
+
+/// The summary of a single chunk.
+struct ChunkSummary<Element, Summary> {
+    summarize: Summarize<Element, Summary, Summary>,
+    // the part of the summary of the whole storage that came from this chunk
+    contributed: Summary,
+}
+
+impl<ChunkKey, Element, Summary> InvertibleReduction<ChunkKey, Element, Summary>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+    Summary: Clone + Default + PartialEq,
+{
+    /// Create a new `InvertibleReduction` on a `Storage`.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `ItemKey`: this is the `ItemKey` matching the `Storage`.
+    /// * `Map`: this operation produces a `Summary` of a single `Element`.
+    /// * `Add`: this operation adds one `Summary` into another.
+    /// * `Subtract`: this operation subtracts one `Summary` from another, undoing `Add`.
+    pub fn new<ItemKey, Map, Add, Subtract>(
+        storage: &Storage<ChunkKey, ItemKey, Element>,
+        map: Map,
+        add: Add,
+        subtract: Subtract,
+    ) -> Self
+    where
+        ItemKey: BorrowedKey + ?Sized,
+        ItemKey::Owned: ValidKey,
+        Element: Record<ChunkKey, ItemKey>,
+        Summary: 'static,
+        Map: Fn(&Element) -> Summary + Send + Sync + 'static,
+        Add: Fn(&mut Summary, &Summary) + Send + Sync + 'static,
+        Subtract: Fn(&mut Summary, &Summary) + Send + Sync + 'static,
+    {
+        let add: InverseRule<Summary> = Arc::new(add);
+        let subtract: InverseRule<Summary> = Arc::new(subtract);
+        let contribute = Arc::clone(&add);
+        let uncontribute = Arc::clone(&subtract);
+
+        InvertibleReduction {
+            parent_id: storage.id(),
+            gc_chunk_list: RVec::default(),
+            changed_chunks: RVec::default(),
+            rules: Arc::new(SummaryRules {
+                map: Arc::new(move |element, old_summary, _internal_idx| {
+                    let new_summary = map(element);
+
+                    if new_summary != *old_summary {
+                        Some(new_summary)
+                    } else {
+                        None
+                    }
+                }),
+                contribute: Arc::new(move |new_summary, _internal_idx, summary| {
+                    contribute(summary, new_summary)
+                }),
+                uncontribute: Arc::new(move |old_summary, _internal_idx, summary| {
+                    uncontribute(summary, old_summary)
+                }),
+            }),
+            add,
+            subtract,
+            chunkwise_summaries: HashMap::with_hasher(HasherImpl::default()),
+            summary: Summary::default(),
+        }
+    }
+
+    /// Summarize all of the elements of the given `Storage`.
+    pub fn reduce<ItemKey>(&mut self, storage: &Storage<ChunkKey, ItemKey, Element>) -> &Summary
+    where
+        Element: Record<ChunkKey, ItemKey>,
+        ItemKey: BorrowedKey + ?Sized,
+        ItemKey::Owned: ValidKey,
+    {
+        self.gc(storage);
+
+        let mut changed = Vec::new();
+
+        self.changed_chunks
+            .reduce(storage.internal_rvec(), 1, |chunk_storages, _, idx| {
+                if !chunk_storages.is_empty() {
+                    changed.push(idx);
+                }
+
+                None
+            });
+
+        for idx in changed {
+            let chunk_storage = &storage.internal_rvec()[idx];
+            let chunk_summary =
+                Self::update_chunk(&mut self.chunkwise_summaries, &self.rules, chunk_storage);
+
+            if chunk_summary.contributed != *chunk_summary.summarize.peek() {
+                (self.subtract)(&mut self.summary, &chunk_summary.contributed);
+                (self.add)(&mut self.summary, chunk_summary.summarize.peek());
+                chunk_summary.contributed = chunk_summary.summarize.peek().clone();
+            }
+        }
+
+        &self.summary
+    }
+
+    /// Summarize all of the elements of a single chunk.
+    pub fn reduce_chunk<ItemKey>(
+        &mut self,
+        storage: &Storage<ChunkKey, ItemKey, Element>,
+        chunk_key: &ChunkKey,
+    ) -> Option<&Summary>
+    where
+        Element: Record<ChunkKey, ItemKey>,
+        ItemKey: BorrowedKey + ?Sized,
+        ItemKey::Owned: ValidKey,
+    {
+        self.gc(storage);
+
+        let idx = storage.internal_idx_of(chunk_key)?;
+        let chunk_summary = Self::update_chunk(
+            &mut self.chunkwise_summaries,
+            &self.rules,
+            &storage.internal_rvec()[idx],
+        );
+
+        Some(chunk_summary.summarize.peek())
+    }
+
+    fn update_chunk<'a, ItemKey>(
+        chunkwise_summaries: &'a mut HashMap<
+            ChunkKey::Owned,
+            ChunkSummary<Element, Summary>,
+            HasherImpl,
+        >,
+        rules: &Arc<SummaryRules<Element, Summary, Summary>>,
+        chunk_storage: &ChunkStorage<ChunkKey, ItemKey, Element>,
+    ) -> &'a mut ChunkSummary<Element, Summary>
+    where
+        Element: Record<ChunkKey, ItemKey>,
+        ItemKey: BorrowedKey + ?Sized,
+        ItemKey::Owned: ValidKey,
+    {
+        let internal_storage = chunk_storage.internal_rvec();
+        let chunk_summary = chunkwise_summaries
+            .entry(chunk_storage.chunk_key().to_owned())
+            .or_insert_with(|| ChunkSummary {
+                summarize: Summarize::new(internal_storage, Arc::clone(rules)),
+                contributed: Summary::default(),
+            });
+
+        chunk_summary.summarize.update(internal_storage);
+        chunk_summary
+    }
+
+    fn gc<ItemKey>(&mut self, storage: &Storage<ChunkKey, ItemKey, Element>)
+    where
+        Element: Record<ChunkKey, ItemKey>,
+        ItemKey: BorrowedKey + ?Sized,
+        ItemKey::Owned: ValidKey,
+    {
+        assert_eq!(
+      self.parent_id,
+      storage.id(),
+      "Id mismatch: an InvertibleReduction may only be used with it's parent Storage, never any other Storage"
+    );
+
+        for (_, chunk_summary) in storage.gc(&mut self.gc_chunk_list, &mut self.chunkwise_summaries)
+        {
+            (self.subtract)(&mut self.summary, &chunk_summary.contributed);
+        }
+    }
+}
+
+impl<ChunkKey, Element, Summary> MemoryUser for InvertibleReduction<ChunkKey, Element, Summary>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+    Summary: Default + PartialEq,
+{
+    fn memory_usage(&self) -> MemoryUsage {
+        let mut result = MemoryUsage::merge(
+            self.gc_chunk_list.memory_usage(),
+            self.changed_chunks.memory_usage(),
+        );
+
+        for chunk_summary in self.chunkwise_summaries.values() {
+            result = MemoryUsage::merge(result, chunk_summary.summarize.memory_usage());
+        }
+
+        result
+    }
+
+    fn shrink_with<F: Fn(&MemoryUsage) -> Option<usize>>(&mut self, f: F) {
+        self.gc_chunk_list.shrink_with(&f);
+        self.changed_chunks.shrink_with(&f);
+
+        for chunk_summary in self.chunkwise_summaries.values_mut() {
+            chunk_summary.summarize.shrink_with(&f);
+        }
+    }
+}
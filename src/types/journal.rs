@@ -0,0 +1,173 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// A write-ahead journal of every mutation made to a `Storage`.
+///
+/// Attach a `Journal` to a `Storage` using `Storage::set_journal()`, or recover a `Storage`
+/// (with its `Journal` already attached) using `Storage::recover()`. From then on, every
+/// `add()`, `add_chunk()`, `modify()`, `remove()`, `remove_chunk()` and `Entry` mutation is
+/// appended to the journal file as one JSON record per line.
+///
+/// Each record holds the final state of the elements touched by a single operation, so
+/// replaying the journal is idempotent. Records are buffered; call `Storage::sync_journal()` to
+/// make them durable. Call `Storage::compact_journal()` to fold the journal into a fresh snapshot.
+///
+/// Requires the `serde` feature.
+///
+/// ```
+/// use retriever::prelude::*;
+///
+/// let dir = std::env::temp_dir().join(format!("retriever-journal-doctest-{}", std::process::id()));
+/// std::fs::create_dir_all(&dir).unwrap();
+/// let snapshot = dir.join("snapshot.json");
+/// let journal = dir.join("journal.jsonl");
+///
+/// // There's nothing to recover the first time, so this is just an empty Storage.
+/// let mut storage : Storage<u64, u64, (u64, u64, String)> =
+///   Storage::recover(&snapshot, &journal).unwrap();
+///
+/// storage.add((1, 1, String::from("hello")));
+/// storage.add((1, 2, String::from("goodbye")));
+/// storage.compact_journal(&snapshot).unwrap();
+///
+/// storage.entry(&ID.chunk(1).item(1)).and_modify(|x| x.2.push_str(", world"));
+/// storage.remove(ID.chunk(1).item(2), std::mem::drop);
+/// storage.sync_journal().unwrap();
+///
+/// // Crash!
+/// drop(storage);
+///
+/// let mut storage : Storage<u64, u64, (u64, u64, String)> =
+///   Storage::recover(&snapshot, &journal).unwrap();
+///
+/// assert_eq!(Some(&(1, 1, String::from("hello, world"))), storage.get(&ID.chunk(1).item(1)));
+/// assert_eq!(None, storage.get(&ID.chunk(1).item(2)));
+///
+/// # storage.validate();
+/// # std::fs::remove_dir_all(&dir).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct Journal {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    error: Option<io::Error>,
+}
+
+/// A single journal record.
+#[derive(Serialize, Deserialize)]
+pub(crate) enum JournalOp<ChunkKey, ItemKey, Element> {
+    /// Insert this element, or replace the element that has the same id.
+    Put(Element),
+    /// Remove the element with this id, if there is one.
+    Remove(ChunkKey, ItemKey),
+    /// Remove the entire chunk, if it exists.
+    RemoveChunk(ChunkKey),
+}
+
+impl Journal {
+    /// Open the journal file at the given path, creating it if it does not exist.
+    /// New records are appended to the end of the file.
+    pub fn open<P: Into<PathBuf>>(path: P) -> io::Result<Self> {
+        let path = path.into();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+
+        Ok(Journal {
+            path,
+            writer: BufWriter::new(file),
+            error: None,
+        })
+    }
+
+    /// The path of this journal file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append a record. `Storage` mutations can't fail, so any error is held until the next
+    /// call to `sync()`.
+    pub(crate) fn write<T: Serialize>(&mut self, op: &T) {
+        if self.error.is_some() {
+            return;
+        }
+
+        let result = serde_json::to_writer(&mut self.writer, op)
+            .map_err(io::Error::from)
+            .and_then(|_| self.writer.write_all(b"\n"));
+
+        if let Err(e) = result {
+            self.error = Some(e);
+        }
+    }
+
+    /// Flush all records to durable storage, reporting any error encountered since the last sync.
+    pub(crate) fn sync(&mut self) -> io::Result<()> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+
+        self.writer.flush()?;
+        self.writer.get_ref().sync_data()
+    }
+
+    /// Discard every record in this journal, including any that are still buffered.
+    pub(crate) fn truncate(&mut self) -> io::Result<()> {
+        let file = OpenOptions::new().append(true).open(&self.path)?;
+        file.set_len(0)?;
+        file.sync_all()?;
+
+        // Dropping a BufWriter would flush it, so take it apart instead.
+        let _ = std::mem::replace(&mut self.writer, BufWriter::new(file)).into_parts();
+        self.error = None;
+
+        Ok(())
+    }
+}
+
+type WriteOp<ChunkKey, ItemKey, Element> =
+    for<'a> fn(&mut Journal, JournalOp<&'a ChunkKey, &'a ItemKey, &'a Element>);
+
+/// A `Journal` together with the knowledge of how to serialize one particular kind of `Storage`.
+pub(crate) struct JournalHandle<ChunkKey: ?Sized, ItemKey: ?Sized, Element> {
+    journal: Journal,
+    write: WriteOp<ChunkKey, ItemKey, Element>,
+}
+
+impl<ChunkKey, ItemKey, Element> JournalHandle<ChunkKey, ItemKey, Element>
+where
+    ChunkKey: ?Sized,
+    ItemKey: ?Sized,
+{
+    pub(crate) fn new(journal: Journal) -> Self
+    where
+        ChunkKey: Serialize,
+        ItemKey: Serialize,
+        Element: Serialize,
+    {
+        JournalHandle {
+            journal,
+            write: |journal, op| journal.write(&op),
+        }
+    }
+
+    pub(crate) fn journal_mut(&mut self) -> &mut Journal {
+        &mut self.journal
+    }
+
+    pub(crate) fn into_journal(self) -> Journal {
+        self.journal
+    }
+
+    pub(crate) fn put(&mut self, element: &Element) {
+        (self.write)(&mut self.journal, JournalOp::Put(element))
+    }
+
+    pub(crate) fn remove(&mut self, chunk_key: &ChunkKey, item_key: &ItemKey) {
+        (self.write)(&mut self.journal, JournalOp::Remove(chunk_key, item_key))
+    }
+
+    pub(crate) fn remove_chunk(&mut self, chunk_key: &ChunkKey) {
+        (self.write)(&mut self.journal, JournalOp::RemoveChunk(chunk_key))
+    }
+}
@@ -1,12 +1,33 @@
+/// Module for reporting which elements of a Storage have changed.
+pub mod change_cursor;
 /// Module for a data type representing the storage for a single chunk.
 pub mod chunk_storage;
+/// Module for summarizing sets of chunks.
+pub mod convolutional_reduction;
 /// Module for an interface to edit stored values.
 pub mod editor;
 /// Module for an interface to edit stored values that may or may not exist.
 pub mod entry;
+/// Module for the error type returned by fallible operations.
+pub mod error;
+/// Module for summarizing only the elements that match a Query.
+pub mod filtered_reduction;
+/// Module for summarizing groups of elements that share a key.
+pub mod grouped_reduction;
 /// Module for a data type that serves as reference to a stored value by it's chunk key and item key.
 pub mod id;
+/// Module for summarizing a Storage with operations that can be undone.
+pub mod invertible_reduction;
+/// Module for a write-ahead journal of `Storage` mutations.
+#[cfg(feature = "serde")]
+pub mod journal;
+/// Module for paginated query results.
+pub mod page;
+/// Module for describing how a Query is evaluated.
+pub mod plan;
 /// Module for an interface to reduce a large number of collected values down to a single value.
 pub mod reduction;
 /// Module for the primary Storage type.
 pub mod storage;
+/// Module for batching changes to a Storage and applying them all at once.
+pub mod transaction;
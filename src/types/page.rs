@@ -0,0 +1,73 @@
+use super::id::Id;
+
+/// An opaque position within a sequence of pages, as returned by `Page::next_cursor()`.
+/// Pass it to `Storage::page()` to fetch the following page.
+///
+/// A `PageCursor` remembers the id of the last element of a page, not a position within the
+/// `Storage`, so it stays valid even if elements are added or removed between pages. If the
+/// `serde` feature is enabled, it can be serialized and handed to a client.
+///
+/// # Type Parameters
+///
+/// * `ChunkKey`: The owned chunk key type of the `Storage`
+/// * `ItemKey`: The owned item key type of the `Storage`
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PageCursor<ChunkKey, ItemKey> {
+    after: Id<ChunkKey, ItemKey>,
+}
+
+impl<ChunkKey, ItemKey> PageCursor<ChunkKey, ItemKey> {
+    pub(crate) fn new(after: Id<ChunkKey, ItemKey>) -> Self {
+        PageCursor { after }
+    }
+
+    pub(crate) fn after(&self) -> &Id<ChunkKey, ItemKey> {
+        &self.after
+    }
+}
+
+/// One page of query results, as returned by `Storage::page()`.
+///
+/// # Type Parameters
+///
+/// * `ChunkKey`: The owned chunk key type of the `Storage`
+/// * `ItemKey`: The owned item key type of the `Storage`
+/// * `Element`: The element type of the `Storage`
+pub struct Page<'a, ChunkKey, ItemKey, Element> {
+    elements: Vec<&'a Element>,
+    next: Option<PageCursor<ChunkKey, ItemKey>>,
+}
+
+impl<'a, ChunkKey, ItemKey, Element> Page<'a, ChunkKey, ItemKey, Element> {
+    pub(crate) fn new(
+        elements: Vec<&'a Element>,
+        next: Option<PageCursor<ChunkKey, ItemKey>>,
+    ) -> Self {
+        Page { elements, next }
+    }
+
+    /// The elements of this page, in order of chunk key and then item key.
+    pub fn elements(&self) -> &[&'a Element] {
+        &self.elements
+    }
+
+    /// A cursor to fetch the next page, or `None` if this is the last page.
+    pub fn next_cursor(&self) -> Option<&PageCursor<ChunkKey, ItemKey>> {
+        self.next.as_ref()
+    }
+
+    /// Take the elements of this page.
+    pub fn into_elements(self) -> Vec<&'a Element> {
+        self.elements
+    }
+}
+
+impl<'a, ChunkKey, ItemKey, Element> IntoIterator for Page<'a, ChunkKey, ItemKey, Element> {
+    type Item = &'a Element;
+    type IntoIter = std::vec::IntoIter<&'a Element>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.elements.into_iter()
+    }
+}
@@ -0,0 +1,111 @@
+use crate::traits::idxset::IdxSet;
+use crate::traits::query::Query;
+use crate::traits::record::Record;
+use crate::traits::valid_key::{BorrowedKey, ValidKey};
+use crate::types::storage::Storage;
+use std::fmt;
+
+/// A description of how a `Query` is evaluated against a particular `Storage`, as returned by
+/// `Query::explain()`. Compound queries have one child `Plan` for each query they are built
+/// from, each measured as if it were evaluated on its own.
+///
+/// A node whose `items` is much larger than its `matches` is scanning items and discarding most
+/// of them, which is a sign that an index could help.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Plan {
+    /// The kind of query, such as `"Filter"` or `"Chunks"`.
+    pub query: &'static str,
+
+    /// The number of candidate chunks, from `Query::chunk_idxs()`.
+    pub chunks: usize,
+
+    /// The number of candidate elements, from `Query::item_idxs()`, over every candidate chunk.
+    pub items: usize,
+
+    /// The number of candidate elements that actually matched the query.
+    pub matches: usize,
+
+    /// True if this query runs a `Query::test()` on each candidate element, beyond what its
+    /// children do, such as a `Filter` closure.
+    pub per_element_test: bool,
+
+    /// The plans of the queries that this query is built from.
+    pub children: Vec<Plan>,
+}
+
+impl Plan {
+    /// Measure a query against a storage. Used to implement `Query::explain()`.
+    pub fn measure<Q, ChunkKey, ItemKey, Element>(
+        query: &Q,
+        storage: &Storage<ChunkKey, ItemKey, Element>,
+        name: &'static str,
+        per_element_test: bool,
+        children: Vec<Plan>,
+    ) -> Plan
+    where
+        Q: Query<ChunkKey, ItemKey, Element> + ?Sized,
+        ChunkKey: BorrowedKey + ?Sized,
+        ChunkKey::Owned: ValidKey,
+        ItemKey: BorrowedKey + ?Sized,
+        ItemKey::Owned: ValidKey,
+        Element: Record<ChunkKey, ItemKey>,
+    {
+        let mut plan = Plan {
+            query: name,
+            chunks: 0,
+            items: 0,
+            matches: 0,
+            per_element_test,
+            children,
+        };
+
+        let chunks = storage.internal_rvec();
+        for chunk_idx in query.chunk_idxs(storage).into_idx_iter().flatten() {
+            let chunk = &chunks[chunk_idx];
+            plan.chunks += 1;
+
+            for idx in query
+                .item_idxs(chunk.chunk_key(), chunk)
+                .into_idx_iter()
+                .flatten()
+            {
+                plan.items += 1;
+                if query.test(chunk.get_idx(idx)) {
+                    plan.matches += 1;
+                }
+            }
+        }
+
+        plan
+    }
+
+    fn fmt_indented(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        writeln!(
+            f,
+            "{:indent$}{}: {} chunks, {} items, {} matches{}",
+            "",
+            self.query,
+            self.chunks,
+            self.items,
+            self.matches,
+            if self.per_element_test {
+                " (tests each item)"
+            } else {
+                ""
+            },
+            indent = depth * 2
+        )?;
+
+        for child in self.children.iter() {
+            child.fmt_indented(f, depth + 1)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_indented(f, 0)
+    }
+}
@@ -260,23 +260,16 @@ where
         let group_size = self.group_size;
         let rules = &self.rules;
 
-        chunkwise_summaries.reduce(&self.gc_chunk_list, 1, |chunk_key, _old_summary, idx| {
-            assert!(chunk_key.len() <= 1);
+        chunkwise_summaries.reduce(storage.internal_rvec(), 1, |chunks, _old_summary, _idx| {
+            assert!(chunks.len() <= 1);
 
-            if chunk_key.is_empty() {
-                return None;
-            }
-
-            let chunk_key = chunk_key[0]
-                .as_ref()
-                .cloned()
-                .expect("retriever bug: chunk keys should be defined for all indices after gc");
-            let internal_storage = storage.internal_rvec()[idx].internal_rvec();
+            let chunk = chunks.first()?;
+            let internal_storage = chunk.internal_rvec();
 
             chunkwise_reductions
-                .entry(chunk_key)
+                .entry(chunk.chunk_key().to_owned())
                 .or_insert_with(|| Reduce::new(internal_storage, group_size, rules.clone()))
-                .update(&internal_storage)
+                .update(internal_storage)
                 .cloned()
         });
 
@@ -312,7 +305,75 @@ where
         chunkwise_reductions
             .entry(chunk_key.to_owned())
             .or_insert_with(|| Reduce::new(internal_storage, group_size, rules.clone()))
-            .update(&internal_storage)
+            .update(internal_storage)
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<ChunkKey, Element, Summary> Reduction<ChunkKey, Element, Summary>
+where
+    ChunkKey: BorrowedKey + Sync + ?Sized,
+    ChunkKey::Owned: ValidKey + Send + Sync,
+    Element: Send + Sync,
+    Summary: Default + Clone + Send + Sync,
+{
+    /// Reduce all of the elements of the given `Storage` down to a single value, reducing
+    /// each chunk in parallel. The result is always the same as `Reduction::reduce()`.
+    ///
+    /// Every chunk is examined, even chunks that haven't changed, so this is only worthwhile
+    /// when there are large or many changes between reductions.
+    ///
+    /// Requires the `rayon` feature.
+    pub fn par_reduce<ItemKey>(
+        &mut self,
+        storage: &Storage<ChunkKey, ItemKey, Element>,
+    ) -> Option<&Summary>
+    where
+        Element: Record<ChunkKey, ItemKey>,
+        ItemKey: BorrowedKey + Sync + ?Sized,
+        ItemKey::Owned: ValidKey + Send + Sync,
+    {
+        use rayon::prelude::*;
+
+        assert_eq!(
+      self.parent_id,
+      storage.id(),
+      "Id mismatch: a Reduction may only be used with it's parent Storage, never any other Storage"
+    );
+
+        self.gc(storage);
+
+        let group_size = self.group_size;
+        let rules = &self.rules;
+
+        for chunk in storage.internal_rvec().iter() {
+            self.chunkwise_reductions
+                .entry(chunk.chunk_key().to_owned())
+                .or_insert_with(|| Reduce::new(chunk.internal_rvec(), group_size, rules.clone()));
+        }
+
+        self.chunkwise_reductions
+            .par_iter_mut()
+            .for_each(|(chunk_key, reduce)| {
+                if let Some(idx) = storage.internal_idx_of::<ChunkKey::Owned>(chunk_key) {
+                    reduce.update(storage.internal_rvec()[idx].internal_rvec());
+                }
+            });
+
+        let chunkwise_reductions = &self.chunkwise_reductions;
+
+        self.chunkwise_summaries.reduce(
+            storage.internal_rvec(),
+            1,
+            |chunks, _old_summary, _idx| {
+                chunkwise_reductions
+                    .get(chunks.first()?.chunk_key())?
+                    .peek()
+                    .cloned()
+            },
+        );
+
+        self.reduction.update(&self.chunkwise_summaries)
     }
 }
 
@@ -1,21 +1,43 @@
 use super::chunk_storage::*;
 use super::entry::Entry;
+use super::error::{Error, LoadChunkError};
+use super::id::Id;
+use super::page::{Page, PageCursor};
+use crate::bits::Bitset;
+use crate::internal::bounds;
 use crate::internal::hasher::HasherImpl;
 use crate::internal::mr::rvec::RVec;
+use crate::traits::chunk_store::ChunkStore;
 use crate::traits::idxset::IdxSet;
 use crate::traits::memory_usage::{MemoryUsage, MemoryUser};
 use crate::traits::query::Query;
 use crate::traits::record::Record;
 use crate::traits::valid_key::{BorrowedKey, ValidKey};
-use crate::types::editor::Editor;
+use crate::types::editor::{Editor, ElementMut};
+#[cfg(feature = "serde")]
+use crate::types::journal::{Journal, JournalHandle, JournalOp};
+use crate::types::transaction::Transaction;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
 use std::borrow::Borrow;
-use std::collections::{HashMap, HashSet};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet};
+#[cfg(feature = "serde")]
+use std::fs;
 use std::hash::Hash;
+#[cfg(feature = "serde")]
+use std::io;
+use std::ops::Bound;
+#[cfg(feature = "serde")]
+use std::path::Path;
 use std::sync::atomic::AtomicU64;
 use std::sync::atomic::Ordering;
 
 static ID_COUNTER: AtomicU64 = AtomicU64::new(0);
 
+type LoadChunkResult<ChunkKey, ItemKey, StoreError> =
+    Result<bool, LoadChunkError<ChunkKey, ItemKey, StoreError>>;
+
 /// Chunked, indexed storage.
 ///
 /// # Type Parameters
@@ -26,7 +48,10 @@ static ID_COUNTER: AtomicU64 = AtomicU64::new(0);
 /// * `ItemKey`: each `Element` is a `Record` that has exactly one `ItemKey`. Every `Element`
 ///   within a chunk must have an `ItemKey` that is unique to that chunk.
 /// * `Element`: the type contained in this `Storage`.
-#[derive(Clone)]
+///
+/// If the `serde` feature is enabled, a `Storage` serializes as a list of chunks, each of which
+/// is a list of `Elements`. Deserializing rebuilds all of the internal indices. It can also
+/// record every mutation to a `Journal`; see `Storage::recover()`.
 pub struct Storage<ChunkKey: ?Sized, ItemKey: ?Sized, Element>
 where
     ChunkKey: BorrowedKey,
@@ -38,6 +63,14 @@ where
     chunks: RVec<ChunkStorage<ChunkKey, ItemKey, Element>>,
     dirty: Vec<usize>,
     index: HashMap<ChunkKey::Owned, usize, HasherImpl>,
+    // The same as `index`, but in order of chunk key. `None` unless enabled with
+    // `set_sorted_chunk_keys()`.
+    sorted_index: Option<BTreeMap<ChunkKey::Owned, usize>>,
+    // Chunks that may contain elements that haven't been written to the journal yet.
+    // `None` unless this `Storage` is journaled.
+    journal_chunks: Option<Vec<usize>>,
+    #[cfg(feature = "serde")]
+    journal: Option<JournalHandle<ChunkKey, ItemKey, Element>>,
 }
 
 impl<ChunkKey, ItemKey, Element> Storage<ChunkKey, ItemKey, Element>
@@ -82,6 +115,10 @@ where
             chunks: RVec::default(),
             dirty: Vec::default(),
             index: HashMap::with_hasher(crate::internal::hasher::HasherImpl::default()),
+            sorted_index: None,
+            journal_chunks: None,
+            #[cfg(feature = "serde")]
+            journal: None,
         }
     }
 
@@ -99,8 +136,13 @@ where
             idx
         } else {
             let new_idx = self.chunks.len();
+            let mut chunk = ChunkStorage::new(chunk_key.to_owned());
+            chunk.set_journaled(self.journal_chunks.is_some());
             self.index.insert(chunk_key.to_owned(), new_idx);
-            self.chunks.push(ChunkStorage::new(chunk_key.to_owned()));
+            if let Some(sorted_index) = self.sorted_index.as_mut() {
+                sorted_index.insert(chunk_key.to_owned(), new_idx);
+            }
+            self.chunks.push(chunk);
             new_idx
         };
 
@@ -108,6 +150,8 @@ where
             self.dirty(idx);
         }
 
+        self.journal_chunk(idx);
+
         &mut self.chunks[idx]
     }
 
@@ -173,10 +217,40 @@ where
         let chunk_key = element.chunk_key();
         let chunk_key_ref = chunk_key.borrow();
         self.chunk(chunk_key_ref, false).add(element);
+        self.write_journal();
 
         self
     }
 
+    /// Add the given element to this Storage, or report an `Error::DuplicateId` if an element
+    /// with the same id already exists. On error, this `Storage` is untouched.
+    ///
+    /// ```
+    /// use retriever::prelude::*;
+    /// use retriever::Error;
+    ///
+    /// let mut storage : Storage<u64, u64, (u64, u64, &'static str)> = Storage::new();
+    ///
+    /// assert!(storage.try_add((1, 1, "hello")).is_ok());
+    /// assert_eq!(
+    ///   Err(Error::DuplicateId(ID.chunk(1).item(1))),
+    ///   storage.try_add((1, 1, "goodbye")).map(|_| ())
+    /// );
+    /// assert_eq!(Some(&(1, 1, "hello")), storage.get(&ID.chunk(1).item(1)));
+    ///
+    /// # storage.validate();
+    /// ```
+    pub fn try_add(
+        &mut self,
+        element: Element,
+    ) -> Result<&mut Self, Error<ChunkKey::Owned, ItemKey::Owned>> {
+        if self.get(&element).is_some() {
+            return Err(Error::DuplicateId(Id::cloned(&element)));
+        }
+
+        Ok(self.add(element))
+    }
+
     /// Add some elements that are all part of the same chunk.
     ///
     /// # Panic
@@ -197,9 +271,71 @@ where
             self.chunk(chunk_key_cow.borrow(), false).extend(i);
         }
 
+        self.write_journal();
+
         self
     }
 
+    /// Add some elements that are all part of the same chunk, or report an `Error` if any
+    /// `Element` does not have the same chunk key as the first, or if any two `Elements` have
+    /// the same id. Either every `Element` is added, or, on error, none of them are.
+    ///
+    /// ```
+    /// use retriever::prelude::*;
+    /// use retriever::Error;
+    ///
+    /// let mut storage : Storage<u64, u64, (u64, u64, &'static str)> = Storage::new();
+    ///
+    /// storage.add((1, 1, "apples"));
+    ///
+    /// assert_eq!(
+    ///   Err(Error::DuplicateId(ID.chunk(1).item(1))),
+    ///   storage.try_add_chunk(vec![(1, 2, "oranges"), (1, 1, "bananas")]).map(|_| ())
+    /// );
+    /// assert_eq!(
+    ///   Err(Error::WrongId { expected: ID.chunk(1).item(3), found: ID.chunk(2).item(3) }),
+    ///   storage.try_add_chunk(vec![(1, 2, "oranges"), (2, 3, "bananas")]).map(|_| ())
+    /// );
+    /// assert_eq!(1, storage.iter().count());
+    ///
+    /// assert!(storage.try_add_chunk(vec![(1, 2, "oranges"), (1, 3, "bananas")]).is_ok());
+    /// assert_eq!(3, storage.iter().count());
+    ///
+    /// # storage.validate();
+    /// ```
+    pub fn try_add_chunk<I, K>(
+        &mut self,
+        i: I,
+    ) -> Result<&mut Self, Error<ChunkKey::Owned, ItemKey::Owned>>
+    where
+        I: IntoIterator<Item = K>,
+        Element: Borrow<K>,
+        K: ToOwned<Owned = Element> + Record<ChunkKey, ItemKey>,
+    {
+        let elements: Vec<K> = i.into_iter().collect();
+        let mut item_keys: HashSet<ItemKey::Owned> = HashSet::with_capacity(elements.len());
+
+        if let Some(first) = elements.first() {
+            let chunk_key = first.chunk_key();
+
+            for element in elements.iter() {
+                if element.chunk_key() != chunk_key {
+                    return Err(Error::WrongId {
+                        expected: Id::new(chunk_key.into_owned(), element.item_key().into_owned()),
+                        found: Id::cloned(element),
+                    });
+                }
+
+                if !item_keys.insert(element.item_key().into_owned()) || self.get(element).is_some()
+                {
+                    return Err(Error::DuplicateId(Id::cloned(element)));
+                }
+            }
+        }
+
+        Ok(self.add_chunk(elements))
+    }
+
     /// Add many many elements, grouped into chunks.
     ///
     /// # Type Parameters
@@ -230,33 +366,77 @@ where
     }
 
     fn clean(&mut self) {
+        // Removing empty chunks below would lose track of any elements removed from them.
+        self.write_journal();
+
         if self.dirty.is_empty() {
             return;
         }
 
-        self.dirty.sort_unstable();
-        self.dirty.dedup();
+        let mut dirty = std::mem::take(&mut self.dirty);
+        dirty.sort_unstable();
+        dirty.dedup();
 
-        for idx in self.dirty.iter().rev() {
-            if !self.chunks[*idx].is_empty() {
+        for idx in dirty.into_iter().rev() {
+            if !self.chunks[idx].is_empty() {
                 continue;
             }
 
-            self.index.remove(self.chunks[*idx].chunk_key());
-            self.chunks.swap_remove(*idx);
-            if self.chunks.len() > *idx {
-                self.index
-                    .insert(self.chunks[*idx].chunk_key().to_owned(), *idx);
+            self.swap_remove_chunk(idx);
+        }
+    }
+
+    /// Remove a chunk, moving the last chunk into its place.
+    fn swap_remove_chunk(&mut self, idx: usize) -> ChunkStorage<ChunkKey, ItemKey, Element> {
+        let chunk = self.chunks.swap_remove(idx);
+        self.index.remove(chunk.chunk_key());
+        if let Some(sorted_index) = self.sorted_index.as_mut() {
+            sorted_index.remove(chunk.chunk_key());
+        }
+
+        if self.chunks.len() > idx {
+            let moved_key = self.chunks[idx].chunk_key().to_owned();
+            if let Some(sorted_index) = self.sorted_index.as_mut() {
+                sorted_index.insert(moved_key.clone(), idx);
             }
+            self.index.insert(moved_key, idx);
         }
 
-        self.dirty.clear();
+        chunk
     }
 
     fn dirty(&mut self, idx: usize) {
         self.dirty.push(idx);
     }
 
+    fn journal_chunk(&mut self, idx: usize) {
+        if let Some(journal_chunks) = self.journal_chunks.as_mut() {
+            journal_chunks.push(idx);
+        }
+    }
+
+    /// Write every element touched since the last call to the journal, if there is one.
+    fn write_journal(&mut self) {
+        #[cfg(feature = "serde")]
+        if let (Some(journal), Some(journal_chunks)) =
+            (self.journal.as_mut(), self.journal_chunks.as_mut())
+        {
+            journal_chunks.sort_unstable();
+            journal_chunks.dedup();
+
+            for idx in journal_chunks.drain(..) {
+                let chunk = &mut self.chunks[idx];
+
+                for item_key in chunk.take_touched() {
+                    match chunk.internal_idx_of::<ItemKey>(item_key.borrow()) {
+                        Some(item_idx) => journal.put(chunk.get_idx(item_idx)),
+                        None => journal.remove(chunk.chunk_key(), item_key.borrow()),
+                    }
+                }
+            }
+        }
+    }
+
     /// Dissolve this Storage into a list of chunks.
     pub fn dissolve(self) -> impl IntoIterator<Item = Vec<Element>> {
         let chunks: Vec<_> = self.chunks.into();
@@ -312,8 +492,8 @@ where
     /// # Type Parameters
     ///
     /// * `R`: Any `Record` with the same `ChunkKey` and `ItemKey` as the record you want to
-    /// access. If there's no obvious choice for `R`, consider using `retriever::types::id::Id`
-    /// to construct an appropriate key.
+    ///   access. If there's no obvious choice for `R`, consider using `retriever::types::id::Id`
+    ///   to construct an appropriate key.
     ///
     /// # Example
     ///
@@ -380,8 +560,8 @@ where
     /// # Type Parameters:
     ///
     /// * `R`: Any `Record` with the same `ChunkKey` and `ItemKey` as the record you want to
-    /// access. If there's no obvious choice for `R`, consider using `retriever::types::id::Id`
-    /// to construct an appropriate key.
+    ///   access. If there's no obvious choice for `R`, consider using `retriever::types::id::Id`
+    ///   to construct an appropriate key.
     ///
     /// # Example
     ///
@@ -461,6 +641,97 @@ where
             .entry(unique_id)
     }
 
+    /// Insert the given element, replacing and returning any element that has the same id.
+    fn put(&mut self, element: Element) -> Option<Element> {
+        let chunk = self.chunk(element.chunk_key().borrow(), false);
+        let idx = chunk.internal_idx_of::<ItemKey>(element.item_key().borrow());
+
+        match idx {
+            Some(idx) => Some(std::mem::replace(chunk.get_idx_mut(idx), element)),
+            None => {
+                chunk.add(element);
+                None
+            }
+        }
+    }
+
+    /// Insert the given element, or replace the element that has the same id. Returns the
+    /// element that was replaced, if there was one.
+    ///
+    /// A replacement is seen by indexes and reductions as a modification of the existing
+    /// element, just like `Storage::modify()`, rather than a removal followed by an addition.
+    ///
+    /// ```
+    /// use retriever::prelude::*;
+    ///
+    /// let mut storage : Storage<u64, u64, (u64, u64, &'static str)> = Storage::new();
+    ///
+    /// assert_eq!(None, storage.upsert((1, 1, "hello")));
+    /// assert_eq!(Some((1, 1, "hello")), storage.upsert((1, 1, "goodbye")));
+    /// assert_eq!(Some(&(1, 1, "goodbye")), storage.get(&ID.chunk(1).item(1)));
+    ///
+    /// # storage.validate();
+    /// ```
+    pub fn upsert(&mut self, element: Element) -> Option<Element> {
+        self.clean();
+
+        let result = self.put(element);
+        self.write_journal();
+
+        result
+    }
+
+    /// Insert or replace many elements, as `Storage::upsert()`. Returns every element that
+    /// was replaced, in the order they were replaced.
+    ///
+    /// If the same id appears more than once, the last element with that id wins, and the
+    /// earlier ones are returned along with the other replaced elements.
+    ///
+    /// ```
+    /// use retriever::prelude::*;
+    ///
+    /// let mut storage : Storage<u64, u64, (u64, u64, &'static str)> = Storage::new();
+    ///
+    /// storage.add((1, 1, "apples"));
+    ///
+    /// let replaced = storage.upsert_all(vec![
+    ///   (1, 1, "oranges"),
+    ///   (1, 2, "bananas"),
+    ///   (2, 1, "pears"),
+    /// ]);
+    ///
+    /// assert_eq!(vec![(1, 1, "apples")], replaced);
+    /// assert_eq!(3, storage.iter().count());
+    /// assert_eq!(Some(&(1, 1, "oranges")), storage.get(&ID.chunk(1).item(1)));
+    ///
+    /// # storage.validate();
+    /// ```
+    pub fn upsert_all<I>(&mut self, i: I) -> Vec<Element>
+    where
+        I: IntoIterator<Item = Element>,
+    {
+        self.clean();
+
+        let result = i
+            .into_iter()
+            .filter_map(|element| self.put(element))
+            .collect();
+        self.write_journal();
+
+        result
+    }
+
+    /// Remove the element with the given id, if it exists. The caller must `clean()` afterwards.
+    fn remove_item(&mut self, chunk_key: &ChunkKey, item_key: &ItemKey) -> Option<Element> {
+        let idx = self.internal_idx_of(chunk_key)?;
+        let item_idx = self.chunks[idx].internal_idx_of(item_key)?;
+
+        self.dirty(idx);
+        self.journal_chunk(idx);
+
+        Some(self.chunks[idx].remove_idx(item_idx))
+    }
+
     /// Iterate over every element in storage.
     ///
     /// # Example
@@ -489,6 +760,9 @@ where
 
     /// Iterate over elements according to some Query. A variety of builtin queries are provided.
     ///
+    /// Elements are visited in no particular order. Use `query_sorted_by()` or `page()` when
+    /// the order matters.
+    ///
     /// # Type Parameters
     ///
     /// * `Q`: Any `Query`. There are a variety of useful `Queries`:
@@ -538,7 +812,7 @@ where
     where
         Q: Query<ChunkKey, ItemKey, Element> + Clone + 'a,
     {
-        let chunk_idxs = query.chunk_idxs(&self);
+        let chunk_idxs = query.chunk_idxs(self);
 
         chunk_idxs
             .into_idx_iter()
@@ -551,6 +825,155 @@ where
             )
     }
 
+    /// Iterate over elements according to some Query, sorted by a key. Elements with equal keys
+    /// are sorted by chunk key and then by item key, so the order is always the same.
+    ///
+    /// This collects and sorts every matching element before returning the first one.
+    ///
+    /// ```
+    /// use retriever::prelude::*;
+    ///
+    /// let mut storage : Storage<u8, u16, (u8, u16, i64)> = Storage::new();
+    ///
+    /// storage.add((1, 1000, 17));
+    /// storage.add((1, 1001, 53));
+    /// storage.add((2, 2000, 17));
+    /// storage.add((2, 2001, -19));
+    ///
+    /// let sorted : Vec<_> = storage.query_sorted_by(Everything, |x| x.2).collect();
+    /// assert_eq!(
+    ///   vec![&(2, 2001, -19), &(1, 1000, 17), &(2, 2000, 17), &(1, 1001, 53)],
+    ///   sorted
+    /// );
+    ///
+    /// # storage.validate();
+    /// ```
+    pub fn query_sorted_by<'a, Q, K, F>(
+        &'a self,
+        query: Q,
+        mut key_fn: F,
+    ) -> impl Iterator<Item = &'a Element>
+    where
+        Q: Query<ChunkKey, ItemKey, Element> + Clone + 'a,
+        K: Ord,
+        F: FnMut(&Element) -> K,
+    {
+        let mut found: Vec<(K, &'a Element)> = self
+            .query(query)
+            .map(|element| (key_fn(element), element))
+            .collect();
+
+        found.sort_by(|(a_key, a), (b_key, b)| {
+            a_key
+                .cmp(b_key)
+                .then_with(|| a.chunk_key().cmp(&b.chunk_key()))
+                .then_with(|| a.item_key().cmp(&b.item_key()))
+        });
+
+        found.into_iter().map(|(_, element)| element)
+    }
+
+    /// Fetch one page of the elements of a Query, in order of chunk key and then item key.
+    /// Start with `None`, then pass the `PageCursor` from each `Page` to fetch the next one.
+    ///
+    /// Pages pick up after the id of the last element of the previous page, so elements that are
+    /// added or removed between pages never cause other elements to be skipped or repeated.
+    ///
+    /// # Panic
+    ///
+    /// This method panics if `limit` is zero.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use retriever::prelude::*;
+    ///
+    /// let mut storage : Storage<u8, u16, (u8, u16, &'static str)> = Storage::new();
+    ///
+    /// storage.add((2, 1, "cherry"));
+    /// storage.add((1, 2, "banana"));
+    /// storage.add((1, 1, "apple"));
+    /// storage.add((3, 1, "date"));
+    ///
+    /// let page = storage.page(Everything, 2, None);
+    /// assert_eq!(vec![&(1, 1, "apple"), &(1, 2, "banana")], page.elements());
+    /// let cursor = page.next_cursor().cloned().unwrap();
+    ///
+    /// // Changes between pages don't disturb the next page.
+    /// storage.remove(ID.chunk(1).item(1), std::mem::drop);
+    /// storage.add((2, 0, "blueberry"));
+    ///
+    /// let page = storage.page(Everything, 2, Some(&cursor));
+    /// assert_eq!(vec![&(2, 0, "blueberry"), &(2, 1, "cherry")], page.elements());
+    /// let cursor = page.next_cursor().cloned().unwrap();
+    ///
+    /// let page = storage.page(Everything, 2, Some(&cursor));
+    /// assert_eq!(vec![&(3, 1, "date")], page.elements());
+    /// assert!(page.next_cursor().is_none());
+    ///
+    /// # storage.validate();
+    /// ```
+    pub fn page<'a, Q>(
+        &'a self,
+        query: Q,
+        limit: usize,
+        after: Option<&PageCursor<ChunkKey::Owned, ItemKey::Owned>>,
+    ) -> Page<'a, ChunkKey::Owned, ItemKey::Owned, Element>
+    where
+        Q: Query<ChunkKey, ItemKey, Element> + Clone + 'a,
+    {
+        assert!(limit > 0, "retriever: page limit must be at least one");
+
+        let after = after.map(|cursor| {
+            let after = cursor.after();
+            (after.0.borrow(), after.1.borrow())
+        });
+
+        let mut chunk_idxs: Vec<usize> = query
+            .chunk_idxs(self)
+            .into_idx_iter()
+            .flatten()
+            .filter(|idx| match after {
+                Some((chunk_key, _)) => self.chunks[*idx].chunk_key() >= chunk_key,
+                None => true,
+            })
+            .collect();
+        chunk_idxs.sort_by(|a, b| self.chunks[*a].chunk_key().cmp(self.chunks[*b].chunk_key()));
+
+        // Look for one extra element, to find out whether there's another page.
+        let mut elements: Vec<&'a Element> = Vec::new();
+        for idx in chunk_idxs {
+            let chunk = &self.chunks[idx];
+            let mut found: Vec<&'a Element> = chunk
+                .query(query.clone())
+                .filter(|element| match after {
+                    Some(after) => (chunk.chunk_key(), element.item_key().as_ref()) > after,
+                    None => true,
+                })
+                .collect();
+            found.sort_by(|a, b| a.item_key().cmp(&b.item_key()));
+            elements.extend(found.into_iter().take(limit + 1 - elements.len()));
+
+            if elements.len() > limit {
+                break;
+            }
+        }
+
+        let next = if elements.len() > limit {
+            elements.truncate(limit);
+            elements.last().map(|element| {
+                PageCursor::new(Id(
+                    element.chunk_key().into_owned(),
+                    element.item_key().into_owned(),
+                ))
+            })
+        } else {
+            None
+        };
+
+        Page::new(elements, next)
+    }
+
     /// Iterate over a Query and modify each element via a callback.
     /// The callback provides retriever's Editor API, which in turn provides
     /// a mutable or immutable reference to the underlying element.
@@ -559,6 +982,17 @@ where
     /// reference to a data element to make sure you really want to mutate it before obtaining a
     /// mutable reference.
     ///
+    /// If the callback changes the chunk key or item key of an element, that element is moved to
+    /// its new chunk, or re-keyed within its chunk, once every element has been visited. Several
+    /// elements may trade ids with each other in a single call.
+    ///
+    /// # Panic
+    ///
+    /// This method panics if the callback moves an element onto the id of another element.
+    /// Before panicking, it moves every other element whose keys were changed, and drops only
+    /// the colliding elements, so that this `Storage` remains consistent. See
+    /// `Storage::try_modify()` for a version that doesn't panic.
+    ///
     /// # Type Parameters
     ///
     /// * `Q`: Any `Query`. There are a variety of useful `Queries`:
@@ -605,6 +1039,17 @@ where
     /// assert_eq!(Some(27000), storage.get(&ID.item(4)).map(|x| x.balance));
     /// assert_eq!(Some(-38),   storage.get(&ID.item(5)).map(|x| x.balance));
     ///
+    /// // Merge accounts 1 and 2 into a new account 6.
+    /// let balance_2 = storage.get(&ID.item(2)).map(|x| x.balance).unwrap();
+    /// storage.remove(ID.item(2), std::mem::drop);
+    /// storage.modify(ID.item(1), |mut account| {
+    ///   account.get_mut().id = 6;
+    ///   account.get_mut().balance += balance_2;
+    /// });
+    ///
+    /// assert_eq!(None,        storage.get(&ID.item(1)).map(|x| x.balance));
+    /// assert_eq!(Some(38),    storage.get(&ID.item(6)).map(|x| x.balance));
+    ///
     /// # storage.validate();
     /// ```
     pub fn modify<Q, F>(&mut self, query: Q, f: F)
@@ -614,105 +1059,623 @@ where
     {
         self.clean();
 
+        let mut moved = Vec::new();
+
         for idx in query.chunk_idxs(self).into_idx_iter().flatten() {
-            self.chunks[idx].modify(&query, &f);
+            self.journal_chunk(idx);
+            moved.extend(
+                self.chunks[idx]
+                    .modify(&query, &f, |_| ())
+                    .into_iter()
+                    .map(|(item_idx, item_key, ())| (idx, item_idx, item_key, ())),
+            );
         }
+
+        self.relocate_or_panic(moved);
+        self.write_journal();
     }
 
-    /// Remove all of the specified elements from this storage.
-    ///
-    /// # Type Parameters
+    /// As `Storage::modify()`, but reports an `Error::IdCollision` instead of panicking if the
+    /// callback moves an element onto the id of another element. In that case, no element
+    /// is moved: every element whose keys were changed is restored to the state it was in
+    /// before the callback. All other modifications are kept.
     ///
-    /// * `Q`: Any `Query`. There are a variety of useful `Queries`:
-    ///   * `Everything`
-    ///   * `Chunks(...)`, an explicit list of chunks
-    ///   * `Id`, the Id of a specific element
-    ///   * Most other `Queries` can be constructed by chaining the methods of the `Query` trait.
+    /// Each matching element is cloned before the callback runs, so that it can be restored.
     ///
     /// ```
     /// use retriever::prelude::*;
-    /// use retriever::queries::everything::Everything;
-    /// use std::borrow::Cow;
-    ///
-    /// // In this example, we will store log entries, some of which might contain sensitive
-    /// // information that we need to delete later.
-    /// struct LogEntry {
-    ///   stardate: u128,
-    ///   msg: String,
-    /// }
-    ///
-    /// impl Record<u128, u128> for LogEntry {
-    ///   fn chunk_key(&self) -> Cow<u128> {
-    ///     Cow::Owned(self.stardate / 1000)
-    ///   }
-    ///
-    ///   fn item_key(&self) -> Cow<u128> {
-    ///     Cow::Borrowed(&self.stardate)
-    ///   }
-    /// }
-    ///
-    /// let mut storage : Storage<u128, u128, LogEntry> = Storage::new();
-    ///
-    /// storage.add(LogEntry {
-    ///   stardate: 109301,
-    ///   msg: String::from("Departed from Starbase Alpha"),
-    /// });
+    /// use retriever::Error;
     ///
-    /// storage.add(LogEntry {
-    ///   stardate: 109302,
-    ///   msg: String::from("Purchased illegal cloaking device from aliens"),
-    /// });
-    ///
-    /// storage.add(LogEntry {
-    ///   stardate: 109303,
-    ///   msg: String::from("Asked doctor to check cat for space fleas"),
-    /// });
+    /// let mut storage : Storage<u64, u64, (u64, u64, i64)> = Storage::new();
     ///
-    /// // Use the 'remove' operation to search for any embarassing log entries
-    /// // and drop them.
-    /// storage.remove(&Everything.filter(|log_entry: &LogEntry| {
-    ///   log_entry.msg.contains("illegal")
-    /// }), std::mem::drop);
+    /// storage.add((1, 1, 10));
+    /// storage.add((1, 2, 20));
     ///
-    /// assert!(
-    ///   storage
-    ///     .get(&ID.chunk(109).item(109302))
-    ///     .is_none());
+    /// assert_eq!(Ok(()), storage.try_modify(Everything, |mut editor| {
+    ///   editor.get_mut().2 += 1;
+    /// }));
     ///
     /// assert_eq!(
-    ///   storage.iter().count(),
-    ///   2);
+    ///   Err(Error::IdCollision { before: ID.chunk(1).item(2), after: ID.chunk(1).item(1) }),
+    ///   storage.try_modify(ID.chunk(1).item(2), |mut editor| {
+    ///     editor.get_mut().1 = 1;
+    ///   })
+    /// );
+    ///
+    /// assert_eq!(Some(&(1, 1, 11)), storage.get(&ID.chunk(1).item(1)));
+    /// assert_eq!(Some(&(1, 2, 21)), storage.get(&ID.chunk(1).item(2)));
     ///
     /// # storage.validate();
     /// ```
-    pub fn remove<Q, F>(&mut self, query: Q, f: F)
+    pub fn try_modify<Q, F>(
+        &mut self,
+        query: Q,
+        f: F,
+    ) -> Result<(), Error<ChunkKey::Owned, ItemKey::Owned>>
     where
-        F: Fn(Element),
         Q: Query<ChunkKey, ItemKey, Element>,
+        F: Fn(Editor<ChunkKey, ItemKey, Element>),
+        Element: Clone,
     {
+        self.clean();
+
+        let mut moved = Vec::new();
+
         for idx in query.chunk_idxs(self).into_idx_iter().flatten() {
-            self.dirty(idx);
-            self.chunks[idx].remove(&query, &f);
+            self.journal_chunk(idx);
+            moved.extend(
+                self.chunks[idx]
+                    .modify(&query, &f, Element::clone)
+                    .into_iter()
+                    .map(|(item_idx, item_key, backup)| (idx, item_idx, item_key, backup)),
+            );
         }
 
-        self.clean();
-    }
+        let result = self.relocate(moved).map_err(|(moved, e)| {
+            for (idx, item_idx, _, backup) in moved {
+                *self.chunks[idx].get_idx_mut(item_idx) = backup;
+            }
 
-    /// List all chunks
-    pub fn chunk_keys(&self) -> impl IntoIterator<Item = &ChunkKey> {
-        self.chunks.iter().map(|chunk| chunk.chunk_key())
-    }
+            e
+        });
 
-    /// Drop an entire chunk and return all associated elements
-    pub fn remove_chunk(&mut self, chunk_key: &ChunkKey) -> Option<Vec<Element>> {
-        self.clean();
-        let idx = self.index.remove(chunk_key)?;
-        let chunk = self.chunks.swap_remove(idx);
-        Some(chunk.into())
+        self.write_journal();
+
+        result
     }
 
-    /// Panic if this storage is malformed or broken in any way.
-    /// This is a slow operation and you shouldn't use it unless you suspect a problem.
+    /// Move elements whose keys were changed in place to the chunks and item keys where they
+    /// now belong. Each entry is the index of the chunk and element, the item key the element
+    /// is indexed by, and a backup.
+    ///
+    /// If any element would collide with another element, nothing is moved, and the entries
+    /// are handed back along with the error.
+    #[allow(clippy::type_complexity)]
+    fn relocate<T>(
+        &mut self,
+        moved: Vec<(usize, usize, ItemKey::Owned, T)>,
+    ) -> Result<
+        (),
+        (
+            Vec<(usize, usize, ItemKey::Owned, T)>,
+            Error<ChunkKey::Owned, ItemKey::Owned>,
+        ),
+    > {
+        if let Some((_, e)) = self.collisions(&moved).into_iter().next() {
+            return Err((moved, e));
+        }
+
+        for (element, _) in self.remove_moved(moved) {
+            self.chunk(element.chunk_key().borrow(), false).add(element);
+        }
+
+        self.clean();
+
+        Ok(())
+    }
+
+    /// As `Storage::relocate()`, but panics if any element would collide with another element.
+    /// Without a backup there's no way to restore the colliding elements, so before panicking
+    /// they are dropped, and every other element is moved as usual, leaving this `Storage` and
+    /// its dependents consistent.
+    fn relocate_or_panic(&mut self, moved: Vec<(usize, usize, ItemKey::Owned, ())>) {
+        let collisions = self.collisions(&moved);
+        let colliding: HashSet<usize> = collisions.iter().map(|(i, _)| *i).collect();
+        let moved = moved
+            .into_iter()
+            .enumerate()
+            .map(|(i, (idx, item_idx, item_key, ()))| {
+                (idx, item_idx, item_key, colliding.contains(&i))
+            })
+            .collect();
+
+        for (element, collides) in self.remove_moved(moved) {
+            if !collides {
+                self.chunk(element.chunk_key().borrow(), false).add(element);
+            }
+        }
+
+        self.clean();
+
+        if let Some((_, e)) = collisions.into_iter().next() {
+            self.write_journal();
+            panic!("{}", e);
+        }
+    }
+
+    /// Find every element, as listed for `Storage::relocate()`, that would collide with another
+    /// element once moved: either its new id belongs to an element that isn't moving, or some
+    /// other moved element has the same new id. Returns the position of each one in the list,
+    /// in order, with the error describing it.
+    #[allow(clippy::type_complexity)]
+    fn collisions<T>(
+        &self,
+        moved: &[(usize, usize, ItemKey::Owned, T)],
+    ) -> Vec<(usize, Error<ChunkKey::Owned, ItemKey::Owned>)> {
+        let old_ids: HashSet<Id<ChunkKey::Owned, ItemKey::Owned>> = moved
+            .iter()
+            .map(|(idx, _, item_key, _)| {
+                Id::new(self.chunks[*idx].chunk_key().to_owned(), item_key.clone())
+            })
+            .collect();
+        let new_ids: Vec<Id<ChunkKey::Owned, ItemKey::Owned>> = moved
+            .iter()
+            .map(|(idx, item_idx, _, _)| Id::cloned(self.chunks[*idx].get_idx(*item_idx)))
+            .collect();
+        let mut counts: HashMap<&Id<ChunkKey::Owned, ItemKey::Owned>, usize> =
+            HashMap::with_capacity(new_ids.len());
+
+        for new_id in new_ids.iter() {
+            *counts.entry(new_id).or_insert(0) += 1;
+        }
+
+        moved
+            .iter()
+            .zip(new_ids.iter())
+            .enumerate()
+            .filter(|(_, (_, new_id))| {
+                (self.get(*new_id).is_some() && !old_ids.contains(*new_id)) || counts[new_id] > 1
+            })
+            .map(|(i, ((idx, _, item_key, _), new_id))| {
+                let e = Error::IdCollision {
+                    before: Id::new(self.chunks[*idx].chunk_key().to_owned(), item_key.clone()),
+                    after: new_id.clone(),
+                };
+
+                (i, e)
+            })
+            .collect()
+    }
+
+    /// Remove elements whose keys were changed in place, as listed for `Storage::relocate()`,
+    /// and return them along with their backups.
+    fn remove_moved<T>(
+        &mut self,
+        mut moved: Vec<(usize, usize, ItemKey::Owned, T)>,
+    ) -> Vec<(Element, T)> {
+        // Remove from the back of each chunk first, so that the remaining indices stay valid.
+        moved.sort_unstable_by_key(|(idx, item_idx, _, _)| Reverse((*idx, *item_idx)));
+
+        let elements = moved
+            .into_iter()
+            .map(|(idx, item_idx, item_key, backup)| {
+                self.dirty(idx);
+                (
+                    self.chunks[idx].remove_moved(item_idx, item_key.borrow()),
+                    backup,
+                )
+            })
+            .collect();
+
+        self.clean();
+
+        elements
+    }
+
+    /// Iterate over a Query, producing a mutable reference to each element. This is an external
+    /// iterator, so unlike `Storage::modify()`, it works with iterator adapters, `break`
+    /// and `?`.
+    ///
+    /// Every element produced by this iterator is treated as modified, whether or not you
+    /// actually change it, so narrow the query down as far as you can first. If this `Storage`
+    /// is journaled, the changes are recorded at the next operation on this `Storage`, or at
+    /// the next call to `Storage::sync_journal()`.
+    ///
+    /// # Panic
+    ///
+    /// Each `ElementMut` panics when it is dropped if the chunk key or item key of its element
+    /// has changed.
+    ///
+    /// ```
+    /// use retriever::prelude::*;
+    ///
+    /// // Note that (A,B,C) implements Record<A,B>.
+    /// let mut storage : Storage<u8,u8,(u8,u8,i64)> = Storage::new();
+    ///
+    /// storage.add((0,1,10));
+    /// storage.add((0,2,20));
+    /// storage.add((0,3,30));
+    /// storage.add((1,1,40));
+    ///
+    /// // Hand out bonuses in order until we run out of money.
+    /// let mut budget = 5;
+    /// let mut winners = storage.query_mut(Chunks([0])).collect::<Vec<_>>();
+    /// winners.sort_by_key(|x| x.get().1);
+    ///
+    /// for (mut winner, bonus) in winners.into_iter().zip(vec![3,2,1]) {
+    ///   if bonus > budget {
+    ///     break;
+    ///   }
+    ///
+    ///   winner.get_mut().2 += bonus;
+    ///   budget -= bonus;
+    /// }
+    ///
+    /// assert_eq!(Some(&(0,1,13)), storage.get(&ID.chunk(0).item(1)));
+    /// assert_eq!(Some(&(0,2,22)), storage.get(&ID.chunk(0).item(2)));
+    /// assert_eq!(Some(&(0,3,30)), storage.get(&ID.chunk(0).item(3)));
+    /// assert_eq!(Some(&(1,1,40)), storage.get(&ID.chunk(1).item(1)));
+    ///
+    /// // The ? operator works too.
+    /// fn withdraw(storage: &mut Storage<u8,u8,(u8,u8,i64)>, amount: i64) -> Result<(), String> {
+    ///   for mut account in storage.query_mut(Everything) {
+    ///     if account.get().2 < amount {
+    ///       Err(format!("insufficient funds in {:?}", account.id()))?;
+    ///     }
+    ///
+    ///     account.get_mut().2 -= amount;
+    ///   }
+    ///
+    ///   Ok(())
+    /// }
+    ///
+    /// assert!(withdraw(&mut storage, 20).is_err());
+    ///
+    /// # storage.validate();
+    /// ```
+    pub fn query_mut<'a, Q>(
+        &'a mut self,
+        query: Q,
+    ) -> impl Iterator<Item = ElementMut<'a, ChunkKey, ItemKey, Element>>
+    where
+        Q: Query<ChunkKey, ItemKey, Element> + 'a,
+    {
+        self.clean();
+
+        let chunk_idxs: Vec<usize> = query.chunk_idxs(self).into_idx_iter().flatten().collect();
+        let mut selected = vec![false; self.chunks.len()];
+
+        for idx in chunk_idxs.iter() {
+            selected[*idx] = true;
+            self.journal_chunk(*idx);
+        }
+
+        self.chunks
+            .touch_many(&chunk_idxs)
+            .iter_mut()
+            .zip(selected)
+            .filter(|(_, selected)| *selected)
+            .flat_map(move |(chunk, _)| {
+                let (chunk_key, elements) = chunk.query_mut(&query);
+                elements.map(move |element| ElementMut::new(chunk_key, element))
+            })
+    }
+
+    /// Remove all of the specified elements from this storage.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `Q`: Any `Query`. There are a variety of useful `Queries`:
+    ///   * `Everything`
+    ///   * `Chunks(...)`, an explicit list of chunks
+    ///   * `Id`, the Id of a specific element
+    ///   * Most other `Queries` can be constructed by chaining the methods of the `Query` trait.
+    ///
+    /// ```
+    /// use retriever::prelude::*;
+    /// use retriever::queries::everything::Everything;
+    /// use std::borrow::Cow;
+    ///
+    /// // In this example, we will store log entries, some of which might contain sensitive
+    /// // information that we need to delete later.
+    /// struct LogEntry {
+    ///   stardate: u128,
+    ///   msg: String,
+    /// }
+    ///
+    /// impl Record<u128, u128> for LogEntry {
+    ///   fn chunk_key(&self) -> Cow<u128> {
+    ///     Cow::Owned(self.stardate / 1000)
+    ///   }
+    ///
+    ///   fn item_key(&self) -> Cow<u128> {
+    ///     Cow::Borrowed(&self.stardate)
+    ///   }
+    /// }
+    ///
+    /// let mut storage : Storage<u128, u128, LogEntry> = Storage::new();
+    ///
+    /// storage.add(LogEntry {
+    ///   stardate: 109301,
+    ///   msg: String::from("Departed from Starbase Alpha"),
+    /// });
+    ///
+    /// storage.add(LogEntry {
+    ///   stardate: 109302,
+    ///   msg: String::from("Purchased illegal cloaking device from aliens"),
+    /// });
+    ///
+    /// storage.add(LogEntry {
+    ///   stardate: 109303,
+    ///   msg: String::from("Asked doctor to check cat for space fleas"),
+    /// });
+    ///
+    /// // Use the 'remove' operation to search for any embarassing log entries
+    /// // and drop them.
+    /// storage.remove(&Everything.filter(|log_entry: &LogEntry| {
+    ///   log_entry.msg.contains("illegal")
+    /// }), std::mem::drop);
+    ///
+    /// assert!(
+    ///   storage
+    ///     .get(&ID.chunk(109).item(109302))
+    ///     .is_none());
+    ///
+    /// assert_eq!(
+    ///   storage.iter().count(),
+    ///   2);
+    ///
+    /// # storage.validate();
+    /// ```
+    pub fn remove<Q, F>(&mut self, query: Q, f: F)
+    where
+        F: Fn(Element),
+        Q: Query<ChunkKey, ItemKey, Element>,
+    {
+        for idx in query.chunk_idxs(self).into_idx_iter().flatten() {
+            self.dirty(idx);
+            self.journal_chunk(idx);
+            self.chunks[idx].remove(&query, &f);
+        }
+
+        self.clean();
+    }
+
+    /// Make several changes to this storage all at once, or not at all.
+    ///
+    /// The callback stages changes in a `Transaction`. If the callback returns `Ok`, every
+    /// change is applied to this `Storage`. If the callback returns `Err` or panics, this `Storage`
+    /// is left exactly as it was. `SecondaryIndices` and `Reductions` never observe a partly
+    /// applied `Transaction`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use retriever::prelude::*;
+    ///
+    /// // Note that (A,B,C) implements Record<A,B>.
+    /// let mut storage : Storage<(), &'static str, (&'static str, i64)> = Storage::new();
+    ///
+    /// storage.add(("alice", 100));
+    /// storage.add(("bob", 20));
+    ///
+    /// let transfer = |storage: &mut Storage<(), &'static str, (&'static str, i64)>, amount: i64| {
+    ///   storage.transaction(|tx| {
+    ///     tx.modify(ID.item("alice"), |account| account.1 -= amount);
+    ///     tx.modify(ID.item("bob"), |account| account.1 += amount);
+    ///
+    ///     if tx.get(&ID.item("alice")).unwrap().1 < 0 {
+    ///       return Err("insufficient funds");
+    ///     }
+    ///
+    ///     Ok(())
+    ///   })
+    /// };
+    ///
+    /// assert_eq!(Ok(()), transfer(&mut storage, 60));
+    /// assert_eq!(Err("insufficient funds"), transfer(&mut storage, 60));
+    ///
+    /// assert_eq!(Some(&("alice", 40)), storage.get(&ID.item("alice")));
+    /// assert_eq!(Some(&("bob", 80)), storage.get(&ID.item("bob")));
+    ///
+    /// # storage.validate();
+    /// ```
+    pub fn transaction<F, T, E>(&mut self, f: F) -> Result<T, E>
+    where
+        F: FnOnce(&mut Transaction<ChunkKey, ItemKey, Element>) -> Result<T, E>,
+        Element: Clone,
+    {
+        let mut transaction = Transaction::new(self);
+        let result = f(&mut transaction)?;
+        let changes = transaction.into_changes();
+
+        for (chunk_key, items) in changes {
+            for (item_key, change) in items {
+                match change {
+                    Some(element) => {
+                        self.put(element);
+                    }
+                    None => {
+                        self.remove_item(chunk_key.borrow(), item_key.borrow());
+                    }
+                }
+            }
+        }
+
+        self.clean();
+
+        Ok(result)
+    }
+
+    /// Begin or stop maintaining an ordered index of chunk keys. With this index, a `Chunks`
+    /// query over a range of chunk keys, such as `Chunks(10..20)` or `Chunks("a".."m")`, looks
+    /// up exactly the chunks in that range. Without it, the query scans every chunk key.
+    ///
+    /// ```
+    /// use retriever::prelude::*;
+    ///
+    /// let mut storage : Storage<String, u64, (String, u64, u64)> = Storage::new();
+    /// storage.set_sorted_chunk_keys(true);
+    ///
+    /// for name in vec!["apple", "kiwi", "mango", "zucchini"] {
+    ///   storage.add((String::from(name), 0, 0));
+    /// }
+    ///
+    /// let mut found : Vec<&str> = storage
+    ///   .query(Chunks(String::from("b")..String::from("n")))
+    ///   .map(|x| x.0.as_str())
+    ///   .collect();
+    /// found.sort();
+    /// assert_eq!(vec!["kiwi", "mango"], found);
+    ///
+    /// # storage.validate();
+    /// ```
+    pub fn set_sorted_chunk_keys(&mut self, sorted: bool) -> &mut Self {
+        match (sorted, self.sorted_index.is_some()) {
+            (true, false) => {
+                self.sorted_index = Some(
+                    self.chunks
+                        .iter()
+                        .enumerate()
+                        .map(|(idx, chunk)| (chunk.chunk_key().to_owned(), idx))
+                        .collect(),
+                );
+            }
+            (false, true) => self.sorted_index = None,
+            _ => {}
+        }
+
+        self
+    }
+
+    /// List all chunks
+    pub fn chunk_keys(&self) -> impl IntoIterator<Item = &ChunkKey> {
+        self.chunks.iter().map(|chunk| chunk.chunk_key())
+    }
+
+    /// Drop an entire chunk and return all associated elements
+    pub fn remove_chunk(&mut self, chunk_key: &ChunkKey) -> Option<Vec<Element>> {
+        self.clean();
+        let idx = self.internal_idx_of(chunk_key)?;
+        let chunk = self.swap_remove_chunk(idx);
+
+        #[cfg(feature = "serde")]
+        if let Some(journal) = self.journal.as_mut() {
+            journal.remove_chunk(chunk_key);
+        }
+
+        Some(chunk.into())
+    }
+
+    /// Load a chunk from a `ChunkStore`. Any elements of that chunk that are already in
+    /// this `Storage` are replaced by the elements from the `ChunkStore`.
+    ///
+    /// Returns `Ok(false)`, and leaves this `Storage` untouched, if the `ChunkStore` doesn't
+    /// contain the chunk.
+    ///
+    /// Reports a `LoadChunkError::Element`, and leaves this `Storage` untouched, if any loaded
+    /// `Element` does not have the requested chunk key, or if any two loaded `Elements` have the
+    /// same item key.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use retriever::prelude::*;
+    /// use std::collections::BTreeMap;
+    ///
+    /// let mut storage : Storage<u64, u64, (u64, u64, &'static str)> = Storage::new();
+    /// let mut store = BTreeMap::new();
+    ///
+    /// storage.add((1, 1, "apples"));
+    /// storage.add((1, 2, "oranges"));
+    /// storage.add((2, 1, "bananas"));
+    ///
+    /// // Unloading writes the chunk to the store and drops it from memory.
+    /// storage.unload_chunk(&mut store, &1).unwrap();
+    /// assert_eq!(1, storage.iter().count());
+    ///
+    /// // Loading brings it back.
+    /// assert!(storage.load_chunk(&store, &1).unwrap());
+    /// assert_eq!(Some(&(1, 2, "oranges")), storage.get(&ID.chunk(1).item(2)));
+    ///
+    /// // There is no chunk 3 anywhere.
+    /// assert!(!storage.load_chunk(&store, &3).unwrap());
+    ///
+    /// # storage.validate();
+    /// ```
+    pub fn load_chunk<S>(
+        &mut self,
+        store: &S,
+        chunk_key: &ChunkKey,
+    ) -> LoadChunkResult<ChunkKey::Owned, ItemKey::Owned, S::Error>
+    where
+        S: ChunkStore<ChunkKey, Element>,
+    {
+        let elements = match store.load(chunk_key).map_err(LoadChunkError::Store)? {
+            Some(elements) => elements,
+            None => return Ok(false),
+        };
+
+        let mut item_keys: HashSet<ItemKey::Owned> = HashSet::with_capacity(elements.len());
+
+        for element in elements.iter() {
+            if element.chunk_key().as_ref() != chunk_key {
+                return Err(LoadChunkError::Element(Error::WrongId {
+                    expected: Id::new(chunk_key.to_owned(), element.item_key().into_owned()),
+                    found: Id::cloned(element),
+                }));
+            }
+
+            if !item_keys.insert(element.item_key().into_owned()) {
+                return Err(LoadChunkError::Element(Error::DuplicateId(Id::cloned(
+                    element,
+                ))));
+            }
+        }
+
+        self.remove_chunk(chunk_key);
+
+        if !elements.is_empty() {
+            let chunk = self.chunk(chunk_key, false);
+
+            for element in elements {
+                chunk.add(element);
+            }
+        }
+
+        self.write_journal();
+
+        Ok(true)
+    }
+
+    /// Save a chunk to a `ChunkStore`, leaving it in memory. If this `Storage` has no elements
+    /// in that chunk, then the chunk is deleted from the `ChunkStore`.
+    pub fn flush_chunk<S>(&self, store: &mut S, chunk_key: &ChunkKey) -> Result<(), S::Error>
+    where
+        S: ChunkStore<ChunkKey, Element>,
+    {
+        match self.internal_idx_of(chunk_key) {
+            Some(idx) if !self.chunks[idx].is_empty() => {
+                store.save(chunk_key, self.chunks[idx].raw())
+            }
+            _ => store.delete(chunk_key),
+        }
+    }
+
+    /// Save a chunk to a `ChunkStore` and then drop it from memory. If saving fails, the chunk
+    /// remains in memory.
+    pub fn unload_chunk<S>(&mut self, store: &mut S, chunk_key: &ChunkKey) -> Result<(), S::Error>
+    where
+        S: ChunkStore<ChunkKey, Element>,
+    {
+        self.clean();
+        self.flush_chunk(store, chunk_key)?;
+        self.remove_chunk(chunk_key);
+        Ok(())
+    }
+
+    /// Panic if this storage is malformed or broken in any way.
+    /// This is a slow operation and you shouldn't use it unless you suspect a problem.
     pub fn validate(&mut self) {
         self.clean();
 
@@ -733,6 +1696,21 @@ where
             assert_ne!(self.chunks[*idx].len(), 0, "empty chunk");
         }
 
+        if let Some(sorted_index) = self.sorted_index.as_ref() {
+            assert_eq!(
+                sorted_index.len(),
+                self.chunks.len(),
+                "sorted chunk key index broken"
+            );
+            for (chunk_key, idx) in sorted_index.iter() {
+                assert_eq!(
+                    self.index.get(chunk_key.borrow()),
+                    Some(idx),
+                    "sorted chunk key index broken"
+                );
+            }
+        }
+
         for chunk in self.chunks.iter() {
             chunk.validate();
         }
@@ -746,13 +1724,41 @@ where
         self.index.get(chunk_key).cloned()
     }
 
+    /// The internal indices of every chunk with a chunk key within the given bounds. This is
+    /// an ordered lookup if this `Storage` has sorted chunk keys, otherwise a scan of every chunk.
+    pub(crate) fn internal_idxs_in_range(
+        &self,
+        start: Bound<&ChunkKey>,
+        end: Bound<&ChunkKey>,
+    ) -> Bitset {
+        let (start, end) = match bounds::nonempty(start, end) {
+            Some(bounds) => bounds,
+            None => return Bitset::default(),
+        };
+
+        match self.sorted_index.as_ref() {
+            Some(sorted_index) => sorted_index
+                .range::<ChunkKey, _>((start, end))
+                .map(|(_, idx)| *idx)
+                .collect(),
+            None => self
+                .chunks
+                .iter()
+                .enumerate()
+                .filter(|(_, chunk)| bounds::contains(start, end, chunk.chunk_key()))
+                .map(|(idx, _)| idx)
+                .collect(),
+        }
+    }
+
     pub(crate) fn internal_rvec(&self) -> &RVec<ChunkStorage<ChunkKey, ItemKey, Element>> {
         &self.chunks
     }
 
     /// This method provides garbage collection services for the caller. Assuming that the
     /// `data` parameter is a HashMap that represents some data about chunks in this `Storage`,
-    /// this method deletes all of the entries in that `HashMap` that no longer exist this `Storage`.
+    /// this method deletes all of the entries in that `HashMap` that no longer exist this `Storage`,
+    /// and returns them.
     ///
     /// The `chunk_list` parameter is an `RVec` containing all chunk keys. It is created for the
     /// purpose of being managed by this method and is managed entirely and only by this method.
@@ -760,7 +1766,7 @@ where
         &self,
         chunk_list: &mut RVec<Option<ChunkKey::Owned>>,
         data: &mut HashMap<ChunkKey::Owned, T, crate::internal::hasher::HasherImpl>,
-    ) {
+    ) -> Vec<(ChunkKey::Owned, T)> {
         let mut removed: HashSet<ChunkKey::Owned, _> =
             HashSet::with_hasher(crate::internal::hasher::HasherImpl::default());
         let mut added: HashSet<ChunkKey::Owned, _> =
@@ -785,8 +1791,162 @@ where
             }
         });
 
-        for chunk_key in removed.difference(&added) {
-            data.remove(chunk_key.borrow());
+        removed
+            .difference(&added)
+            .filter_map(|chunk_key| data.remove_entry(chunk_key.borrow()))
+            .collect()
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<ChunkKey, ItemKey, Element> Storage<ChunkKey, ItemKey, Element>
+where
+    ChunkKey: BorrowedKey + Sync + ?Sized,
+    ChunkKey::Owned: ValidKey + Send + Sync,
+    ItemKey: BorrowedKey + Sync + ?Sized,
+    ItemKey::Owned: ValidKey + Send + Sync,
+    Element: Record<ChunkKey, ItemKey> + Send + Sync,
+{
+    /// Iterate over every element in storage, in parallel. Chunks are distributed among
+    /// threads, so storage with only a few chunks won't benefit much.
+    ///
+    /// Requires the `rayon` feature.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use retriever::prelude::*;
+    /// use rayon::prelude::*;
+    ///
+    /// // Note that (A,B,C) implements Record<A,B>.
+    /// let mut storage : Storage<usize,usize,(usize,usize,i64)> = Storage::new();
+    ///
+    /// for i in 0..1000 {
+    ///   storage.add((i % 10, i, i as i64));
+    /// }
+    ///
+    /// assert_eq!(499500, storage.par_iter().map(|x| x.2).sum::<i64>());
+    /// ```
+    pub fn par_iter(&self) -> impl ParallelIterator<Item = &Element> {
+        let chunks: &[ChunkStorage<ChunkKey, ItemKey, Element>] = &self.chunks;
+
+        chunks.par_iter().flat_map_iter(|chunk| chunk.iter())
+    }
+
+    /// Iterate over elements according to some Query, in parallel. The order of the elements
+    /// is the same as for `Storage::query()`.
+    ///
+    /// Requires the `rayon` feature.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use retriever::prelude::*;
+    /// use rayon::prelude::*;
+    ///
+    /// // Note that (A,B,C) implements Record<A,B>.
+    /// let mut storage : Storage<usize,usize,(usize,usize,i64)> = Storage::new();
+    ///
+    /// for i in 0..1000 {
+    ///   storage.add((i % 10, i, i as i64));
+    /// }
+    ///
+    /// let odd_chunks = Chunks(vec![1,3,5,7,9]);
+    /// let sequential : Vec<_> = storage.query(&odd_chunks).collect();
+    /// let parallel : Vec<_> = storage.par_query(&odd_chunks).collect();
+    /// assert_eq!(sequential, parallel);
+    /// ```
+    pub fn par_query<'a, Q>(&'a self, query: Q) -> impl ParallelIterator<Item = &'a Element>
+    where
+        Q: Query<ChunkKey, ItemKey, Element> + Clone + Send + Sync + 'a,
+    {
+        let chunk_idxs: Vec<usize> = query.chunk_idxs(self).into_idx_iter().flatten().collect();
+
+        chunk_idxs
+            .into_par_iter()
+            .flat_map_iter(move |idx| self.chunks[idx].query(query.clone()))
+    }
+
+    /// Modify the elements matching some query, in parallel. Each chunk is modified by a single
+    /// thread, so the callback is never called concurrently for two elements of the same chunk.
+    /// Elements whose keys change are moved, and collisions panic, just as for
+    /// `Storage::modify()`.
+    ///
+    /// Requires the `rayon` feature.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use retriever::prelude::*;
+    ///
+    /// // Note that (A,B,C) implements Record<A,B>.
+    /// let mut storage : Storage<usize,usize,(usize,usize,i64)> = Storage::new();
+    ///
+    /// for i in 0..1000 {
+    ///   storage.add((i % 10, i, i as i64));
+    /// }
+    ///
+    /// storage.par_modify(Everything.filter(|x: &(usize,usize,i64)| x.2 % 2 == 1), |mut editor| {
+    ///   editor.get_mut().2 *= -1;
+    /// });
+    ///
+    /// assert_eq!(-500, storage.iter().map(|x| x.2).sum::<i64>());
+    /// # storage.validate();
+    /// ```
+    pub fn par_modify<Q, F>(&mut self, query: Q, f: F)
+    where
+        Q: Query<ChunkKey, ItemKey, Element> + Sync,
+        F: Fn(Editor<ChunkKey, ItemKey, Element>) + Send + Sync,
+    {
+        self.clean();
+
+        let chunk_idxs: Vec<usize> = query.chunk_idxs(self).into_idx_iter().flatten().collect();
+        let mut selected = vec![false; self.chunks.len()];
+
+        for idx in chunk_idxs.iter() {
+            selected[*idx] = true;
+            self.journal_chunk(*idx);
+        }
+
+        let moved: Vec<_> = self
+            .chunks
+            .touch_many(&chunk_idxs)
+            .par_iter_mut()
+            .zip(selected.par_iter())
+            .enumerate()
+            .filter(|(_, (_, selected))| **selected)
+            .flat_map_iter(|(idx, (chunk, _))| {
+                chunk
+                    .modify(&query, &f, |_| ())
+                    .into_iter()
+                    .map(move |(item_idx, item_key, ())| (idx, item_idx, item_key, ()))
+            })
+            .collect();
+
+        self.relocate_or_panic(moved);
+        self.write_journal();
+    }
+}
+
+impl<ChunkKey, ItemKey, Element> Clone for Storage<ChunkKey, ItemKey, Element>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+    ItemKey: BorrowedKey + ?Sized,
+    ItemKey::Owned: ValidKey,
+    Element: Clone,
+{
+    /// Clone this `Storage`. The clone is never journaled, even if this `Storage` is.
+    fn clone(&self) -> Self {
+        Storage {
+            id: self.id,
+            chunks: self.chunks.clone(),
+            dirty: self.dirty.clone(),
+            index: self.index.clone(),
+            sorted_index: self.sorted_index.clone(),
+            journal_chunks: None,
+            #[cfg(feature = "serde")]
+            journal: None,
         }
     }
 }
@@ -837,3 +1997,191 @@ where
         self.chunks.shrink_with(&f);
     }
 }
+
+#[cfg(feature = "serde")]
+impl<ChunkKey, ItemKey, Element> Storage<ChunkKey, ItemKey, Element>
+where
+    ChunkKey: BorrowedKey + serde::Serialize + ?Sized,
+    ChunkKey::Owned: ValidKey + serde::de::DeserializeOwned,
+    ItemKey: BorrowedKey + serde::Serialize + ?Sized,
+    ItemKey::Owned: ValidKey + serde::de::DeserializeOwned,
+    Element: Record<ChunkKey, ItemKey> + serde::Serialize + serde::de::DeserializeOwned,
+{
+    /// Recover a `Storage` from a snapshot file and a journal file, replaying every record of
+    /// the journal on top of the snapshot. Either file may be missing, in which case it is
+    /// treated as empty. The returned `Storage` continues to append to the same journal.
+    ///
+    /// A record that was only partly written, because of a crash, is dropped from the end of
+    /// the journal. Any other malformed record is an error.
+    ///
+    /// See `Journal` for an example.
+    pub fn recover<P, Q>(snapshot: P, journal: Q) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let mut storage: Self = match fs::read(snapshot) {
+            Ok(data) => serde_json::from_slice(&data)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Storage::new(),
+            Err(e) => return Err(e),
+        };
+
+        let journal = journal.as_ref();
+        let data = match fs::read(journal) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+
+        let mut end = 0;
+
+        for line in data.split_inclusive(|byte| *byte == b'\n') {
+            if line.last() != Some(&b'\n') {
+                break;
+            }
+
+            storage.replay(serde_json::from_slice(line)?);
+            end += line.len();
+        }
+
+        if end < data.len() {
+            fs::OpenOptions::new()
+                .write(true)
+                .open(journal)?
+                .set_len(end as u64)?;
+        }
+
+        storage.set_journal(Journal::open(journal)?);
+
+        Ok(storage)
+    }
+
+    fn replay(&mut self, op: JournalOp<ChunkKey::Owned, ItemKey::Owned, Element>) {
+        match op {
+            JournalOp::Put(element) => {
+                self.put(element);
+            }
+            JournalOp::Remove(chunk_key, item_key) => {
+                self.remove_item(chunk_key.borrow(), item_key.borrow());
+                self.clean();
+            }
+            JournalOp::RemoveChunk(chunk_key) => {
+                self.remove_chunk(chunk_key.borrow());
+            }
+        }
+    }
+
+    /// Record every future mutation of this `Storage` to the given `Journal`. Returns the
+    /// previous `Journal`, if there was one.
+    ///
+    /// The journal only records changes, so it should begin from a snapshot of this `Storage`.
+    /// `Storage::recover()` and `Storage::compact_journal()` take care of this for you.
+    pub fn set_journal(&mut self, journal: Journal) -> Option<Journal> {
+        let old_journal = self.take_journal();
+
+        for idx in 0..self.chunks.len() {
+            self.chunks[idx].set_journaled(true);
+        }
+
+        self.journal_chunks = Some(Vec::new());
+        self.journal = Some(JournalHandle::new(journal));
+
+        old_journal
+    }
+
+    /// Stop journaling this `Storage`, and return the `Journal`, if there was one.
+    pub fn take_journal(&mut self) -> Option<Journal> {
+        self.journal.as_ref()?;
+        self.write_journal();
+
+        for idx in 0..self.chunks.len() {
+            self.chunks[idx].set_journaled(false);
+        }
+
+        self.journal_chunks = None;
+        self.journal.take().map(JournalHandle::into_journal)
+    }
+
+    /// Write any outstanding records to the journal and flush it to durable storage.
+    ///
+    /// Mutations made through a reference obtained from an `Entry` or from
+    /// `Storage::query_mut()` are only recorded at the next operation on this `Storage`, or at
+    /// the next call to this method.
+    ///
+    /// If this method reports an error, then the journal may be missing records. Use
+    /// `Storage::compact_journal()` to start over with a fresh snapshot.
+    pub fn sync_journal(&mut self) -> io::Result<()> {
+        self.write_journal();
+
+        match self.journal.as_mut() {
+            Some(journal) => journal.journal_mut().sync(),
+            None => Ok(()),
+        }
+    }
+
+    /// Write a snapshot of this entire `Storage` to the given file, and then discard every
+    /// record in the journal. The snapshot is written to a temporary file first, so a crash
+    /// part way through leaves the previous snapshot and journal intact.
+    pub fn compact_journal<P: AsRef<Path>>(&mut self, snapshot: P) -> io::Result<()> {
+        self.clean();
+
+        let snapshot = snapshot.as_ref();
+        let temp_file = snapshot.with_extension("tmp");
+        let mut writer = io::BufWriter::new(fs::File::create(&temp_file)?);
+
+        serde_json::to_writer(&mut writer, self)?;
+        writer.into_inner()?.sync_all()?;
+        fs::rename(&temp_file, snapshot)?;
+
+        match self.journal.as_mut() {
+            Some(journal) => journal.journal_mut().truncate(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<ChunkKey, ItemKey, Element> serde::Serialize for Storage<ChunkKey, ItemKey, Element>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+    ItemKey: BorrowedKey + ?Sized,
+    ItemKey::Owned: ValidKey,
+    Element: Record<ChunkKey, ItemKey> + serde::Serialize,
+{
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.raw())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, ChunkKey, ItemKey, Element> serde::Deserialize<'de>
+    for Storage<ChunkKey, ItemKey, Element>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+    ItemKey: BorrowedKey + ?Sized,
+    ItemKey::Owned: ValidKey,
+    Element: Record<ChunkKey, ItemKey> + serde::Deserialize<'de>,
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        let chunks: Vec<Vec<Element>> = serde::Deserialize::deserialize(deserializer)?;
+        let mut storage = Storage::new();
+
+        for element in chunks.into_iter().flatten() {
+            if storage.get(&element).is_some() {
+                return Err(D::Error::custom(format!(
+                    "retriever: duplicate item key {:?} within chunk {:?}",
+                    element.item_key(),
+                    element.chunk_key()
+                )));
+            }
+
+            storage.add(element);
+        }
+
+        Ok(storage)
+    }
+}
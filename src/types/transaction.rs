@@ -0,0 +1,190 @@
+use super::id::Id;
+use super::storage::Storage;
+use crate::internal::hasher::HasherImpl;
+use crate::traits::query::Query;
+use crate::traits::record::Record;
+use crate::traits::valid_key::{BorrowedKey, ValidKey};
+use std::borrow::Borrow;
+use std::collections::HashMap;
+
+/// Changes that a `Transaction` will apply when it commits, grouped by chunk.
+/// `None` means that the element is removed.
+pub(crate) type Changes<ChunkKey, ItemKey, Element> =
+    HashMap<ChunkKey, HashMap<ItemKey, Option<Element>, HasherImpl>, HasherImpl>;
+
+/// A batch of changes to a `Storage`, applied all at once or not at all.
+/// See `Storage::transaction()`.
+///
+/// Until the transaction commits, the `Storage` is untouched. `Transaction::get()` sees the
+/// changes made so far by this transaction, but queries are always evaluated against the
+/// committed state of the `Storage`. So, for example, `Transaction::modify()` won't find an
+/// element that was added earlier in the same transaction.
+///
+/// # Type Parameters
+///
+/// * `ChunkKey`: The chunk key type of the `Storage`
+/// * `ItemKey`: The item key type of the `Storage`
+/// * `Element`: The element type of the `Storage`
+pub struct Transaction<'a, ChunkKey: ?Sized, ItemKey: ?Sized, Element>
+where
+    ChunkKey: BorrowedKey,
+    ChunkKey::Owned: ValidKey,
+    ItemKey: BorrowedKey,
+    ItemKey::Owned: ValidKey,
+{
+    storage: &'a Storage<ChunkKey, ItemKey, Element>,
+    changes: Changes<ChunkKey::Owned, ItemKey::Owned, Element>,
+}
+
+impl<'a, ChunkKey, ItemKey, Element> Transaction<'a, ChunkKey, ItemKey, Element>
+where
+    ChunkKey: BorrowedKey + ?Sized,
+    ChunkKey::Owned: ValidKey,
+    ItemKey: BorrowedKey + ?Sized,
+    ItemKey::Owned: ValidKey,
+    Element: Record<ChunkKey, ItemKey> + Clone,
+{
+    pub(crate) fn new(storage: &'a Storage<ChunkKey, ItemKey, Element>) -> Self {
+        Transaction {
+            storage,
+            changes: HashMap::with_hasher(HasherImpl::default()),
+        }
+    }
+
+    pub(crate) fn into_changes(self) -> Changes<ChunkKey::Owned, ItemKey::Owned, Element> {
+        self.changes
+    }
+
+    /// The committed state of the `Storage`, without any of the changes made by this
+    /// `Transaction`.
+    pub fn storage(&self) -> &'a Storage<ChunkKey, ItemKey, Element> {
+        self.storage
+    }
+
+    /// Get an element, including any changes made by this `Transaction`.
+    pub fn get<R>(&self, unique_id: &R) -> Option<&Element>
+    where
+        R: Record<ChunkKey, ItemKey>,
+    {
+        match self
+            .changes
+            .get(unique_id.chunk_key().borrow())
+            .and_then(|chunk| chunk.get(unique_id.item_key().borrow()))
+        {
+            Some(change) => change.as_ref(),
+            None => self.storage.get(unique_id),
+        }
+    }
+
+    fn change(
+        &mut self,
+        chunk_key: ChunkKey::Owned,
+        item_key: ItemKey::Owned,
+        change: Option<Element>,
+    ) {
+        self.changes
+            .entry(chunk_key)
+            .or_insert_with(|| HashMap::with_hasher(HasherImpl::default()))
+            .insert(item_key, change);
+    }
+
+    /// Add an element when this `Transaction` commits.
+    ///
+    /// # Panic
+    ///
+    /// This method panics if an element with the same id already exists, including elements
+    /// added by this `Transaction`. A panic rolls back the entire `Transaction`.
+    pub fn add(&mut self, element: Element) -> &mut Self {
+        assert!(
+            self.get(&element).is_none(),
+            "retriever: duplicate item key within chunk"
+        );
+
+        let chunk_key = element.chunk_key().into_owned();
+        let item_key = element.item_key().into_owned();
+        self.change(chunk_key, item_key, Some(element));
+
+        self
+    }
+
+    /// Modify every element that matches a query, as of the committed state of the `Storage`.
+    /// Each element is modified starting from its current state within this `Transaction`.
+    ///
+    /// The callback runs exactly once for each matching element, except for elements already
+    /// removed by this `Transaction`, which are skipped. If the callback changes the chunk key
+    /// or item key of an element, the element is moved to its new id when this `Transaction`
+    /// commits. Elements modified by the same call may move onto each other's old ids.
+    ///
+    /// # Panic
+    ///
+    /// This method panics if the callback moves an element onto an id that is already in use,
+    /// including by elements added or moved earlier in this `Transaction`. A panic rolls back the
+    /// entire `Transaction`.
+    pub fn modify<Q, F>(&mut self, query: Q, mut f: F) -> &mut Self
+    where
+        Q: Query<ChunkKey, ItemKey, Element>,
+        F: FnMut(&mut Element),
+    {
+        // Take every matching element before changing any of them, so that an element moved
+        // onto the id of a later match isn't found and modified a second time.
+        let mut modified: Vec<(ChunkKey::Owned, ItemKey::Owned, Element)> = self
+            .ids_of(&query)
+            .into_iter()
+            .filter_map(|(chunk_key, item_key)| {
+                let element = self
+                    .get(&Id::new(chunk_key.borrow(), item_key.borrow()))?
+                    .clone();
+                Some((chunk_key, item_key, element))
+            })
+            .collect();
+
+        for (_, _, element) in modified.iter_mut() {
+            f(element);
+        }
+
+        for (chunk_key, item_key, _) in modified.iter() {
+            self.change(chunk_key.clone(), item_key.clone(), None);
+        }
+
+        for (_, _, element) in modified {
+            assert!(
+                self.get(&element).is_none(),
+                "retriever: transaction moved an element onto an existing id"
+            );
+
+            let chunk_key = element.chunk_key().into_owned();
+            let item_key = element.item_key().into_owned();
+            self.change(chunk_key, item_key, Some(element));
+        }
+
+        self
+    }
+
+    /// Remove every element that matches a query, as of the committed state of the `Storage`,
+    /// when this `Transaction` commits.
+    pub fn remove<Q>(&mut self, query: Q) -> &mut Self
+    where
+        Q: Query<ChunkKey, ItemKey, Element>,
+    {
+        for (chunk_key, item_key) in self.ids_of(&query) {
+            self.change(chunk_key, item_key, None);
+        }
+
+        self
+    }
+
+    fn ids_of<Q>(&self, query: &Q) -> Vec<(ChunkKey::Owned, ItemKey::Owned)>
+    where
+        Q: Query<ChunkKey, ItemKey, Element>,
+    {
+        self.storage
+            .query(query)
+            .map(|element| {
+                (
+                    element.chunk_key().into_owned(),
+                    element.item_key().into_owned(),
+                )
+            })
+            .collect()
+    }
+}